@@ -0,0 +1,571 @@
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::metafile::MetaFile;
+
+/// something to do with the redundant copies in a group of duplicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Delete,
+    Hardlink,
+    Reflink,
+}
+
+impl Action {
+    /// applies `self` to a group of duplicates, keeping the file chosen by
+    /// `keep`. See `delete_duplicates` and `hardlink_duplicates`.
+    /// ## Note:
+    /// Groups are matched by checksum, so before a redundant copy is touched,
+    /// it's compared byte for byte with the survivor. Copies which turn out
+    /// to differ are left alone, and their paths are returned with an
+    /// `io::ErrorKind::InvalidData` error, see `contents_differ`.
+    pub fn apply<'a>(
+        self,
+        group: impl IntoIterator<Item = &'a MetaFile>,
+        keep: Keep,
+        dry_run: bool,
+    ) -> Vec<(PathBuf, io::Result<()>)> {
+        let Some((survivor, redundant)) = split_survivor(group, keep) else {
+            return vec![];
+        };
+        self.apply_to(survivor, &redundant, dry_run)
+    }
+
+    /// applies `self` to each of the `redundant` copies of `survivor`, e.g.
+    /// as chosen by `split_survivor`. Returns each path which was changed
+    /// (or, when `dry_run` is set, which would have been changed) along with
+    /// the result of changing it.
+    pub fn apply_to(
+        self,
+        survivor: &MetaFile,
+        redundant: &[&MetaFile],
+        dry_run: bool,
+    ) -> Vec<(PathBuf, io::Result<()>)> {
+        for_each_redundant_file(survivor, redundant, |survivor, p| {
+            if dry_run {
+                Ok(())
+            } else {
+                self.change(survivor, p)
+            }
+        })
+    }
+
+    // makes the change `self` stands for to `p`, a redundant copy of
+    // `survivor`.
+    fn change(self, survivor: &Path, p: &Path) -> io::Result<()> {
+        match self {
+            Action::Delete => fs::remove_file(p),
+            Action::Hardlink => replace_with(p, |tmp| fs::hard_link(survivor, tmp)),
+            Action::Reflink => {
+                let permissions = fs::metadata(p)?.permissions();
+                replace_with(p, |tmp| {
+                    reflink(survivor, tmp)?;
+                    fs::set_permissions(tmp, permissions)
+                })
+            }
+        }
+    }
+}
+
+/// which file in a group of duplicates is kept when the others are acted on.
+/// Paths are compared by the path of each file, see `MetaFile::primary`, and
+/// ties go to the first file by path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Keep {
+    /// the first file by path.
+    #[default]
+    First,
+    /// the last file by path.
+    Last,
+    /// the file whose path has the fewest components.
+    ShortestPath,
+    /// the file whose path has the most components.
+    LongestPath,
+    /// the file which was modified longest ago.
+    Oldest,
+    /// the file which was modified most recently.
+    Newest,
+}
+
+impl Keep {
+    /// returns the position of the file which is kept among `members`, which
+    /// must be sorted by path, or `None` if there are none.
+    /// ## Note:
+    /// `Oldest` and `Newest` go by the modification time each file had when
+    /// it was found, see `MetaFile::mtime`. Files whose modification time
+    /// isn't known are only kept if no other file's is.
+    pub fn pick<'a>(self, members: impl IntoIterator<Item = &'a MetaFile>) -> Option<usize> {
+        let members = members.into_iter().enumerate();
+        let depth = |f: &MetaFile| f.primary().components().count();
+        // `None` sorts first, so the key is paired with whether it's missing
+        let mtime = |f: &MetaFile| (f.mtime().is_none(), f.mtime());
+        let picked = match self {
+            Keep::First => members.min_by_key(|&(idx, _)| idx),
+            Keep::Last => members.max_by_key(|&(idx, _)| idx),
+            Keep::ShortestPath => members.min_by_key(|(_, f)| depth(f)),
+            Keep::LongestPath => members.min_by_key(|(_, f)| Reverse(depth(f))),
+            Keep::Oldest => members.min_by_key(|(_, f)| mtime(f)),
+            Keep::Newest => members.min_by_key(|(_, f)| {
+                let (missing, mtime) = mtime(f);
+                (missing, Reverse(mtime.unwrap_or(SystemTime::UNIX_EPOCH)))
+            }),
+        };
+        picked.map(|(idx, _)| idx)
+    }
+}
+
+/// splits a group of duplicate files into the file which is kept, chosen by
+/// `keep`, and the redundant copies of it, sorted by path.
+pub fn split_survivor<'a>(
+    group: impl IntoIterator<Item = &'a MetaFile>,
+    keep: Keep,
+) -> Option<(&'a MetaFile, Vec<&'a MetaFile>)> {
+    let mut members: Vec<&MetaFile> = group.into_iter().collect();
+    members.sort();
+    let idx = keep.pick(members.iter().copied())?;
+    let survivor = members.remove(idx);
+    Some((survivor, members))
+}
+
+/// removes every redundant copy in a group of duplicates, keeping the file
+/// chosen by `split_survivor`. Returns each path which was removed (or, when
+/// `dry_run` is set, which would have been removed) along with the result of
+/// removing it.
+/// ## Note:
+/// All of the paths of a `MetaFile` refer to the same data, so a redundant
+/// copy is only removed by removing all of its hard links. Symlinks to a
+/// redundant copy are left in place.
+pub fn delete_duplicates<'a>(
+    group: impl IntoIterator<Item = &'a MetaFile>,
+    keep: Keep,
+    dry_run: bool,
+) -> Vec<(PathBuf, io::Result<()>)> {
+    Action::Delete.apply(group, keep, dry_run)
+}
+
+/// replaces every redundant copy in a group of duplicates with a hard link to
+/// the file chosen by `split_survivor`. Returns each path which was replaced
+/// (or, when `dry_run` is set, which would have been replaced) along with the
+/// result of replacing it.
+/// ## Note:
+/// The hard link is created next to the redundant copy and then renamed over
+/// it, so the copy is left untouched if linking fails, e.g. because the
+/// survivor is on another filesystem.
+pub fn hardlink_duplicates<'a>(
+    group: impl IntoIterator<Item = &'a MetaFile>,
+    keep: Keep,
+    dry_run: bool,
+) -> Vec<(PathBuf, io::Result<()>)> {
+    Action::Hardlink.apply(group, keep, dry_run)
+}
+
+/// replaces every redundant copy in a group of duplicates with a copy-on-write
+/// clone (reflink) of the file chosen by `split_survivor`, which shares its
+/// storage with the survivor but can be modified independently. Returns each
+/// path which was replaced (or, when `dry_run` is set, which would have been
+/// replaced) along with the result of replacing it.
+/// ## Note:
+/// Reflinks are only supported on some filesystems (e.g. btrfs, XFS, APFS).
+/// Elsewhere an error is returned and the redundant copy is left untouched;
+/// the data is never copied instead.
+pub fn reflink_duplicates<'a>(
+    group: impl IntoIterator<Item = &'a MetaFile>,
+    keep: Keep,
+    dry_run: bool,
+) -> Vec<(PathBuf, io::Result<()>)> {
+    Action::Reflink.apply(group, keep, dry_run)
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let src_file = fs::File::open(src)?;
+    let dst_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)?;
+    // SAFETY: both file descriptors are valid for the duration of the call,
+    // and FICLONE takes the source descriptor as its argument.
+    if unsafe { libc::ioctl(dst_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd()) } == 0 {
+        return Ok(());
+    }
+    let e = io::Error::last_os_error();
+    drop(dst_file);
+    let _ = fs::remove_file(dst);
+    Err(match e.raw_os_error() {
+        Some(libc::EXDEV | libc::EINVAL | libc::ENOTTY | libc::EOPNOTSUPP) => io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("reflinks are not supported here: {e}"),
+        ),
+        _ => e,
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let src = CString::new(src.as_os_str().as_bytes())?;
+    let dst = CString::new(dst.as_os_str().as_bytes())?;
+    // SAFETY: both pointers are to valid NUL-terminated strings which live
+    // for the duration of the call.
+    if unsafe { libc::clonefile(src.as_ptr(), dst.as_ptr(), 0) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reflinks are not supported on this platform",
+    ))
+}
+
+/// whether `e` is the error `Action::apply` returns for a redundant copy
+/// which turned out not to be a duplicate of the survivor after all.
+pub fn contents_differ(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::InvalidData
+}
+
+// the size of the chunks `same_contents` reads files in
+const COMPARE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// compares the contents of `a` and `b` byte for byte.
+pub fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let mut a_buf = vec![0; COMPARE_CHUNK_SIZE];
+    let mut b_buf = vec![0; COMPARE_CHUNK_SIZE];
+    loop {
+        let read = read_chunk(&mut a, &mut a_buf)?;
+        if read_chunk(&mut b, &mut b_buf)? != read || a_buf[..read] != b_buf[..read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+// fills as much of `buf` as there is left of `f`, returning how much.
+fn read_chunk(f: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match f.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn for_each_redundant_file(
+    survivor: &MetaFile,
+    redundant: &[&MetaFile],
+    mut f: impl FnMut(&Path, &Path) -> io::Result<()>,
+) -> Vec<(PathBuf, io::Result<()>)> {
+    // symlinks are never kept in place of a file
+    if survivor.files().is_empty() {
+        return vec![];
+    }
+    let survivor_path = survivor.primary();
+    let mut results = vec![];
+    for mf in redundant {
+        let Some(first) = mf.files().first() else {
+            continue;
+        };
+        // all of the paths of a metafile share their contents, so one
+        // comparison covers them all
+        let verified = match same_contents(survivor_path, first) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err((
+                io::ErrorKind::InvalidData,
+                format!(
+                    "its contents differ from {:?}, which is kept",
+                    survivor_path.as_os_str()
+                ),
+            )),
+            // so that it can't be taken for a copy which differs
+            Err(e) if contents_differ(&e) => Err((
+                io::ErrorKind::Other,
+                format!(
+                    "couldn't compare it with {:?}, which is kept: {e}",
+                    survivor_path.as_os_str()
+                ),
+            )),
+            Err(e) => Err((
+                e.kind(),
+                format!(
+                    "couldn't compare it with {:?}, which is kept: {e}",
+                    survivor_path.as_os_str()
+                ),
+            )),
+        };
+        for p in mf.files() {
+            let res = match &verified {
+                Ok(()) => f(survivor_path, p),
+                Err((kind, msg)) => Err(io::Error::new(*kind, msg.clone())),
+            };
+            results.push((p.clone(), res));
+        }
+    }
+    results
+}
+
+/// creates a replacement for `p` at a temporary path next to it using
+/// `create`, then renames the replacement over `p`.
+fn replace_with(p: &Path, create: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    let mut tmp_name = p.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".find-duplicates-tmp");
+    let tmp = p.with_file_name(tmp_name);
+    create(&tmp)?;
+    fs::rename(&tmp, p).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use indexmap::indexset;
+
+    use super::{
+        contents_differ, delete_duplicates, hardlink_duplicates, reflink_duplicates, same_contents,
+        split_survivor, Keep,
+    };
+    use crate::metafile::collect_into_metafiles;
+
+    #[test]
+    fn keep_policies() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-keep");
+        let names = ["a/b", "c/d", "k/l", "n", "y/long/x", "z"];
+        // modified in this order, oldest first
+        let by_mtime = ["c/d", "z", "a/b", "y/long/x", "n", "k/l"];
+        let epoch = SystemTime::now() - Duration::from_secs(3600);
+        for name in names {
+            let p = root.join(name);
+            fs::create_dir_all(p.parent().unwrap())?;
+            fs::write(&p, "meow")?;
+            let age = by_mtime.iter().position(|n| *n == name).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&p)?
+                .set_modified(epoch + Duration::from_secs(age as u64 * 60))?;
+        }
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            names.map(|name| root.join(name)),
+            false,
+            &mut vec![],
+        );
+        assert_eq!(metafiles.len(), 6);
+        /* test */
+        for (keep, expected) in [
+            (Keep::First, "a/b"),
+            (Keep::Last, "z"),
+            // n and z are tied, and n comes first
+            (Keep::ShortestPath, "n"),
+            (Keep::LongestPath, "y/long/x"),
+            (Keep::Oldest, "c/d"),
+            (Keep::Newest, "k/l"),
+        ] {
+            let (survivor, redundant) = split_survivor(metafiles.iter().rev(), keep).unwrap();
+            assert_eq!(survivor.primary(), &root.join(expected), "{keep:?}");
+            assert_eq!(redundant.len(), 5);
+            assert!(redundant.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        // the survivor is the file which is left in place
+        let removed = delete_duplicates(&metafiles, Keep::Newest, false);
+        assert_eq!(removed.len(), 5);
+        assert!(root.join("k/l").exists());
+        assert!(!root.join("a/b").exists());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn delete() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-delete");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        fs::write(root.join("c"), "meow")?;
+        fs::hard_link(root.join("c"), root.join("c-hardlink"))?;
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            ["a", "b", "c", "c-hardlink"].map(|name| root.join(name)),
+            false,
+            &mut vec![],
+        );
+        assert_eq!(metafiles.len(), 3);
+        /* test */
+        let removed = delete_duplicates(&metafiles, Keep::First, true);
+        assert_eq!(removed.len(), 3);
+        for name in ["a", "b", "c", "c-hardlink"] {
+            assert!(root.join(name).exists());
+        }
+        let removed = delete_duplicates(&metafiles, Keep::First, false);
+        assert_eq!(removed.len(), 3);
+        assert!(removed.iter().all(|(_, res)| res.is_ok()));
+        assert!(root.join("a").exists());
+        for name in ["b", "c", "c-hardlink"] {
+            assert!(!root.join(name).exists());
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn verifies_contents() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-verify-contents");
+        fs::create_dir(root)?;
+        // the same size, and as far as the actions know, the same checksum
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        fs::write(root.join("c"), "purr")?;
+        fs::hard_link(root.join("c"), root.join("c-hardlink"))?;
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            ["a", "b", "c", "c-hardlink"].map(|name| root.join(name)),
+            false,
+            &mut vec![],
+        );
+        /* test */
+        assert!(same_contents(&root.join("a"), &root.join("b"))?);
+        assert!(!same_contents(&root.join("a"), &root.join("c"))?);
+        for dry_run in [true, false] {
+            let removed = delete_duplicates(&metafiles, Keep::First, dry_run);
+            assert_eq!(removed.len(), 3);
+            for (p, res) in removed {
+                if p == root.join("b") {
+                    assert!(res.is_ok());
+                } else {
+                    assert!(res.is_err_and(|e| contents_differ(&e)), "{p:?}");
+                }
+            }
+        }
+        // the copy which differs is left in place, with its hard link
+        assert!(root.join("a").exists());
+        assert!(!root.join("b").exists());
+        assert_eq!(fs::read(root.join("c"))?, b"purr");
+        assert!(root.join("c-hardlink").exists());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn keeps_hardlinked_survivor() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-delete-hardlinks");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::hard_link(root.join("a"), root.join("a-hardlink"))?;
+        fs::write(root.join("b"), "meow")?;
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            ["a", "a-hardlink", "b"].map(|name| root.join(name)),
+            false,
+            &mut vec![],
+        );
+        /* test */
+        let removed = delete_duplicates(&metafiles, Keep::First, false);
+        assert_eq!(removed.len(), 1);
+        assert!(root.join("a").exists());
+        assert!(root.join("a-hardlink").exists());
+        assert!(!root.join("b").exists());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn hardlink() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-hardlink");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        fs::write(root.join("c"), "meow")?;
+        let paths = ["a", "b", "c"].map(|name| root.join(name));
+        let mut metafiles = indexset![];
+        collect_into_metafiles(&mut metafiles, paths.clone(), false, &mut vec![]);
+        assert_eq!(metafiles.len(), 3);
+        /* test */
+        hardlink_duplicates(&metafiles, Keep::First, true);
+        let mut after = indexset![];
+        collect_into_metafiles(&mut after, paths.clone(), false, &mut vec![]);
+        assert_eq!(after.len(), 3);
+        let replaced = hardlink_duplicates(&metafiles, Keep::First, false);
+        assert_eq!(replaced.len(), 2);
+        assert!(replaced.iter().all(|(_, res)| res.is_ok()));
+        // all paths now share an identifier, and so are one metafile
+        let mut after = indexset![];
+        collect_into_metafiles(&mut after, paths.clone(), false, &mut vec![]);
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].paths().len(), 3);
+        for p in &paths {
+            assert_eq!(fs::read(p)?, b"meow");
+        }
+        assert_eq!(fs::read_dir(root)?.count(), 3);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn reflink() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-reflink");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            [root.join("a"), root.join("b")],
+            false,
+            &mut vec![],
+        );
+        /* test */
+        // whether this succeeds depends on the filesystem the tests are run
+        // on, but either way nothing should be lost or left behind.
+        let replaced = reflink_duplicates(&metafiles, Keep::First, false);
+        assert_eq!(replaced.len(), 1);
+        assert_eq!(replaced[0].0, root.join("b"));
+        assert_eq!(fs::read(root.join("a"))?, b"meow");
+        assert_eq!(fs::read(root.join("b"))?, b"meow");
+        assert_eq!(fs::read_dir(root)?.count(), 2);
+        if replaced[0].1.is_ok() {
+            // a reflink is an independent file, unlike a hard link
+            let mut after = indexset![];
+            collect_into_metafiles(
+                &mut after,
+                [root.join("a"), root.join("b")],
+                false,
+                &mut vec![],
+            );
+            assert_eq!(after.len(), 2);
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::PathBuf;
+
+use indexmap::{IndexMap, IndexSet};
+use indicatif::ProgressBar;
+
+use crate::metafile::{collect_into_metafiles, FileErrors, MetaFile};
+use crate::records::invalid_data;
+use crate::scan::{canonical_file_path, checksum_files, DuplicateGroup, ScanOptions};
+
+/// a group of duplicates from an earlier report, as written by `--csv`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaselineGroup {
+    checksum: u32,
+    size: u64,
+    paths: Vec<PathBuf>, /* in the order they were listed */
+}
+
+impl BaselineGroup {
+    pub fn new(checksum: u32, size: u64, paths: Vec<PathBuf>) -> Self {
+        Self {
+            checksum,
+            size,
+            paths,
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+/// reads the groups of duplicates from a report written by `--csv`. The
+/// columns are found by name, so only the `group`, `checksum`, `size` and
+/// `path` columns are needed. A report written by `--json-stream` is an
+/// error saying so, rather than a missing column.
+pub fn read_baseline(report: impl Read) -> io::Result<Vec<BaselineGroup>> {
+    let mut report = BufReader::new(report);
+    // each line of a --json-stream report is an object
+    if report.fill_buf()?.trim_ascii_start().starts_with(b"{") {
+        return Err(invalid_data(
+            "the report is JSON, only reports written with --csv can be read",
+        ));
+    }
+    let mut reader = csv::Reader::from_reader(report);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        (headers.iter().position(|header| header == name))
+            .ok_or_else(|| invalid_data(&format!("no {name} column in the report")))
+    };
+    let (group, checksum, size, path) = (
+        column("group")?,
+        column("checksum")?,
+        column("size")?,
+        column("path")?,
+    );
+    let mut groups: IndexMap<String, BaselineGroup> = IndexMap::new();
+    for row in reader.records() {
+        let row = row?;
+        let field = |idx| {
+            row.get(idx)
+                .ok_or_else(|| invalid_data("malformed report row"))
+        };
+        let number = |idx| {
+            let field = field(idx)?;
+            field
+                .parse()
+                .map_err(|_| invalid_data("malformed report row"))
+        };
+        let checksum =
+            u32::try_from(number(checksum)?).map_err(|_| invalid_data("malformed report row"))?;
+        let size = number(size)?;
+        (groups.entry(field(group)?.to_string()))
+            .or_insert_with(|| BaselineGroup {
+                checksum,
+                size,
+                paths: vec![],
+            })
+            .paths
+            .push(PathBuf::from(field(path)?));
+    }
+    Ok(groups.into_values().collect())
+}
+
+/// the groups of a scan which weren't in a baseline, and the groups of the
+/// baseline which weren't found by the scan.
+#[derive(Debug)]
+pub struct BaselineDiff {
+    pub new: Vec<DuplicateGroup>,
+    pub gone: Vec<BaselineGroup>,
+}
+
+/// compares the groups found by a scan with those of a baseline. Two groups
+/// are the same when they share a checksum and the same set of canonical
+/// paths, see `canonical_file_path`, so a group which gained or lost a copy
+/// is both new and gone. Paths which can't be canonicalized, e.g. because
+/// the directory they were in has been removed, are compared as they are.
+pub fn diff_baseline(groups: &[DuplicateGroup], baseline: Vec<BaselineGroup>) -> BaselineDiff {
+    let keys: Vec<GroupKey> = groups
+        .iter()
+        .map(|g| group_key(g.checksum(), g.files().iter().flat_map(|f| f.paths())))
+        .collect();
+    let baseline_keys: Vec<GroupKey> = (baseline.iter())
+        .map(|g| group_key(g.checksum, &g.paths))
+        .collect();
+    let (current, previous): (HashSet<&GroupKey>, HashSet<&GroupKey>) =
+        (keys.iter().collect(), baseline_keys.iter().collect());
+    BaselineDiff {
+        new: (groups.iter().zip(&keys))
+            .filter(|(_, key)| !previous.contains(key))
+            .map(|(g, _)| g.clone())
+            .collect(),
+        gone: (baseline.into_iter().zip(&baseline_keys))
+            .filter(|(_, key)| !current.contains(key))
+            .map(|(g, _)| g)
+            .collect(),
+    }
+}
+
+// a group's checksum, along with the canonical forms of its paths.
+type GroupKey = (u32, BTreeSet<PathBuf>);
+
+fn group_key<'a>(checksum: u32, paths: impl IntoIterator<Item = &'a PathBuf>) -> GroupKey {
+    let canonical = paths
+        .into_iter()
+        .map(|p| canonical_file_path(p).unwrap_or_else(|_| p.clone()));
+    (checksum, canonical.collect())
+}
+
+/// how the files of a group from an earlier report compare with the report,
+/// see `verify_groups`. Each of the group's paths is in one of the lists, in
+/// the order the report listed them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupCheck {
+    pub unchanged: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>, /* paths whose size or checksum is no longer
+                               the group's */
+    pub gone: Vec<PathBuf>, /* paths which no longer exist */
+    pub unreadable: Vec<PathBuf>, /* paths which couldn't be stat'd or read,
+                            see `verify_groups` */
+}
+
+impl GroupCheck {
+    /// whether the group can still be acted on, i.e. whether all of its
+    /// files are still there and unchanged.
+    pub fn is_valid(&self) -> bool {
+        self.changed.is_empty() && self.gone.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+/// stats and checksums the files of each of `groups` again, to check that
+/// they're still the duplicates an earlier report found, e.g. before acting
+/// on it. Files are checksummed as a scan would, see `checksum_files`, but
+/// only the paths in the report are read. Why paths couldn't be stat'd or
+/// read is added to `errors`, except for paths which no longer exist.
+/// `progress` is incremented once per file which is read.
+pub fn verify_groups(
+    groups: &[BaselineGroup],
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    progress: &ProgressBar,
+) -> Vec<GroupCheck> {
+    groups
+        .iter()
+        .map(|group| verify_group(group, options, errors, progress))
+        .collect()
+}
+
+fn verify_group(
+    group: &BaselineGroup,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    progress: &ProgressBar,
+) -> GroupCheck {
+    let mut check = GroupCheck::default();
+    let mut same_size = vec![];
+    for p in &group.paths {
+        match fs::metadata(p) {
+            Ok(md) if md.is_file() && md.len() == group.size => same_size.push(p.clone()),
+            Ok(_) => check.changed.push(p.clone()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => check.gone.push(p.clone()),
+            Err(e) => {
+                check.unreadable.push(p.clone());
+                errors.push((p.clone(), e));
+            }
+        }
+    }
+    let mut files: IndexSet<MetaFile> = IndexSet::new();
+    let mut unreadable = vec![];
+    collect_into_metafiles(&mut files, same_size.clone(), false, &mut unreadable);
+    let files: Vec<MetaFile> = files.into_iter().collect();
+    let checksums = checksum_files(files, group.size, options, &mut unreadable, progress);
+    let unchanged: HashSet<&PathBuf> = (checksums.iter())
+        .filter(|(checksum, _)| *checksum == group.checksum)
+        .flat_map(|(_, f)| f.paths())
+        .collect();
+    let read: HashSet<&PathBuf> = checksums.iter().flat_map(|(_, f)| f.paths()).collect();
+    // the paths which were stat'd again but not read are the ones which
+    // couldn't be
+    for p in same_size {
+        if unchanged.contains(&p) {
+            check.unchanged.push(p);
+        } else if read.contains(&p) {
+            check.changed.push(p);
+        } else {
+            check.unreadable.push(p);
+        }
+    }
+    errors.append(&mut unreadable);
+    // the lists are put back in the order of the report
+    for list in [&mut check.changed, &mut check.unreadable] {
+        list.sort_by_key(|p| group.paths.iter().position(|other| other == p));
+    }
+    check
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use adler32::adler32;
+    use indicatif::ProgressBar;
+
+    use super::{diff_baseline, read_baseline, verify_groups, GroupCheck};
+    use crate::scan::{find_duplicates, ScanOptions};
+
+    #[test]
+    fn read_report() -> io::Result<()> {
+        let report = "\
+group,checksum,path,size,is_primary,is_symlink
+1,42,\"a, b\",4,true,false
+1,42,c,4,false,false
+2,7,d,5,true,false
+2,7,e,5,false,true
+";
+        let groups = read_baseline(report.as_bytes())?;
+        assert_eq!(groups.len(), 2);
+        assert_eq!((groups[0].checksum(), groups[0].size()), (42, 4));
+        assert_eq!(
+            groups[0].paths(),
+            [PathBuf::from("a, b"), PathBuf::from("c")]
+        );
+        assert_eq!((groups[1].checksum(), groups[1].size()), (7, 5));
+        assert_eq!(groups[1].paths(), [PathBuf::from("d"), PathBuf::from("e")]);
+        assert!(read_baseline("group,path\n1,a\n".as_bytes()).is_err());
+        assert!(read_baseline("group,checksum,path,size\n1,x,a,4\n".as_bytes()).is_err());
+        let json = r#"{"group":1,"checksum":42,"size":4,"files":[]}"#;
+        let error = read_baseline(json.as_bytes()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the report is JSON, only reports written with --csv can be read"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn diff() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-baseline");
+        fs::create_dir(root)?;
+        for (name, contents) in [("a", "meow"), ("b", "meow"), ("c", "nyaa!"), ("d", "nyaa!")] {
+            fs::write(root.join(name), contents)?;
+        }
+        let groups = find_duplicates(&[PathBuf::from(root)], &ScanOptions::default())?;
+        assert_eq!(groups.len(), 2);
+        let meow = adler32("meow".as_bytes())?;
+        // a and b are listed by their canonical paths, and e and f have
+        // since been removed
+        let canonical = |name| root.join(name).canonicalize().unwrap();
+        let report = format!(
+            "group,checksum,path,size,is_primary,is_symlink\n\
+            1,{meow},{},4,true,false\n\
+            1,{meow},{},4,false,false\n\
+            2,1,{},6,true,false\n\
+            2,1,{},6,false,false\n",
+            canonical("a").display(),
+            canonical("b").display(),
+            root.join("e").display(),
+            root.join("f").display(),
+        );
+        let baseline = read_baseline(report.as_bytes())?;
+        /* test */
+        let diff = diff_baseline(&groups, baseline.clone());
+        assert_eq!(diff.new.len(), 1);
+        assert_eq!(diff.new[0].files()[0].primary(), &root.join("c"));
+        assert_eq!(diff.gone, [baseline[1].clone()]);
+        // a group which lost a copy is both new and gone
+        fs::remove_file(root.join("d"))?;
+        fs::write(root.join("b2"), "meow")?;
+        let groups = find_duplicates(&[PathBuf::from(root)], &ScanOptions::default())?;
+        let diff = diff_baseline(&groups, baseline.clone());
+        assert_eq!(diff.new.len(), 1);
+        assert_eq!(diff.new[0].files().len(), 3);
+        assert_eq!(diff.gone, baseline);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn verify() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-verify");
+        fs::create_dir(root)?;
+        let files = [
+            ("a", "meow"),
+            ("b", "meow"),
+            ("c", "meow"),
+            ("d", "nyaa!"),
+            ("e", "nyaa!"),
+            ("f", "purrr!"),
+            ("g", "purrr!"),
+        ];
+        for (name, contents) in files {
+            fs::write(root.join(name), contents)?;
+        }
+        let checksum = |contents: &str| adler32(contents.as_bytes()).unwrap();
+        let mut report = String::from("group,checksum,path,size,is_primary,is_symlink\n");
+        for (idx, (name, contents)) in files.into_iter().enumerate() {
+            let group = match idx {
+                0..=2 => 1,
+                3..=4 => 2,
+                _ => 3,
+            };
+            report += &format!(
+                "{group},{},{},{},false,false\n",
+                checksum(contents),
+                root.join(name).display(),
+                contents.len()
+            );
+        }
+        let groups = read_baseline(report.as_bytes())?;
+        let verified = |errors: &mut Vec<_>| {
+            verify_groups(
+                &groups,
+                &ScanOptions::default(),
+                errors,
+                &ProgressBar::hidden(),
+            )
+        };
+        let mut errors = vec![];
+        /* test */
+        assert!(verified(&mut errors).iter().all(GroupCheck::is_valid));
+        // b keeps its size, d doesn't, and e is removed
+        fs::write(root.join("b"), "mrrp")?;
+        fs::write(root.join("d"), "nyaa!!")?;
+        fs::remove_file(root.join("e"))?;
+        let checks = verified(&mut errors);
+        assert_eq!(
+            checks[0],
+            GroupCheck {
+                unchanged: vec![root.join("a"), root.join("c")],
+                changed: vec![root.join("b")],
+                ..GroupCheck::default()
+            }
+        );
+        assert_eq!(
+            checks[1],
+            GroupCheck {
+                changed: vec![root.join("d")],
+                gone: vec![root.join("e")],
+                ..GroupCheck::default()
+            }
+        );
+        assert!(checks[2].is_valid());
+        assert!(!checks[0].is_valid() && !checks[1].is_valid());
+        // a file which is left on its own is still read
+        fs::remove_file(root.join("g"))?;
+        let checks = verified(&mut errors);
+        assert_eq!(checks[2].unchanged, [root.join("f")]);
+        assert_eq!(checks[2].gone, [root.join("g")]);
+        assert!(errors.is_empty());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
@@ -0,0 +1,82 @@
+const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// formats a number of bytes as a human readable string using binary
+/// (1024-based) units, e.g. `4.2 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// parses a number of bytes with an optional, case-insensitive binary unit
+/// suffix (`K`, `M`, `G` or `T`), e.g. `512`, `4k`, `10M`.
+pub fn parse_bytes(s: &str) -> Option<u64> {
+    let (digits, multiplier) = match s.char_indices().last()? {
+        (idx, 'k' | 'K') => (&s[..idx], 1024),
+        (idx, 'm' | 'M') => (&s[..idx], 1024u64.pow(2)),
+        (idx, 'g' | 'G') => (&s[..idx], 1024u64.pow(3)),
+        (idx, 't' | 'T') => (&s[..idx], 1024u64.pow(4)),
+        _ => (s, 1),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_bytes, parse_bytes};
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1), "1 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+    }
+
+    #[test]
+    fn unit_boundaries() {
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1024 * 1024 - 1), "1024.0 KiB");
+        assert_eq!(format_bytes(1024 * 1024), "1.0 MiB");
+        assert_eq!(format_bytes(1024 * 1024 * 1024), "1.0 GiB");
+        assert_eq!(format_bytes(4_509_715_661), "4.2 GiB");
+        assert_eq!(format_bytes(1024u64.pow(4)), "1.0 TiB");
+        assert_eq!(format_bytes(u64::MAX), "16777216.0 TiB");
+    }
+
+    #[test]
+    fn parse_plain() {
+        assert_eq!(parse_bytes("0"), Some(0));
+        assert_eq!(parse_bytes("1"), Some(1));
+        assert_eq!(parse_bytes("4096"), Some(4096));
+    }
+
+    #[test]
+    fn parse_suffixes() {
+        assert_eq!(parse_bytes("1K"), Some(1024));
+        assert_eq!(parse_bytes("1k"), Some(1024));
+        assert_eq!(parse_bytes("10M"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_bytes("2G"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_bytes("1t"), Some(1024u64.pow(4)));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_bytes(""), None);
+        assert_eq!(parse_bytes("K"), None);
+        assert_eq!(parse_bytes("-1"), None);
+        assert_eq!(parse_bytes("1.5M"), None);
+        assert_eq!(parse_bytes("12X"), None);
+        assert_eq!(parse_bytes("99999999999T"), None);
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io;
+use std::path::{self, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metafile::FileStat;
+use crate::records::{read_records, write_records};
+
+const HEADER: &str = "find-duplicates checksum cache 1";
+
+/// the size and modification time of a file, which a cached checksum is only
+/// valid for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub(crate) size: u64,
+    pub(crate) mtime: (u64, u32), /* seconds and nanoseconds since the unix
+                                  epoch */
+}
+
+impl Fingerprint {
+    /// returns `None` if the modification time isn't available or is before
+    /// the unix epoch, in which case the file's checksum isn't cached.
+    pub fn new(metadata: &Metadata) -> Option<Self> {
+        Self::from_parts(metadata.len(), metadata.modified().ok()?)
+    }
+
+    /// like `new`, for a file which was stat'd when it was found.
+    pub fn from_stat(stat: &FileStat) -> Option<Self> {
+        Self::from_parts(stat.size, stat.mtime?)
+    }
+
+    fn from_parts(size: u64, mtime: SystemTime) -> Option<Self> {
+        let mtime = mtime.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size,
+            mtime: (mtime.as_secs(), mtime.subsec_nanos()),
+        })
+    }
+}
+
+/// checksums from a previous run, keyed by absolute path.
+/// ## Note:
+/// The cache is stored as a header line followed by one record per file,
+/// each of the form `<checksum> <size> <seconds> <nanoseconds> <path>` and
+/// terminated by a NUL character, so paths may contain spaces and newlines.
+#[derive(Debug, Default)]
+pub struct ChecksumCache {
+    entries: HashMap<PathBuf, (Fingerprint, u32)>,
+}
+
+impl ChecksumCache {
+    /// reads a cache written by `save`. A missing file is an empty cache.
+    pub fn load(cache_file: impl AsRef<Path>) -> io::Result<Self> {
+        let header = [(HEADER, "not a checksum cache")];
+        let records = read_records(
+            cache_file,
+            &header,
+            "malformed cache record",
+            |[checksum, size, secs, nanos], path| {
+                let fingerprint = Fingerprint {
+                    size,
+                    mtime: (secs, u32::try_from(nanos).ok()?),
+                };
+                Some((path, (fingerprint, u32::try_from(checksum).ok()?)))
+            },
+        )?;
+        let entries = records.unwrap_or_default().into_iter().collect();
+        Ok(Self { entries })
+    }
+
+    /// writes the cache to `cache_file`, replacing it only once the new
+    /// cache has been written in full.
+    pub fn save(&self, cache_file: impl AsRef<Path>) -> io::Result<()> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(path, _)| *path);
+        let records = entries.into_iter().map(|(path, (fingerprint, checksum))| {
+            let (secs, nanos) = fingerprint.mtime;
+            let numbers = [*checksum as u64, fingerprint.size, secs, nanos as u64];
+            (numbers, path.as_path())
+        });
+        write_records(cache_file, &[HEADER], records)
+    }
+
+    /// the cached checksum of `path`, if it was cached when the file had the
+    /// same size and modification time as `fingerprint`.
+    pub fn get(&self, path: &Path, fingerprint: &Fingerprint) -> Option<u32> {
+        let (cached, checksum) = self.entries.get(&path::absolute(path).ok()?)?;
+        (cached == fingerprint).then_some(*checksum)
+    }
+
+    pub fn insert(&mut self, path: &Path, fingerprint: Fingerprint, checksum: u32) {
+        if let Ok(path) = path::absolute(path) {
+            self.entries.insert(path, (fingerprint, checksum));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{self, File};
+    use std::io;
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{ChecksumCache, Fingerprint};
+
+    fn fingerprint(p: &Path) -> io::Result<Fingerprint> {
+        Ok(Fingerprint::new(&p.metadata()?).expect("mtime after the epoch"))
+    }
+
+    #[test]
+    fn hit_miss_and_invalidation() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-cache");
+        fs::create_dir(root)?;
+        let (cached, uncached) = (root.join("cached file"), root.join("uncached"));
+        fs::write(&cached, "meow")?;
+        fs::write(&uncached, "meow")?;
+        File::options()
+            .write(true)
+            .open(&cached)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))?;
+        let mut cache = ChecksumCache::default();
+        cache.insert(&cached, fingerprint(&cached)?, 42);
+        cache.save(root.join("cache"))?;
+        /* test */
+        let cache = ChecksumCache::load(root.join("cache"))?;
+        assert_eq!(cache.len(), 1);
+        // hit
+        assert_eq!(cache.get(&cached, &fingerprint(&cached)?), Some(42));
+        // miss
+        assert_eq!(cache.get(&uncached, &fingerprint(&uncached)?), None);
+        // invalidated by a change in mtime
+        File::options()
+            .write(true)
+            .open(&cached)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(2_000_000))?;
+        assert_eq!(cache.get(&cached, &fingerprint(&cached)?), None);
+        // invalidated by a change in size, even with the same mtime
+        let edited = File::options().append(true).open(&cached)?;
+        edited.set_len(5)?;
+        edited.set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))?;
+        assert_eq!(cache.get(&cached, &fingerprint(&cached)?), None);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn missing_and_malformed() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-cache-malformed");
+        fs::create_dir(root)?;
+        fs::write(root.join("not-a-cache"), "meow")?;
+        /* test */
+        assert!(ChecksumCache::load(root.join("missing"))?.is_empty());
+        let err = ChecksumCache::load(root.join("not-a-cache")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{self, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use indicatif::ProgressBar;
+
+use crate::cache::{ChecksumCache, Fingerprint};
+use crate::metafile::{FileErrors, MetaFile};
+use crate::records::{read_records, write_records};
+use crate::scan::{find_dups, Dups, ScanOptions, SizewiseDups};
+
+const HEADER: &str = "find-duplicates checkpoint 1";
+
+/// how often `find_dups_checkpointed` saves the checkpoint, at most.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// a file of a size which has been finished, as it was when it was read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CheckpointFile {
+    fingerprint: Fingerprint,
+    group: usize,  /* which of the groups of duplicates of its size it's in,
+                   from 1, or 0 if it's in none of them */
+    checksum: u32, /* of its group, or 0 */
+}
+
+/// the groups of duplicates found by a scan so far, one size at a time, so
+/// that a scan which was stopped can carry on where it left off. See
+/// `find_dups_checkpointed`.
+/// ## Note:
+/// A checkpoint is stored as a header line and a line describing the scan
+/// it's for, see `describe_scan`, followed by one record per file of each
+/// finished size, of the form
+/// `<size> <group> <checksum> <seconds> <nanoseconds> <path>` and terminated
+/// by a NUL character, see `read_records`.
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    scan: String,
+    sizes: HashMap<u64, HashMap<PathBuf, CheckpointFile>>, /* the files of
+                                                           each finished
+                                                           size, by absolute
+                                                           primary path */
+}
+
+impl Checkpoint {
+    /// an empty checkpoint for the scan described by `scan`.
+    pub fn new(scan: String) -> Self {
+        Self {
+            scan,
+            sizes: HashMap::new(),
+        }
+    }
+
+    /// reads a checkpoint written by `save`. A missing file is an empty
+    /// checkpoint, and one written for a scan other than `scan` is an error.
+    pub fn load(checkpoint_file: impl AsRef<Path>, scan: &str) -> io::Result<Self> {
+        let header = [
+            (HEADER, "not a checkpoint"),
+            (
+                scan,
+                "the checkpoint is for different directories or options",
+            ),
+        ];
+        let records = read_records(
+            checkpoint_file,
+            &header,
+            "malformed checkpoint record",
+            |[size, group, checksum, secs, nanos], path| {
+                let file = CheckpointFile {
+                    fingerprint: Fingerprint {
+                        size,
+                        mtime: (secs, u32::try_from(nanos).ok()?),
+                    },
+                    group: usize::try_from(group).ok()?,
+                    checksum: u32::try_from(checksum).ok()?,
+                };
+                Some((size, path, file))
+            },
+        )?;
+        let mut checkpoint = Self::new(scan.to_string());
+        for (size, path, file) in records.unwrap_or_default() {
+            checkpoint.sizes.entry(size).or_default().insert(path, file);
+        }
+        Ok(checkpoint)
+    }
+
+    /// writes the checkpoint to `checkpoint_file`, replacing it only once
+    /// the new checkpoint has been written in full.
+    pub fn save(&self, checkpoint_file: impl AsRef<Path>) -> io::Result<()> {
+        let mut sizes: Vec<_> = self.sizes.iter().collect();
+        sizes.sort_by_key(|(size, _)| *size);
+        let records = sizes.into_iter().flat_map(|(size, files)| {
+            let mut files: Vec<_> = files.iter().collect();
+            files.sort_by_key(|(path, _)| *path);
+            files.into_iter().map(move |(path, file)| {
+                let (secs, nanos) = file.fingerprint.mtime;
+                let numbers = [
+                    *size,
+                    file.group as u64,
+                    file.checksum as u64,
+                    secs,
+                    nanos as u64,
+                ];
+                (numbers, path.as_path())
+            })
+        });
+        write_records(checkpoint_file, &[HEADER, &self.scan], records)
+    }
+
+    /// how many sizes have been finished.
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// records that all of `files`, which are `size` bytes long, have been
+    /// read, and that `dups` are the groups of duplicates among them. Sizes
+    /// with files whose modification time isn't available aren't recorded.
+    fn record(&mut self, size: u64, files: &HashSet<MetaFile>, dups: &Dups) {
+        let mut recorded = HashMap::with_capacity(files.len());
+        for f in files {
+            let (Some(fingerprint), Ok(path)) = (
+                Fingerprint::from_stat(f.stat()),
+                path::absolute(f.primary()),
+            ) else {
+                self.sizes.remove(&size);
+                return;
+            };
+            let group = dups
+                .iter()
+                .enumerate()
+                .find(|(_, (_, _, group))| group.contains(f));
+            let (group, checksum) =
+                group.map_or((0, 0), |(idx, (_, checksum, _))| (idx + 1, *checksum));
+            let file = CheckpointFile {
+                fingerprint,
+                group,
+                checksum,
+            };
+            recorded.insert(path, file);
+        }
+        self.sizes.insert(size, recorded);
+    }
+
+    /// the groups of duplicates among `files`, which are `size` bytes long,
+    /// if their size has been finished and they are the same files, with the
+    /// same modification times, as when it was.
+    fn resume(&self, size: u64, files: &HashSet<MetaFile>) -> Option<Dups> {
+        let recorded = self.sizes.get(&size)?;
+        if recorded.len() != files.len() {
+            return None;
+        }
+        let mut groups: HashMap<usize, (u32, HashSet<MetaFile>)> = HashMap::new();
+        for f in files {
+            let file = recorded.get(&path::absolute(f.primary()).ok()?)?;
+            if Fingerprint::from_stat(f.stat())? != file.fingerprint {
+                return None;
+            }
+            if file.group > 0 {
+                let (_, group) = groups
+                    .entry(file.group)
+                    .or_insert_with(|| (file.checksum, HashSet::new()));
+                group.insert(f.clone());
+            }
+        }
+        let dups = groups
+            .into_values()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(checksum, files)| (size, checksum, files))
+            .collect();
+        Some(dups)
+    }
+}
+
+/// describes a scan of `dirs` with `options`, as far as they change which
+/// groups of duplicates are found, so that a checkpoint is only resumed by
+/// the same scan. Paths are escaped, so the description is one line.
+pub fn describe_scan(dirs: &[PathBuf], options: &ScanOptions) -> String {
+    let dirs: Vec<PathBuf> = (dirs.iter())
+        .map(|d| path::absolute(d).unwrap_or_else(|_| d.clone()))
+        .collect();
+    format!(
+        "dirs={dirs:?} recursive={} max_depth={} follow_symlinks={} symlinks_as_duplicates={} include_hidden={} include_empty={} hash_limit={:?}",
+        options.recursive,
+        options.max_depth,
+        options.follow_symlinks,
+        options.symlinks_as_duplicates,
+        options.include_hidden,
+        options.include_empty,
+        options.hash_limit
+    )
+}
+
+/// like `find_dups`, but the groups of each size which `checkpoint` has
+/// finished, and whose files haven't changed since, are taken from it
+/// rather than read again, and each size which is read in full without any
+/// errors is added to it. `save` is called with the checkpoint at most once
+/// every `SAVE_INTERVAL`, and once no sizes are left or the scan is
+/// interrupted. The groups are the same as `find_dups` would return.
+/// ## Note:
+/// `options.limit` is applied to each size on its own, so it shouldn't be
+/// used with a checkpoint.
+pub fn find_dups_checkpointed(
+    sizewise_dups: SizewiseDups,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    mut cache: Option<&mut ChecksumCache>,
+    progress: &ProgressBar,
+    checkpoint: &mut Checkpoint,
+    mut save: impl FnMut(&Checkpoint),
+) -> Dups {
+    let mut dups = vec![];
+    let mut last_save = Instant::now();
+    for (size, files) in sizewise_dups {
+        if options.interrupt.is_set() {
+            break;
+        }
+        if let Some(resumed) = checkpoint.resume(size, &files) {
+            progress.inc(files.len() as u64);
+            dups.extend(resumed);
+            continue;
+        }
+        let error_count = errors.len();
+        let sizewise_dups = HashMap::from([(size, files.clone())]);
+        let found = find_dups(
+            sizewise_dups,
+            options,
+            errors,
+            cache.as_deref_mut(),
+            progress,
+        );
+        // files which couldn't be read, or weren't read because the scan was
+        // interrupted, are read again when the scan is resumed
+        if errors.len() == error_count && !options.interrupt.is_set() {
+            checkpoint.record(size, &files, &found);
+        }
+        dups.extend(found);
+        if last_save.elapsed() >= SAVE_INTERVAL {
+            save(checkpoint);
+            last_save = Instant::now();
+        }
+    }
+    save(checkpoint);
+    dups
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs::{self, File};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use indicatif::ProgressBar;
+
+    use super::{describe_scan, find_dups_checkpointed, Checkpoint};
+    use crate::scan::{build_file_list, find_dups, find_sizewise_dups, Dups, ScanOptions};
+
+    // each group as its size, checksum and sorted paths, in order
+    fn normalize(dups: Dups) -> Vec<(u64, u32, Vec<PathBuf>)> {
+        let mut groups: Vec<_> = dups
+            .into_iter()
+            .map(|(size, checksum, files)| {
+                let mut paths: Vec<PathBuf> = files.iter().map(|f| f.primary().clone()).collect();
+                paths.sort();
+                (size, checksum, paths)
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    #[test]
+    fn resume() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-checkpoint");
+        fs::create_dir(root)?;
+        for n in 0..6 {
+            let contents = "meow".repeat(n + 1);
+            fs::write(root.join(format!("{n}a")), &contents)?;
+            fs::write(root.join(format!("{n}b")), &contents)?;
+            fs::write(root.join(format!("{n}c")), contents.to_uppercase())?;
+        }
+        let dirs = [root.to_path_buf()];
+        let options = ScanOptions::default();
+        let scan = describe_scan(&dirs, &options);
+        let checkpoint_file = root.join("checkpoint");
+        let sizewise_dups = || -> io::Result<_> {
+            let file_list = build_file_list(&dirs, &options, &mut vec![])?;
+            Ok(find_sizewise_dups(file_list, &options, &ProgressBar::hidden()).0)
+        };
+        let progress = ProgressBar::hidden();
+        let full = normalize(find_dups(
+            sizewise_dups()?,
+            &options,
+            &mut vec![],
+            None,
+            &progress,
+        ));
+        assert_eq!(full.len(), 6);
+        /* test */
+        // half of the sizes are finished before the scan is stopped
+        let half: HashMap<_, _> = sizewise_dups()?.into_iter().take(3).collect();
+        let mut checkpoint = Checkpoint::load(&checkpoint_file, &scan)?;
+        let save = |checkpoint: &Checkpoint| checkpoint.save(&checkpoint_file).unwrap();
+        find_dups_checkpointed(
+            half,
+            &options,
+            &mut vec![],
+            None,
+            &progress,
+            &mut checkpoint,
+            save,
+        );
+        // and the rest are read once it's resumed
+        let mut checkpoint = Checkpoint::load(&checkpoint_file, &scan)?;
+        assert_eq!(checkpoint.len(), 3);
+        let progress = ProgressBar::hidden();
+        let resumed = find_dups_checkpointed(
+            sizewise_dups()?,
+            &options,
+            &mut vec![],
+            None,
+            &progress,
+            &mut checkpoint,
+            save,
+        );
+        assert_eq!(normalize(resumed), full);
+        assert_eq!(Checkpoint::load(&checkpoint_file, &scan)?.len(), 6);
+        // a finished size whose files have changed is read again
+        fs::write(root.join("0c"), "meow")?;
+        File::options()
+            .write(true)
+            .open(root.join("0c"))?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))?;
+        let mut checkpoint = Checkpoint::load(&checkpoint_file, &scan)?;
+        let resumed = find_dups_checkpointed(
+            sizewise_dups()?,
+            &options,
+            &mut vec![],
+            None,
+            &progress,
+            &mut checkpoint,
+            save,
+        );
+        let changed = normalize(resumed);
+        assert_eq!(changed.len(), 6);
+        assert!(changed.iter().any(|(_, _, paths)| paths.len() == 3));
+        // and a checkpoint for another scan isn't resumed
+        let other_scan = describe_scan(&[root.join("other")], &options);
+        assert!(Checkpoint::load(&checkpoint_file, &other_scan).is_err());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::actions::{Action, Keep};
+use crate::baseline::BaselineGroup;
+use crate::metafile::{collect_into_metafiles, FileErrors, MetaFile};
+use crate::records::{invalid_data, path_to_str};
+use crate::scan::DuplicateGroup;
+
+/// which file in a group of duplicates is kept, and which redundant copies
+/// of it an action is applied to, e.g. as chosen interactively.
+#[derive(Debug, Clone)]
+pub struct Decision {
+    checksum: u32,
+    size: u64,
+    survivor: MetaFile,
+    redundant: Vec<MetaFile>, /* sorted by path */
+}
+
+impl Decision {
+    /// keeps the file at position `survivor` in `group`, and acts on the
+    /// others. Returns `None` if there's no such file, or if it was only
+    /// found through symlinks, which are never kept in place of a file.
+    pub fn keeping(group: &DuplicateGroup, survivor: usize) -> Option<Self> {
+        let kept = group.files().get(survivor)?;
+        if kept.files().is_empty() {
+            return None;
+        }
+        let redundant = (group.files().iter().enumerate())
+            .filter(|(idx, _)| *idx != survivor)
+            .map(|(_, f)| f.clone())
+            .collect();
+        Some(Self {
+            checksum: group.checksum(),
+            size: group.size(),
+            survivor: kept.clone(),
+            redundant,
+        })
+    }
+
+    /// keeps the file of `group` which `keep` picks, see `Keep::pick`.
+    pub fn planned(group: &DuplicateGroup, keep: Keep) -> Option<Self> {
+        Self::keeping(group, keep.pick(group.files())?)
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn survivor(&self) -> &MetaFile {
+        &self.survivor
+    }
+
+    /// the redundant copies which are acted on. Copies which were only found
+    /// through symlinks are left out, as actions leave symlinks in place.
+    pub fn redundant(&self) -> impl Iterator<Item = &MetaFile> {
+        self.redundant.iter().filter(|f| !f.files().is_empty())
+    }
+
+    /// how much space acting on the redundant copies frees up.
+    pub fn reclaimable_space(&self) -> u64 {
+        self.size * self.redundant().count() as u64
+    }
+
+    /// applies `action` to the redundant copies, see `Action::apply_to`.
+    pub fn apply(&self, action: Action, dry_run: bool) -> Vec<(PathBuf, io::Result<()>)> {
+        let redundant: Vec<&MetaFile> = self.redundant().collect();
+        action.apply_to(&self.survivor, &redundant, dry_run)
+    }
+}
+
+/// a decision as it was saved by `write_decisions`, before the files it's
+/// about have been checked again, see `SavedDecision::group`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedDecision {
+    action: Action,
+    group: BaselineGroup,
+    kept: Vec<PathBuf>, /* the paths to the file which is kept */
+}
+
+impl SavedDecision {
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// the group the decision was made for, so that its files can be
+    /// checked with `verify_groups` before they're acted on.
+    pub fn group(&self) -> &BaselineGroup {
+        &self.group
+    }
+
+    /// the decision for the files as they are now, or `None` if the paths to
+    /// the file which is kept no longer lead to one file. Paths which can't
+    /// be stat'd are left out and added to `errors`.
+    pub fn resolve(&self, errors: &mut FileErrors) -> Option<Decision> {
+        let mut kept = IndexSet::new();
+        collect_into_metafiles(&mut kept, self.kept.iter().cloned(), false, errors);
+        let redundant_paths = (self.group.paths().iter())
+            .filter(|p| !self.kept.contains(p))
+            .cloned();
+        let mut redundant = IndexSet::new();
+        collect_into_metafiles(&mut redundant, redundant_paths, false, errors);
+        if kept.len() != 1 {
+            return None;
+        }
+        let survivor = kept.pop()?;
+        // a copy which has since been linked to the survivor is no longer a
+        // copy of it
+        let mut redundant: Vec<MetaFile> =
+            (redundant.into_iter()).filter(|f| *f != survivor).collect();
+        redundant.sort();
+        Some(Decision {
+            checksum: self.group.checksum(),
+            size: self.group.size(),
+            survivor,
+            redundant,
+        })
+    }
+}
+
+// how decisions name the survivor of each group, and how they name actions.
+const KEEP: &str = "keep";
+
+fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Delete => "delete",
+        Action::Hardlink => "hardlink",
+        Action::Reflink => "reflink",
+    }
+}
+
+fn parse_action(name: &str) -> Option<Action> {
+    [Action::Delete, Action::Hardlink, Action::Reflink]
+        .into_iter()
+        .find(|action| action_name(*action) == name)
+}
+
+/// writes `decisions` to `out` as CSV, with one row for each path which is
+/// kept or acted on: the checksum and size of its group, `keep` or the name
+/// of `action`, and the path. Groups are keyed by their checksum and size,
+/// and each group's files are checked against them before they're acted on
+/// again, see `SavedDecision::group`, so the rows can be reordered, and
+/// which path is kept can be changed by editing them.
+pub fn write_decisions(out: impl Write, action: Action, decisions: &[Decision]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(["checksum", "size", "decision", "path"])?;
+    for d in decisions {
+        let kept = d.survivor.files().iter().map(|p| (KEEP, p));
+        let redundant = (d.redundant().flat_map(|f| f.files())).map(|p| (action_name(action), p));
+        for (decision, p) in kept.chain(redundant) {
+            writer.write_record([
+                d.checksum.to_string(),
+                d.size.to_string(),
+                decision.to_string(),
+                path_to_str(p)?.to_string(),
+            ])?;
+        }
+    }
+    writer.flush()
+}
+
+/// reads decisions written by `write_decisions`, grouping the rows by their
+/// checksum and size. Every group needs at least one path which is kept,
+/// and every path which isn't kept has to be acted on by the same action.
+/// ## Note:
+/// Two groups of files which differ but share a checksum and size, which
+/// can happen with files compared block by block, are read back as one.
+/// Their files still check out, but such a group has more than one file to
+/// keep, so `SavedDecision::resolve` leaves it alone.
+pub fn read_decisions(report: impl Read) -> io::Result<Vec<SavedDecision>> {
+    let mut reader = csv::Reader::from_reader(report);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        (headers.iter().position(|header| header == name))
+            .ok_or_else(|| invalid_data(&format!("no {name} column in the decisions")))
+    };
+    let (checksum, size, decision, path) = (
+        column("checksum")?,
+        column("size")?,
+        column("decision")?,
+        column("path")?,
+    );
+    // each group's paths along with whether they're kept, by checksum and
+    // size
+    let mut groups: IndexMap<(u32, u64), Vec<(PathBuf, bool)>> = IndexMap::new();
+    let mut action = None;
+    for row in reader.records() {
+        let row = row?;
+        let field = |idx| {
+            row.get(idx)
+                .ok_or_else(|| invalid_data("malformed decision"))
+        };
+        let number = |idx| {
+            let field = field(idx)?;
+            field
+                .parse()
+                .map_err(|_| invalid_data("malformed decision"))
+        };
+        let checksum =
+            u32::try_from(number(checksum)?).map_err(|_| invalid_data("malformed decision"))?;
+        let size = number(size)?;
+        let kept = match field(decision)? {
+            KEEP => true,
+            name => {
+                let named = parse_action(name)
+                    .ok_or_else(|| invalid_data(&format!("unknown decision {name:?}")))?;
+                if action.is_some_and(|action| action != named) {
+                    return Err(invalid_data("the decisions are for more than one action"));
+                }
+                action = Some(named);
+                false
+            }
+        };
+        (groups.entry((checksum, size)).or_default()).push((PathBuf::from(field(path)?), kept));
+    }
+    let Some(action) = action else {
+        // with nothing to act on, there's nothing to decide
+        return Ok(vec![]);
+    };
+    let mut decisions = vec![];
+    for ((checksum, size), paths) in groups {
+        let kept: Vec<PathBuf> = (paths.iter())
+            .filter(|(_, kept)| *kept)
+            .map(|(p, _)| p.clone())
+            .collect();
+        if kept.is_empty() {
+            return Err(invalid_data("a group has no file to keep"));
+        }
+        let paths = paths.into_iter().map(|(p, _)| p).collect();
+        decisions.push(SavedDecision {
+            action,
+            group: BaselineGroup::new(checksum, size, paths),
+            kept,
+        });
+    }
+    Ok(decisions)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use indicatif::ProgressBar;
+
+    use super::{read_decisions, write_decisions, Decision};
+    use crate::actions::{Action, Keep};
+    use crate::baseline::verify_groups;
+    use crate::scan::{find_duplicates, ScanOptions};
+
+    #[test]
+    fn decisions() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-decisions");
+        fs::create_dir(root)?;
+        for (name, contents) in [
+            ("a1", "meow"),
+            ("a2", "meow"),
+            ("a3", "meow"),
+            ("b1", "nyaa!"),
+            ("b2", "nyaa!"),
+            ("c1", "purr"),
+            ("c2", "purr"),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        fs::hard_link(root.join("a2"), root.join("a2-hardlink"))?;
+        let options = ScanOptions::default();
+        let groups = find_duplicates(&[PathBuf::from(root)], &options)?;
+        assert_eq!(groups.len(), 3);
+        /* test */
+        // a2 is kept rather than a1, and c isn't decided on at all
+        let decisions: Vec<Decision> = (groups.iter())
+            .filter(|g| g.files().len() == 3)
+            .map(|g| Decision::keeping(g, 1).unwrap())
+            .chain(
+                groups
+                    .iter()
+                    .filter(|g| g.size() == 5)
+                    .map(|g| Decision::planned(g, Keep::Last).unwrap()),
+            )
+            .collect();
+        let mut saved = vec![];
+        write_decisions(&mut saved, Action::Delete, &decisions)?;
+        let saved = read_decisions(&saved[..])?;
+        assert_eq!(saved.len(), 2);
+        assert!(saved.iter().all(|d| d.action() == Action::Delete));
+        // the hard link to the survivor is kept too
+        assert_eq!(saved[0].group().paths().len(), 4);
+        // b1 changes after the decisions were saved, so b is left alone
+        fs::write(root.join("b1"), "mrrp!")?;
+        let checks = verify_groups(
+            &saved.iter().map(|d| d.group().clone()).collect::<Vec<_>>(),
+            &options,
+            &mut vec![],
+            &ProgressBar::hidden(),
+        );
+        assert!(checks[0].is_valid());
+        assert!(!checks[1].is_valid());
+        let resolved = saved[0].resolve(&mut vec![]).unwrap();
+        assert_eq!(resolved.survivor().primary(), &root.join("a2"));
+        assert_eq!(resolved.reclaimable_space(), 8);
+        for (p, res) in resolved.apply(Action::Delete, false) {
+            assert!(res.is_ok(), "{p:?}");
+        }
+        assert!(root.join("a2").exists());
+        assert!(root.join("a2-hardlink").exists());
+        assert!(!root.join("a1").exists());
+        assert!(!root.join("a3").exists());
+        for name in ["b1", "b2", "c1", "c2"] {
+            assert!(root.join(name).exists());
+        }
+        // decisions for more than one action, or with nothing to keep, are
+        // rejected
+        let mixed =
+            "checksum,size,decision,path\n1,4,keep,x\n1,4,delete,y\n2,4,keep,z\n2,4,hardlink,w\n";
+        assert!(read_decisions(mixed.as_bytes()).is_err());
+        let nothing_kept = "checksum,size,decision,path\n1,4,delete,x\n1,4,delete,y\n";
+        assert!(read_decisions(nothing_kept.as_bytes()).is_err());
+        // rows are grouped by checksum and size, whatever order they're in
+        let reordered =
+            "checksum,size,decision,path\n1,4,delete,y\n2,4,keep,z\n1,4,keep,x\n2,4,delete,w\n";
+        let reordered = read_decisions(reordered.as_bytes())?;
+        assert_eq!(reordered.len(), 2);
+        assert_eq!(
+            reordered[0].group().paths(),
+            [PathBuf::from("y"), PathBuf::from("x")]
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
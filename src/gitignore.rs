@@ -0,0 +1,225 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// the rules from a single `.gitignore` file, layered on top of the rules
+/// from the `.gitignore` files in the directories above it.
+/// ## Note:
+/// Only `.gitignore` files in and below the directory a search starts from
+/// are read. Rules from `.git/info/exclude` and the global excludes file are
+/// not applied.
+#[derive(Debug)]
+pub struct Gitignore {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+    parent: Option<Rc<Gitignore>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Rule {
+    glob: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool, /* matched against the whole path relative to the
+                    `.gitignore`, rather than just the file name */
+}
+
+impl Gitignore {
+    /// reads `dir/.gitignore`, if there is one, on top of `parent`. When
+    /// there isn't one, or it can't be read, `parent` is returned as is.
+    pub fn read(dir: &Path, parent: Option<Rc<Gitignore>>) -> Option<Rc<Gitignore>> {
+        match fs::read_to_string(dir.join(".gitignore")) {
+            Ok(contents) => Some(Rc::new(Gitignore::parse(dir, &contents, parent))),
+            Err(_) => parent,
+        }
+    }
+
+    pub fn parse(dir: impl AsRef<Path>, contents: &str, parent: Option<Rc<Gitignore>>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            rules: contents.lines().filter_map(Rule::parse).collect(),
+            parent,
+        }
+    }
+
+    /// whether `path` is ignored. The last rule which matches `path` decides,
+    /// and rules from deeper `.gitignore` files take precedence.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(&self.dir).ok().map(|relative| {
+            let components: Vec<_> = relative.iter().map(|c| c.to_string_lossy()).collect();
+            components.join("/").chars().collect::<Vec<char>>()
+        });
+        let decided = relative.and_then(|relative| {
+            self.rules
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(&relative, is_dir))
+        });
+        match decided {
+            Some(rule) => !rule.negated,
+            None => self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_ignored(path, is_dir)),
+        }
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        Some(Self {
+            glob: line.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &[char], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            return glob_matches(&self.glob, relative);
+        }
+        let name_start = relative
+            .iter()
+            .rposition(|c| *c == '/')
+            .map_or(0, |idx| idx + 1);
+        glob_matches(&self.glob, &relative[name_start..])
+    }
+}
+
+/// matches a gitignore style glob against a `/` separated path. `*` and `?`
+/// don't match `/`, while `**` matches across directories.
+fn glob_matches(glob: &[char], s: &[char]) -> bool {
+    match glob {
+        [] => s.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob_matches(rest, s)
+                || s.iter()
+                    .enumerate()
+                    .any(|(idx, c)| *c == '/' && glob_matches(rest, &s[idx + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=s.len()).any(|idx| glob_matches(rest, &s[idx..])),
+        ['*', rest @ ..] => {
+            let segment_len = s.iter().position(|c| *c == '/').unwrap_or(s.len());
+            (0..=segment_len).any(|idx| glob_matches(rest, &s[idx..]))
+        }
+        ['?', rest @ ..] => s.first().is_some_and(|c| *c != '/') && glob_matches(rest, &s[1..]),
+        ['[', class @ ..] => match class.iter().skip(1).position(|c| *c == ']') {
+            Some(end) => {
+                let (class, rest) = (&class[..end + 1], &class[end + 2..]);
+                s.first()
+                    .is_some_and(|c| *c != '/' && class_matches(class, *c))
+                    && glob_matches(rest, &s[1..])
+            }
+            // an unclosed `[` is matched literally
+            None => s.first() == Some(&'[') && glob_matches(class, &s[1..]),
+        },
+        ['\\', c, rest @ ..] | [c, rest @ ..] => {
+            s.first() == Some(c) && glob_matches(rest, &s[1..])
+        }
+    }
+}
+
+/// matches a character class such as `abc`, `a-z` or `!0-9`, without its
+/// brackets.
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, mut class) = match class {
+        ['!' | '^', rest @ ..] if !rest.is_empty() => (true, rest),
+        _ => (false, class),
+    };
+    let mut found = false;
+    while !class.is_empty() {
+        match class {
+            [low, '-', high, rest @ ..] => {
+                found |= (*low..=*high).contains(&c);
+                class = rest;
+            }
+            [only, rest @ ..] => {
+                found |= *only == c;
+                class = rest;
+            }
+            [] => unreachable!(),
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use super::Gitignore;
+
+    fn ignored(gitignore: &Gitignore, path: &str) -> bool {
+        gitignore.is_ignored(&Path::new("repo").join(path), path.ends_with('/'))
+    }
+
+    #[test]
+    fn names_and_paths() {
+        let gitignore = Gitignore::parse(
+            "repo",
+            "# build output\ntarget/\n*.o\n/root.txt\ndocs/*.html\n\n",
+            None,
+        );
+        assert!(ignored(&gitignore, "target/"));
+        assert!(ignored(&gitignore, "nested/target/"));
+        assert!(!ignored(&gitignore, "target"));
+        assert!(ignored(&gitignore, "main.o"));
+        assert!(ignored(&gitignore, "src/main.o"));
+        assert!(ignored(&gitignore, "root.txt"));
+        assert!(!ignored(&gitignore, "src/root.txt"));
+        assert!(ignored(&gitignore, "docs/index.html"));
+        assert!(!ignored(&gitignore, "docs/api/index.html"));
+        assert!(!ignored(&gitignore, "# build output"));
+    }
+
+    #[test]
+    fn wildcards() {
+        let gitignore = Gitignore::parse("repo", "a/**/z\nlog?.txt\n[abc]-[0-9].bin\n", None);
+        assert!(ignored(&gitignore, "a/z"));
+        assert!(ignored(&gitignore, "a/b/c/z"));
+        assert!(!ignored(&gitignore, "b/a/z"));
+        assert!(ignored(&gitignore, "log1.txt"));
+        assert!(!ignored(&gitignore, "log10.txt"));
+        assert!(ignored(&gitignore, "b-7.bin"));
+        assert!(!ignored(&gitignore, "d-7.bin"));
+        assert!(!ignored(&gitignore, "b-x.bin"));
+    }
+
+    #[test]
+    fn negation_and_layering() {
+        let root = Rc::new(Gitignore::parse("repo", "*.log\n!keep.log\n", None));
+        assert!(ignored(&root, "a.log"));
+        assert!(!ignored(&root, "keep.log"));
+        let nested = Gitignore::parse(
+            Path::new("repo").join("sub"),
+            "!a.log\nkeep.log\n",
+            Some(root),
+        );
+        assert!(!ignored(&nested, "sub/a.log"));
+        assert!(ignored(&nested, "sub/keep.log"));
+        assert!(ignored(&nested, "b.log"));
+        assert!(!ignored(&nested, "keep.log"));
+    }
+}
@@ -0,0 +1,52 @@
+#[cfg(test)]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// a flag which asks a scan to stop starting new work, e.g. once Ctrl-C is
+/// pressed. Clones share the flag, so one can be set from a signal handler
+/// while the scan checks another.
+/// ## Note:
+/// Work which has already started is finished, so whatever a scan returns
+/// once it's interrupted is correct, but is missing whatever it didn't get
+/// to.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    flag: Arc<AtomicBool>,
+    #[cfg(test)]
+    checks_left: Option<Arc<AtomicUsize>>, /* sets the flag once it has been
+                                           checked this many times, see
+                                           `Interrupt::after` */
+}
+
+impl Interrupt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// an interrupt which is set by the time it has been checked `checks`
+    /// times, so that scans can be interrupted at a given point in tests.
+    #[cfg(test)]
+    pub fn after(checks: usize) -> Self {
+        Self {
+            checks_left: Some(Arc::new(AtomicUsize::new(checks))),
+            ..Self::default()
+        }
+    }
+
+    pub fn set(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_set(&self) -> bool {
+        #[cfg(test)]
+        if let Some(checks_left) = &self.checks_left {
+            let counted = checks_left
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+            if counted.is_err() {
+                self.set();
+            }
+        }
+        self.flag.load(Ordering::Relaxed)
+    }
+}
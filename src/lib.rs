@@ -1,4 +1,20 @@
-#![feature(windows_by_handle)]
-
+pub mod actions;
+pub mod baseline;
+pub mod byte_size;
+pub mod cache;
+pub mod checkpoint;
+pub mod decisions;
+pub mod gitignore;
+pub mod interrupt;
 pub mod metafile;
+pub mod mtime;
+pub mod open_files;
+pub mod pattern;
+pub mod read_stats;
+pub mod records;
 pub mod recursive_dir_reader;
+pub mod scan;
+
+pub use scan::{
+    find_duplicates, iter_duplicates, DuplicateGroup, Duplicates, ScanOptions, SortOrder,
+};
@@ -1,17 +1,36 @@
-use find_duplicates::metafile::collect_into_metafiles;
-use find_duplicates::metafile::MetaFile;
-use find_duplicates::recursive_dir_reader::RecReadDir;
-use indexmap::indexset;
+use find_duplicates::actions::{contents_differ, split_survivor, Action, Keep};
+use find_duplicates::baseline::{
+    diff_baseline, read_baseline, verify_groups, BaselineDiff, BaselineGroup, GroupCheck,
+};
+use find_duplicates::byte_size::{format_bytes, parse_bytes};
+use find_duplicates::cache::ChecksumCache;
+use find_duplicates::checkpoint::{describe_scan, find_dups_checkpointed, Checkpoint};
+use find_duplicates::decisions::{read_decisions, write_decisions, Decision, SavedDecision};
+use find_duplicates::interrupt::Interrupt;
+use find_duplicates::metafile::{FileErrors, MetaFile};
+use find_duplicates::mtime::parse_time;
+use find_duplicates::open_files::OpenFileLimit;
+use find_duplicates::pattern::{read_patterns, Pattern};
+use find_duplicates::read_stats::ReadCount;
+use find_duplicates::records::path_to_str;
+use find_duplicates::scan::{
+    self, add_to_file_list, analyze_sizes, canonical_file_path, clamp_block_size, cross_set_groups,
+    dedup_target_dirs, find_dups, find_dups_streamed, find_namewise_dups, find_probable_dups,
+    parse_extensions, read_path_list, relative_path, resolve_target_dir, side_of, sort_groups,
+    sorted_groups, split_off_sampled, Digest, NamewiseDups, ProbableGroup, Side, SizeAnalysis,
+    SizeGrouper, SizewiseDups, SAMPLE_BLOCK_SIZE,
+};
+use find_duplicates::{DuplicateGroup, ScanOptions, SortOrder};
 use indexmap::IndexSet;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{debug, error, info, warn, Level, LevelFilter};
 
-use std::collections::{HashMap, HashSet};
 use std::env;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::process;
-
-use adler32::adler32;
-
-use rayon::prelude::*;
+use std::time::SystemTime;
 
 fn usage(application_name: &str) {
     println!("USAGE: {} [flags] <input>", application_name);
@@ -19,234 +38,2545 @@ fn usage(application_name: &str) {
     println!("    -r, --recursive      include files in subdirectories,");
     println!("                         search recursively.");
     println!();
+    println!("    --max-depth <n>      when searching recursively, do not");
+    println!("                         descend more than <n> directories");
+    println!("                         below the input directories.");
+    println!();
+    println!("    --follow-symlinks    when searching recursively, descend");
+    println!("                         into symlinked directories. each");
+    println!("                         directory is only searched once.");
+    println!("                         <input> directories which are");
+    println!("                         symlinks are always followed, and");
+    println!("                         their files are listed under the");
+    println!("                         directory they point to.");
+    println!();
+    println!("    --symlinks-as-duplicates");
+    println!("                         list symlinks to duplicates as");
+    println!("                         aliases of them. symlinks are never");
+    println!("                         read, and are otherwise left out.");
+    println!();
+    println!("    --one-file-system    when searching recursively, don't");
+    println!("                         descend into directories on other");
+    println!("                         filesystems, such as mount points.");
+    println!();
+    println!("    --respect-gitignore  skip files and directories which are");
+    println!("                         ignored by .gitignore files in the");
+    println!("                         input directories, and .git");
+    println!("                         directories.");
+    println!();
+    println!("    --ignore-case        take paths which only differ in case");
+    println!("                         to be the same path, and only search");
+    println!("                         the first of them to be found. for");
+    println!("                         case-insensitive filesystems.");
+    println!();
+    println!("    --verify-links       stat files found through more than");
+    println!("                         one path again, and compare paths");
+    println!("                         which turn out to be different files");
+    println!("                         separately, with a warning. for");
+    println!("                         filesystems which report file");
+    println!("                         identifiers inconsistently.");
+    println!();
+    println!("    --limit <n>          stop once <n> groups of duplicates");
+    println!("                         have been found. which groups those");
+    println!("                         are depends on the order files are");
+    println!("                         compared in, so they're some of the");
+    println!("                         duplicates, not the biggest ones.");
+    println!();
+    println!("    --no-size-filter     checksum every file in full, even ones");
+    println!("                         which don't share a size with any");
+    println!("                         other, and match files up across");
+    println!("                         sizes, to check that grouping files");
+    println!("                         by size first doesn't hide any");
+    println!("                         duplicates. much slower, since every");
+    println!("                         file is read. cannot be used with");
+    println!("                         --sample or --checkpoint.");
+    println!();
+    println!("    --threads <n>        calculate checksums using <n> threads.");
+    println!("                         1 reads files one at a time, which");
+    println!("                         can be faster on spinning disks. 0,");
+    println!("                         the default, uses one per cpu core.");
+    println!();
+    println!("    --max-open-files <n> open at most <n> files at once while");
+    println!("                         calculating checksums, whatever the");
+    println!("                         number of threads. by default, a");
+    println!("                         quarter of the limit on open files,");
+    println!("                         up to 256.");
+    println!();
+    println!("    --mmap               memory map files to calculate their");
+    println!("                         checksums instead of reading them,");
+    println!("                         which can be faster on fast disks.");
+    println!("                         the scan may crash if a file is");
+    println!("                         truncated while it is mapped.");
+    println!();
+    println!("    --block-size <size>  read files <size> bytes at a time, 64K");
+    println!("                         by default, or 1M for big files which");
+    println!("                         are compared block by block. bigger");
+    println!("                         blocks can be faster on spinning");
+    println!("                         disks. must be a power of two, and is");
+    println!("                         kept between 4K and 64M.");
+    println!();
+    println!("    --hash-limit <size>  only compare the first <size> bytes");
+    println!("                         of each file. files which match up");
+    println!("                         to there are listed as duplicates,");
+    println!("                         however they differ after it.");
+    println!("                         cannot be used with actions or");
+    println!("                         --cache.");
+    println!();
+    println!("    --sample             only read the first, middle and last");
+    println!("                         64 KiB of files of 1 MiB or more,");
+    println!("                         and list the ones which match as");
+    println!("                         probable duplicates. much faster");
+    println!("                         for big files, but files which only");
+    println!("                         differ elsewhere are listed too.");
+    println!("                         cannot be used with actions, -0 or");
+    println!("                         -u.");
+    println!();
+    println!("    --from-file <file>   also search the files listed in");
+    println!("                         <file>, one path per line. use - to");
+    println!("                         read the list from stdin.");
+    println!();
+    println!("    --from-file0 <file>  like --from-file, but the paths are");
+    println!("                         separated by NUL characters.");
+    println!();
+    println!("    --cache <file>       remember checksums in <file>, and");
+    println!("                         reuse them for files whose size and");
+    println!("                         modification time are unchanged.");
+    println!("                         <file> is created if it is missing.");
+    println!();
+    println!("    --checkpoint <file>  save the duplicates found so far to");
+    println!("                         <file> every so often, and when the");
+    println!("                         scan stops. a scan of the same");
+    println!("                         directories with the same options");
+    println!("                         carries on from <file> rather than");
+    println!("                         reading unchanged files again.");
+    println!();
     println!("    -v, --verbose        enable progress bars and other");
     println!("                         extra output. cannot be used with");
     println!("                         -q, --quiet.");
     println!();
+    println!("    --progress-interval <ms>");
+    println!("                         redraw progress at most once every");
+    println!("                         <ms> milliseconds, 100 by default.");
+    println!("                         progress is redrawn at least once");
+    println!("                         a second.");
+    println!();
     println!("    -q, --quiet          disable all non-essential output,");
     println!("                         good for redirecting to files or");
     println!("                         piping to other programs. cannot");
     println!("                         be used with -v, --verbose");
     println!();
+    println!("    -0, --print0         print the paths of redundant copies,");
+    println!("                         separated by NUL characters, for");
+    println!("                         use with `xargs -0`. the file in");
+    println!("                         each group which --keep picks isn't");
+    println!("                         printed. implies -q, --quiet.");
+    println!();
+    println!("    --csv                print one row per path to a duplicate,");
+    println!("                         as csv with the columns group,");
+    println!("                         checksum, path, size, is_primary (the");
+    println!("                         file which is kept) and is_symlink.");
+    println!("                         groups are numbered from 1 in the");
+    println!("                         order they are listed in. implies");
+    println!("                         -q, --quiet. paths which aren't");
+    println!("                         valid UTF-8 are an error.");
+    println!();
+    println!("    --json-stream        print each group of duplicates as");
+    println!("                         soon as it's found, as one line of");
+    println!("                         JSON with the checksum, size and");
+    println!("                         files of the group, which have the");
+    println!("                         path, is_primary and is_symlink of");
+    println!("                         the --csv columns. groups are");
+    println!("                         numbered from 1 in the order they");
+    println!("                         are found. implies -q, --quiet.");
+    println!("                         cannot be used with actions, -u,");
+    println!("                         --sample or --baseline.");
+    println!();
+    println!("    --paths-only[=remove|keep]");
+    println!("                         print only paths, one per line: the");
+    println!("                         redundant copies in each group");
+    println!("                         (remove, the default), or the file");
+    println!("                         which --keep picks (keep). implies");
+    println!("                         -q, --quiet.");
+    println!();
+    println!("    --baseline <file>    only list the groups of duplicates");
+    println!("                         which aren't in <file>, an earlier");
+    println!("                         report written with --csv, and the");
+    println!("                         ones in it which are gone. groups");
+    println!("                         are the same when their checksums");
+    println!("                         and paths are. --json-stream");
+    println!("                         reports can't be read back.");
+    println!();
+    println!("    --set-a <dir>        compare the files in <dir> against");
+    println!("    --set-b <dir>        the ones in the directories given");
+    println!("                         with the other flag, and only list");
+    println!("                         groups of duplicates with files on");
+    println!("                         both sides. each file is marked A or");
+    println!("                         B. both can be repeated, and replace");
+    println!("                         <input>. cannot be used with");
+    println!("                         actions, -0, -u, --csv, --sample or");
+    println!("                         --baseline.");
+    println!();
+    println!("    --bench              print how long each phase of the");
+    println!("                         scan took to stderr, one line per");
+    println!("                         phase, along with how many files");
+    println!("                         and bytes it went through. the");
+    println!("                         compare, hash and sample phases");
+    println!("                         count what was actually read, and");
+    println!("                         --apply-decisions has a verify");
+    println!("                         phase.");
+    println!();
+    println!("    --analyze            only group the files by size, and");
+    println!("                         print how many files and bytes would");
+    println!("                         be checksummed, by size, along with");
+    println!("                         the largest groups. no files are");
+    println!("                         read, so it's quick to check whether");
+    println!("                         --hash-limit or --sample would help.");
+    println!();
+    println!("    --json               print --analyze as JSON.");
+    println!();
+    println!("    --summary-only       only print the number of duplicates");
+    println!("                         and how much space they take up,");
+    println!("                         without listing them.");
+    println!();
+    println!("    --all                does nothing, every group of");
+    println!("                         duplicates is listed unless");
+    println!("                         --summary-only is given. kept so");
+    println!("                         that scripts which pass it still");
+    println!("                         work.");
+    println!();
+    println!("    --sort <order>       list groups of duplicates by size,");
+    println!("                         the default, which puts the ones");
+    println!("                         which take up the most space first,");
+    println!("                         by count, which puts the ones with");
+    println!("                         the most files first, or by path.");
+    println!();
+    println!("    --decimal-checksums  list checksums as decimal numbers, as");
+    println!("                         earlier versions did, rather than as");
+    println!("                         e.g. adler32:0a1b2c3d.");
+    println!();
+    println!("    --color <when>       color the list of duplicates: auto,");
+    println!("                         the default, when printing to a");
+    println!("                         terminal, always, or never.");
+    println!();
+    println!("    --output-relative    print paths relative to the input");
+    println!("                         directory they were found in, e.g.");
+    println!("                         2023/a.jpg for photos/2023/a.jpg");
+    println!("                         found in photos. the first matching");
+    println!("                         directory is used. cannot be used");
+    println!("                         with actions, --annotate or");
+    println!("                         --set-a and --set-b.");
+    println!();
+    println!("    --annotate           mark each duplicate with whether");
+    println!("                         the others are 'inside', i.e. in");
+    println!("                         its directory or a subdirectory of");
+    println!("                         it, or 'outside' of it.");
+    println!();
     println!("    -u, --unique         return files that are unique instead");
     println!("                         of files that are duplicates.");
     println!();
+    println!("    --min-size <size>    ignore files smaller than <size>.");
+    println!("                         <size> is a number of bytes and may");
+    println!("                         end with one of K, M, G or T.");
+    println!();
+    println!("    --max-size <size>    ignore files larger than <size>.");
+    println!();
+    println!("    --newer-than <time>  ignore files last modified before");
+    println!("                         <time>. <time> is either a date,");
+    println!("                         e.g. 2024-01-01 (midnight UTC), or");
+    println!("                         how long ago, e.g. 7d. s, m, h, d");
+    println!("                         and w are seconds to weeks.");
+    println!();
+    println!("    --older-than <time>  ignore files last modified at or");
+    println!("                         after <time>.");
+    println!();
+    println!("    --include-empty      include empty files, which are");
+    println!("                         skipped by default since they are");
+    println!("                         all duplicates of each other.");
+    println!();
+    println!("    --include-hidden     include files and directories whose");
+    println!("                         names start with a `.`, which are");
+    println!("                         skipped by default.");
+    println!();
+    println!("    --exclude <pattern>  skip files and directories whose path");
+    println!("                         matches <pattern>. `*` matches any");
+    println!("                         characters and `?` matches one. a");
+    println!("                         pattern without a path separator");
+    println!("                         is also matched against file names.");
+    println!("                         can be given more than once.");
+    println!();
+    println!("    --exclude-from <file>");
+    println!("                         like --exclude, for each pattern in");
+    println!("                         <file>, one per line. blank lines and");
+    println!("                         lines starting with # are skipped.");
+    println!();
+    println!("    --ext <list>         only search files with one of the");
+    println!("                         comma separated extensions in");
+    println!("                         <list>, e.g. jpg,png. case is");
+    println!("                         ignored.");
+    println!();
+    println!("    --exclude-ext <list> skip files with one of the comma");
+    println!("                         separated extensions in <list>.");
+    println!();
+    println!("    --delete             in each group of duplicates, keep");
+    println!("                         one file and delete the others.");
+    println!();
+    println!("    --hardlink           in each group of duplicates, keep");
+    println!("                         one file and replace the others");
+    println!("                         with hard links to it.");
+    println!();
+    println!("    --reflink            in each group of duplicates, keep");
+    println!("                         one file and replace the others");
+    println!("                         with copy-on-write clones of it.");
+    println!("                         only some filesystems support this,");
+    println!("                         e.g. btrfs, XFS and APFS.");
+    println!();
+    println!("    --keep <policy>      which file in each group is kept:");
+    println!("                         first or last by path (first by");
+    println!("                         default), shortest-path or");
+    println!("                         longest-path by number of");
+    println!("                         directories, or oldest or newest by");
+    println!("                         modification time. ties go to the");
+    println!("                         first by path.");
+    println!();
+    println!("    --dry-run            print what --delete, --hardlink or");
+    println!("                         --reflink would do without changing");
+    println!("                         anything.");
+    println!();
+    println!("    -y, --yes            act without asking for confirmation.");
+    println!("                         otherwise, the planned changes are");
+    println!("                         listed and only made once yes is");
+    println!("                         typed, which needs stdin to be a");
+    println!("                         terminal.");
+    println!();
+    println!("    --interactive        ask which file to keep in each group");
+    println!("                         of duplicates rather than going by");
+    println!("                         --keep, or whether to leave the group");
+    println!("                         alone. needs stdin to be a terminal.");
+    println!();
+    println!("    --save-decisions <file>");
+    println!("                         save which file is kept in each group");
+    println!("                         and what's done with the others to");
+    println!("                         <file>, e.g. with --interactive and");
+    println!("                         --dry-run to go through the groups");
+    println!("                         once and act on them later.");
+    println!();
+    println!("    --apply-decisions <file>");
+    println!("                         act on the decisions saved in <file>");
+    println!("                         rather than searching for duplicates.");
+    println!("                         the files of each group are read");
+    println!("                         again first, and groups whose files");
+    println!("                         have changed are left alone.");
+    println!();
+    println!("    --report-duplicate-names-only");
+    println!("                         report files which share a name,");
+    println!("                         ignoring their contents. no");
+    println!("                         checksums are calculated.");
+    println!();
     println!("    -h, --help           print this message.");
     println!();
     println!("  and where <input> is one or more paths to directories.");
+    println!("  <input> can be left out when --from-file is given.");
+    println!("  short flags can be combined, e.g. -rq for -r -q, and flags");
+    println!("  which take a value can also be given as --flag=value, e.g.");
+    println!("  --min-size=1M. everything after -- is an <input>, even if");
+    println!("  it starts with -.");
+    println!();
+    println!("   or: {} verify <report>", application_name);
+    println!("  to check that the groups of duplicates in <report>, written");
+    println!("  earlier with --csv, are still duplicates before acting on");
+    println!("  them. each file is read again, and files which have changed");
+    println!("  or are gone are listed. --json-stream reports can't be read");
+    println!("  back.");
+    println!();
+    println!("EXIT STATUS:");
+    println!("  0 if no duplicates were found, 1 if duplicates were found, and");
+    println!("  2 if an error stopped the search, e.g. an invalid flag.");
+    println!("  with --report-duplicate-names-only, duplicates are files which");
+    println!("  share a name. with --baseline, 1 means that groups are new or");
+    println!("  gone since the baseline. with verify, 1 means that groups are");
+    println!("  no longer duplicates. with --apply-decisions, 1 means that");
+    println!("  groups had changed and were left alone. with --analyze, 0");
+    println!("  unless there was an error.");
+    println!("  130 if the search was interrupted with Ctrl-C. the duplicates");
+    println!("  found by then are listed, but no action is taken. pressing");
+    println!("  Ctrl-C again quits straight away.");
+    println!();
+    println!("ENVIRONMENT:");
+    println!("  RUST_LOG overrides how much is logged to stderr: error, warn");
+    println!("  (-q), info (the default), debug (-v) or trace.");
 }
 
+// exit codes, so that scripts can tell whether duplicates were found without
+// parsing the output
+const EXIT_NO_DUPLICATES: i32 = 0;
+const EXIT_DUPLICATES: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+// as a shell reports a process killed by SIGINT
+const EXIT_INTERRUPTED: i32 = 130;
+
+fn exit_code(duplicate_count: usize) -> i32 {
+    if duplicate_count == 0 {
+        EXIT_NO_DUPLICATES
+    } else {
+        EXIT_DUPLICATES
+    }
+}
+
+// which paths of each group of duplicates `--paths-only` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathsOnly {
+    Remove, /* the redundant copies */
+    Keep,   /* the file which is kept */
+}
+
+// whether to color the list of duplicates, see `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => atty::is(Stream::Stdout),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+// ansi escape codes for `paint`
+const HEADER_STYLE: &str = "\x1b[1;36m";
+const KEPT_STYLE: &str = "\x1b[32m";
+const REDUNDANT_STYLE: &str = "\x1b[33m";
+const SYMLINK_STYLE: &str = "\x1b[2m";
+const RESET_STYLE: &str = "\x1b[0m";
+
+// wraps `text` in `style` when `color` is set, and leaves it as is otherwise.
+fn paint(text: impl std::fmt::Display, style: &str, color: bool) -> String {
+    if color {
+        format!("{style}{text}{RESET_STYLE}")
+    } else {
+        text.to_string()
+    }
+}
+
+// how many of the largest groups of files with equal sizes --analyze lists.
+const ANALYZE_GROUP_LIMIT: usize = 10;
+
 #[derive(Debug)]
 struct Options {
     target_dirs: Vec<PathBuf>,
+    path_lists: Vec<(String, u8)>, /* files listing paths to search, and the
+                                   byte which separates the paths */
     verbose: bool,
-    recursive: bool,
     quiet: bool,
+    progress_interval: u64, /* the minimum number of milliseconds between
+                            progress redraws */
     unique: bool,
+    print0: bool,
+    csv: bool,
+    json_stream: bool,
+    paths_only: Option<PathsOnly>,
+    annotate: bool,
+    output_relative: bool,
+    decimal_checksums: bool,
+    color: ColorChoice,
+    sort: SortOrder,
+    summary_only: bool,
+    analyze: bool,
+    json: bool,
+    bench: bool,
+    names_only: bool,
+    action: Option<Action>,
+    dry_run: bool,
+    yes: bool,
+    interactive: bool,
+    save_decisions: Option<PathBuf>,
+    apply_decisions: Option<PathBuf>,
+    keep: Keep,
+    sample: bool,
+    cache_file: Option<PathBuf>,
+    checkpoint: Option<PathBuf>,
+    baseline: Option<PathBuf>,
+    block_size: Option<u64>, /* as given, before it's clamped into
+                             `scan.block_size` */
+    set_a: Vec<PathBuf>,
+    set_b: Vec<PathBuf>,
+    scan: ScanOptions,
 }
 
 impl Options {
     fn default() -> Options {
         Options {
             target_dirs: Vec::new(),
+            path_lists: Vec::new(),
             verbose: false,
             quiet: false,
-            recursive: false,
+            progress_interval: 100,
             unique: false,
+            print0: false,
+            csv: false,
+            json_stream: false,
+            paths_only: None,
+            annotate: false,
+            output_relative: false,
+            decimal_checksums: false,
+            color: ColorChoice::Auto,
+            sort: SortOrder::Size,
+            summary_only: false,
+            analyze: false,
+            json: false,
+            bench: false,
+            names_only: false,
+            action: None,
+            dry_run: false,
+            yes: false,
+            interactive: false,
+            save_decisions: None,
+            apply_decisions: None,
+            keep: Keep::First,
+            sample: false,
+            cache_file: None,
+            checkpoint: None,
+            baseline: None,
+            block_size: None,
+            set_a: Vec::new(),
+            set_b: Vec::new(),
+            scan: ScanOptions::default(),
         }
     }
 }
 
+// the short flags, none of which take a value, so they can be combined.
+const SHORT_FLAGS: &str = "vqr0huy";
+
+// the long flags which take a value, which can also be given as
+// --flag=value.
+const VALUE_FLAGS: [&str; 27] = [
+    "--block-size",
+    "--hash-limit",
+    "--limit",
+    "--threads",
+    "--max-open-files",
+    "--progress-interval",
+    "--max-depth",
+    "--sort",
+    "--color",
+    "--keep",
+    "--min-size",
+    "--max-size",
+    "--newer-than",
+    "--older-than",
+    "--from-file",
+    "--from-file0",
+    "--ext",
+    "--exclude-ext",
+    "--cache",
+    "--checkpoint",
+    "--save-decisions",
+    "--apply-decisions",
+    "--baseline",
+    "--set-a",
+    "--set-b",
+    "--exclude",
+    "--exclude-from",
+];
+
+// splits combined short flags and --flag=value into the arguments
+// `parse_args` matches on, e.g. `-rq --sort=path` into `-r -q --sort path`.
+// Anything which isn't one of those, the values of flags, and everything
+// after -- are left as they are.
+fn split_args(mut args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut res = vec![];
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            res.push(arg);
+            res.extend(args);
+            break;
+        }
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            res.push(arg);
+            res.extend(args.next());
+            continue;
+        }
+        let value_flag = arg
+            .split_once('=')
+            .filter(|(flag, _)| VALUE_FLAGS.contains(flag));
+        if let Some((flag, value)) = value_flag {
+            res.push(flag.to_string());
+            res.push(value.to_string());
+        } else if arg.len() > 2
+            && arg.starts_with('-')
+            && !arg.starts_with("--")
+            && arg[1..].chars().all(|c| SHORT_FLAGS.contains(c))
+        {
+            res.extend(arg[1..].chars().map(|c| format!("-{c}")));
+        } else {
+            res.push(arg);
+        }
+    }
+    res
+}
+
+// input directories which are symlinks are resolved, so that the paths found
+// in them start with the directory they're listed under.
+fn add_target_dir(program_name: &str, res: &mut Options, arg: &str) {
+    let maybe_path = PathBuf::from(arg);
+    if maybe_path.is_dir() {
+        res.target_dirs.push(resolved_dir(&maybe_path));
+    } else {
+        usage(program_name);
+        eprintln!("ERROR: no such directory or flag: {}", arg);
+        process::exit(EXIT_ERROR);
+    }
+}
+
+fn resolved_dir(dir: &Path) -> PathBuf {
+    resolve_target_dir(dir).unwrap_or_else(|e| {
+        eprintln!("ERROR: couldn't resolve {}: {e}", dir.display());
+        process::exit(EXIT_ERROR);
+    })
+}
+
 fn parse_args(mut args: env::Args) -> Options {
     let program_name = args.next().expect("program name 0th element of args");
+    let mut args = split_args(args).into_iter();
     let mut res = Options::default();
-    for arg in args {
+    while let Some(arg) = args.next() {
         match arg.as_str() {
+            "--" => break,
             "-v" | "--verbose" => {
                 if res.quiet {
                     usage(&program_name);
                     eprintln!("ERROR: incompatible flags: cannot be quiet and verbose.");
-                    process::exit(1);
+                    process::exit(EXIT_ERROR);
                 }
                 res.verbose = true;
             }
-            "-q" | "--quiet" => {
+            "-q"
+            | "--quiet"
+            | "-0"
+            | "--print0"
+            | "--csv"
+            | "--json-stream"
+            | "--paths-only"
+            | "--paths-only=remove"
+            | "--paths-only=keep" => {
                 if res.verbose {
                     usage(&program_name);
                     eprintln!("ERROR: incompatible flags: cannot be quiet and verbose.");
-                    process::exit(1);
+                    process::exit(EXIT_ERROR);
                 }
                 res.quiet = true;
+                res.print0 |= arg == "-0" || arg == "--print0";
+                res.csv |= arg == "--csv";
+                res.json_stream |= arg == "--json-stream";
+                if arg == "--paths-only=keep" {
+                    res.paths_only = Some(PathsOnly::Keep);
+                } else if arg.starts_with("--paths-only") {
+                    res.paths_only = Some(PathsOnly::Remove);
+                }
             }
-            "-r" | "--recursive" => res.recursive = true,
+            "-r" | "--recursive" => res.scan.recursive = true,
+            "--follow-symlinks" => res.scan.follow_symlinks = true,
+            "--symlinks-as-duplicates" => res.scan.symlinks_as_duplicates = true,
+            "--one-file-system" => res.scan.one_file_system = true,
+            "--respect-gitignore" => res.scan.respect_gitignore = true,
+            "--mmap" => res.scan.mmap = true,
+            "--no-size-filter" => res.scan.no_size_filter = true,
+            "--block-size" => match parse_size_arg(&program_name, &arg, args.next()) {
+                size if size.is_power_of_two() => {
+                    res.block_size = Some(size);
+                    res.scan.block_size = Some(clamp_block_size(size));
+                }
+                _ => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --block-size must be a power of two, e.g. 64K or 1M.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--hash-limit" => match parse_size_arg(&program_name, &arg, args.next()) {
+                0 => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --hash-limit must be greater than 0.");
+                    process::exit(EXIT_ERROR);
+                }
+                limit => res.scan.hash_limit = Some(limit),
+            },
+            "--limit" => match args.next().map(|limit| limit.parse()) {
+                Some(Ok(limit)) if limit > 0 => res.scan.limit = Some(limit),
+                Some(_) | None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --limit requires a positive number.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--threads" => match args.next().map(|threads| threads.parse()) {
+                Some(Ok(threads)) => res.scan.threads = threads,
+                Some(Err(_)) | None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --threads requires a non-negative number.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--max-open-files" => match args.next().map(|max| max.parse()) {
+                Some(Ok(max)) if max > 0 => res.scan.open_files = OpenFileLimit::new(max),
+                Some(_) | None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --max-open-files requires a positive number.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--progress-interval" => match args.next().map(|ms| ms.parse()) {
+                Some(Ok(ms)) if ms > 0 => res.progress_interval = ms,
+                Some(_) | None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --progress-interval requires a positive number.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--max-depth" => match args.next().map(|depth| depth.parse()) {
+                Some(Ok(depth)) => res.scan.max_depth = depth,
+                Some(Err(_)) | None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --max-depth requires a non-negative number.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
             "-h" | "--help" => {
                 usage(&program_name);
-                process::exit(1);
+                process::exit(EXIT_NO_DUPLICATES);
             }
             "-u" | "--unique" => res.unique = true,
-            otherwise => {
-                let maybe_path = PathBuf::from(otherwise);
-                if maybe_path.is_dir() {
-                    res.target_dirs.push(maybe_path);
-                } else {
+            "--annotate" => res.annotate = true,
+            "--output-relative" => res.output_relative = true,
+            "--decimal-checksums" => res.decimal_checksums = true,
+            "--sort" => match args.next().as_deref() {
+                Some("size") => res.sort = SortOrder::Size,
+                Some("count") => res.sort = SortOrder::Count,
+                Some("path") => res.sort = SortOrder::Path,
+                _ => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --sort requires one of size, count or path.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--color" => match args.next().as_deref() {
+                Some("auto") => res.color = ColorChoice::Auto,
+                Some("always") => res.color = ColorChoice::Always,
+                Some("never") => res.color = ColorChoice::Never,
+                _ => {
                     usage(&program_name);
-                    eprintln!("ERROR: no such directory or flag: {}", otherwise);
-                    process::exit(1);
+                    eprintln!("ERROR: --color requires one of auto, always or never.");
+                    process::exit(EXIT_ERROR);
                 }
+            },
+            "--summary-only" => res.summary_only = true,
+            "--analyze" => res.analyze = true,
+            "--json" => res.json = true,
+            "--bench" => res.bench = true,
+            // every group is listed anyway
+            "--all" => {}
+            "--report-duplicate-names-only" => res.names_only = true,
+            "--delete" => set_action(&program_name, &mut res, Action::Delete),
+            "--hardlink" => set_action(&program_name, &mut res, Action::Hardlink),
+            "--reflink" => set_action(&program_name, &mut res, Action::Reflink),
+            "--dry-run" => res.dry_run = true,
+            "--interactive" => res.interactive = true,
+            "--save-decisions" | "--apply-decisions" => match args.next() {
+                Some(decisions_file) if arg == "--save-decisions" => {
+                    res.save_decisions = Some(PathBuf::from(decisions_file))
+                }
+                Some(decisions_file) => res.apply_decisions = Some(PathBuf::from(decisions_file)),
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: {} requires a file.", arg);
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "-y" | "--yes" => res.yes = true,
+            "--keep" => match args.next().as_deref() {
+                Some("first") => res.keep = Keep::First,
+                Some("last") => res.keep = Keep::Last,
+                Some("shortest-path") => res.keep = Keep::ShortestPath,
+                Some("longest-path") => res.keep = Keep::LongestPath,
+                Some("oldest") => res.keep = Keep::Oldest,
+                Some("newest") => res.keep = Keep::Newest,
+                _ => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --keep requires one of first, last, shortest-path, longest-path, oldest or newest.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--sample" => res.sample = true,
+            "--include-empty" => res.scan.include_empty = true,
+            "--include-hidden" => res.scan.include_hidden = true,
+            "--verify-links" => res.scan.verify_links = true,
+            "--ignore-case" => res.scan.ignore_case = true,
+            "--min-size" => res.scan.min_size = parse_size_arg(&program_name, &arg, args.next()),
+            "--max-size" => res.scan.max_size = parse_size_arg(&program_name, &arg, args.next()),
+            "--newer-than" => {
+                res.scan.newer_than = Some(parse_time_arg(&program_name, &arg, args.next()))
+            }
+            "--older-than" => {
+                res.scan.older_than = Some(parse_time_arg(&program_name, &arg, args.next()))
             }
+            "--from-file" | "--from-file0" => match args.next() {
+                Some(path_list) => {
+                    let separator = if arg == "--from-file" { b'\n' } else { b'\0' };
+                    res.path_lists.push((path_list, separator));
+                }
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: {} requires a file.", arg);
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--ext" | "--exclude-ext" => match args.next().map(|list| parse_extensions(&list)) {
+                Some(exts) if arg == "--ext" => res
+                    .scan
+                    .extensions
+                    .get_or_insert_with(Default::default)
+                    .extend(exts),
+                Some(exts) => res.scan.excluded_extensions.extend(exts),
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: {} requires a list of extensions.", arg);
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--cache" => match args.next() {
+                Some(cache_file) => res.cache_file = Some(PathBuf::from(cache_file)),
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --cache requires a file.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--checkpoint" => match args.next() {
+                Some(checkpoint) => res.checkpoint = Some(PathBuf::from(checkpoint)),
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --checkpoint requires a file.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--baseline" => match args.next() {
+                Some(baseline) => res.baseline = Some(PathBuf::from(baseline)),
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --baseline requires a file.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--set-a" | "--set-b" => match args.next().map(PathBuf::from) {
+                Some(dir) if dir.is_dir() => {
+                    let dir = resolved_dir(&dir);
+                    let set = if arg == "--set-a" {
+                        &mut res.set_a
+                    } else {
+                        &mut res.set_b
+                    };
+                    set.push(dir.clone());
+                    res.target_dirs.push(dir);
+                }
+                Some(dir) => {
+                    usage(&program_name);
+                    eprintln!("ERROR: no such directory: {}", dir.display());
+                    process::exit(EXIT_ERROR);
+                }
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: {} requires a directory.", arg);
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--exclude" => match args.next() {
+                Some(pattern) => res.scan.excludes.push(Pattern::new(&pattern)),
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --exclude requires a pattern.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            "--exclude-from" => match args.next() {
+                Some(pattern_file) => {
+                    match File::open(&pattern_file).and_then(|f| read_patterns(BufReader::new(f))) {
+                        Ok(patterns) => res.scan.excludes.extend(patterns),
+                        Err(e) => {
+                            eprintln!("ERROR: couldn't read patterns from {pattern_file}: {e}");
+                            process::exit(EXIT_ERROR);
+                        }
+                    }
+                }
+                None => {
+                    usage(&program_name);
+                    eprintln!("ERROR: --exclude-from requires a file.");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            paths_only if paths_only.starts_with("--paths-only=") => {
+                usage(&program_name);
+                eprintln!("ERROR: --paths-only requires one of remove or keep.");
+                process::exit(EXIT_ERROR);
+            }
+            otherwise => add_target_dir(&program_name, &mut res, otherwise),
         }
     }
+    // only input directories are left after --
+    for arg in args {
+        add_target_dir(&program_name, &mut res, &arg);
+    }
 
-    if res.target_dirs.is_empty() {
+    if res.apply_decisions.is_some()
+        && (!res.target_dirs.is_empty()
+            || !res.path_lists.is_empty()
+            || res.action.is_some()
+            || res.interactive
+            || res.save_decisions.is_some())
+    {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --apply-decisions acts on saved decisions rather than searching for duplicates.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.target_dirs.is_empty() && res.path_lists.is_empty() && res.apply_decisions.is_none() {
         usage(&program_name);
         eprintln!("ERROR: no directories provided.");
-        process::exit(1);
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.dry_run && res.action.is_none() && res.apply_decisions.is_none() {
+        usage(&program_name);
+        eprintln!("ERROR: --dry-run requires an action such as --delete.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.yes && res.action.is_none() && res.apply_decisions.is_none() {
+        usage(&program_name);
+        eprintln!("ERROR: --yes requires an action such as --delete.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if (res.interactive || res.save_decisions.is_some()) && res.action.is_none() {
+        usage(&program_name);
+        eprintln!("ERROR: --interactive and --save-decisions require an action such as --delete.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.interactive && res.yes {
+        usage(&program_name);
+        eprintln!(
+            "ERROR: incompatible flags: --interactive asks about each group, --yes doesn't ask."
+        );
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.action.is_some() && (res.unique || res.names_only) {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: actions only apply to duplicates.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.print0 && (res.action.is_some() || res.names_only) {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: -0 only prints duplicates or unique files.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.csv
+        && (res.print0
+            || res.action.is_some()
+            || res.unique
+            || res.names_only
+            || res.sample
+            || res.summary_only)
+    {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --csv only prints duplicates.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.scan.limit.is_some() && (res.unique || res.names_only || res.sample) {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --limit only finds some of the duplicates.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.json_stream
+        && (res.csv
+            || res.print0
+            || res.action.is_some()
+            || res.unique
+            || res.names_only
+            || res.sample
+            || res.summary_only
+            || res.baseline.is_some())
+    {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --json-stream only lists groups of duplicates as they're found.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.paths_only.is_some()
+        && (res.csv
+            || res.print0
+            || res.json_stream
+            || res.action.is_some()
+            || res.unique
+            || res.names_only
+            || res.sample
+            || res.summary_only
+            || res.baseline.is_some())
+    {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --paths-only only prints the paths of duplicates.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.json && !res.analyze {
+        usage(&program_name);
+        eprintln!("ERROR: --json requires --analyze.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.analyze
+        && (res.csv
+            || res.print0
+            || res.json_stream
+            || res.paths_only.is_some()
+            || res.action.is_some()
+            || res.unique
+            || res.names_only
+            || res.sample
+            || res.summary_only
+            || res.baseline.is_some())
+    {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --analyze stops before any duplicates are found.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.baseline.is_some()
+        && (res.csv
+            || res.print0
+            || res.action.is_some()
+            || res.unique
+            || res.names_only
+            || res.sample
+            || res.scan.limit.is_some())
+    {
+        usage(&program_name);
+        eprintln!(
+            "ERROR: incompatible flags: --baseline only lists changes to all of the duplicates."
+        );
+        process::exit(EXIT_ERROR);
+    }
+
+    if !res.set_a.is_empty() || !res.set_b.is_empty() {
+        check_sets(&program_name, &res);
+    }
+
+    // actions and annotations need the paths as they were found, and sets
+    // are told apart by them
+    if res.output_relative && (res.action.is_some() || res.annotate || !res.set_a.is_empty()) {
+        usage(&program_name);
+        eprintln!(
+            "ERROR: incompatible flags: --output-relative only changes how paths are printed."
+        );
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.sample && (res.action.is_some() || res.print0 || res.unique || res.names_only) {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --sample only finds probable duplicates, which can't be acted on.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.scan.no_size_filter && res.sample {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --no-size-filter reads every file in full, --sample only part of them.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.scan.no_size_filter && res.checkpoint.is_some() {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --checkpoint goes one size at a time, --no-size-filter compares files of every size with each other.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.scan.hash_limit.is_some() && (res.action.is_some() || res.cache_file.is_some()) {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --hash-limit can list files which differ as duplicates, so they can't be acted on or cached.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.checkpoint.is_some()
+        && (res.json_stream
+            || res.unique
+            || res.names_only
+            || res.sample
+            || res.analyze
+            || res.scan.limit.is_some())
+    {
+        usage(&program_name);
+        eprintln!(
+            "ERROR: incompatible flags: --checkpoint only resumes a full search for duplicates."
+        );
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.summary_only && (res.print0 || res.unique || res.names_only) {
+        usage(&program_name);
+        eprintln!("ERROR: incompatible flags: --summary-only doesn't list any files.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if res.scan.min_size > res.scan.max_size {
+        usage(&program_name);
+        eprintln!("ERROR: --min-size cannot be greater than --max-size.");
+        process::exit(EXIT_ERROR);
+    }
+
+    if let (Some(newer_than), Some(older_than)) = (res.scan.newer_than, res.scan.older_than) {
+        if newer_than >= older_than {
+            usage(&program_name);
+            eprintln!("ERROR: --newer-than must be earlier than --older-than.");
+            process::exit(EXIT_ERROR);
+        }
+    }
+
+    // the cache and the checkpoint are never searched, so that they aren't
+    // reported or acted on as duplicates. if their directories don't exist,
+    // they can't be searched anyway.
+    if let Some(Ok(cache_file)) = res.cache_file.as_deref().map(canonical_file_path) {
+        res.scan.excluded_files.push(cache_file);
+    }
+    if let Some(Ok(checkpoint)) = res.checkpoint.as_deref().map(canonical_file_path) {
+        res.scan.excluded_files.push(checkpoint);
     }
     res
 }
 
-fn build_file_list(options: &Options) -> IndexSet<MetaFile> {
-    if !options.quiet {
-        print!("Building file list... \r");
+// exits if --set-a and --set-b can't be compared: when only one of them is
+// given, when they overlap, or alongside other inputs or flags which don't
+// make sense for them.
+fn check_sets(program_name: &str, options: &Options) {
+    let fail = |message: &str| {
+        usage(program_name);
+        eprintln!("ERROR: {message}");
+        process::exit(EXIT_ERROR);
+    };
+    if options.set_a.is_empty() || options.set_b.is_empty() {
+        fail("--set-a and --set-b must be used together.");
+    }
+    if options.target_dirs.len() != options.set_a.len() + options.set_b.len()
+        || !options.path_lists.is_empty()
+    {
+        fail("incompatible flags: --set-a and --set-b replace the input directories.");
+    }
+    if options.action.is_some()
+        || options.print0
+        || options.unique
+        || options.names_only
+        || options.csv
+        || options.paths_only.is_some()
+        || options.analyze
+        || options.sample
+        || options.baseline.is_some()
+    {
+        fail("incompatible flags: --set-a and --set-b only list duplicates across the sets.");
+    }
+    let canonical = |dirs: &[PathBuf]| -> Vec<PathBuf> {
+        (dirs.iter())
+            .filter_map(|d| d.canonicalize().ok())
+            .collect()
+    };
+    let (set_a, set_b) = (canonical(&options.set_a), canonical(&options.set_b));
+    let overlap =
+        (set_a.iter()).any(|a| set_b.iter().any(|b| a.starts_with(b) || b.starts_with(a)));
+    if overlap {
+        fail("--set-a and --set-b overlap, a directory can only be in one of them.");
+    }
+}
+
+fn set_action(program_name: &str, options: &mut Options, action: Action) {
+    if options.action.is_some_and(|a| a != action) {
+        usage(program_name);
+        eprintln!("ERROR: incompatible flags: only one action can be given.");
+        process::exit(EXIT_ERROR);
+    }
+    options.action = Some(action);
+}
+
+fn parse_time_arg(program_name: &str, flag: &str, value: Option<String>) -> SystemTime {
+    let Some(value) = value else {
+        usage(program_name);
+        eprintln!("ERROR: {} requires a time.", flag);
+        process::exit(EXIT_ERROR);
+    };
+    match parse_time(&value, SystemTime::now()) {
+        Some(time) => time,
+        None => {
+            usage(program_name);
+            eprintln!("ERROR: invalid time for {}: {}", flag, value);
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+fn parse_size_arg(program_name: &str, flag: &str, value: Option<String>) -> u64 {
+    let Some(value) = value else {
+        usage(program_name);
+        eprintln!("ERROR: {} requires a size.", flag);
+        process::exit(EXIT_ERROR);
+    };
+    match parse_bytes(&value) {
+        Some(size) => size,
+        None => {
+            usage(program_name);
+            eprintln!("ERROR: invalid size for {}: {}", flag, value);
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// the directories to search, with warnings about the ones which are left out.
+fn target_dirs(options: &Options) -> Vec<PathBuf> {
+    let target_dirs = match dedup_target_dirs(&options.target_dirs, &options.scan) {
+        Ok((target_dirs, overlapping)) => {
+            for (dir, covering_dir) in overlapping {
+                warn!(
+                    "skipping {:?}, it is already searched as part of {:?}.",
+                    dir.as_os_str(),
+                    covering_dir.as_os_str()
+                );
+            }
+            target_dirs
+        }
+        Err(e) => {
+            error!("couldn't read input directories: {e}");
+            process::exit(EXIT_ERROR);
+        }
+    };
+    if let Some((cache_file, Ok(canonical_cache_file))) = (options.cache_file.as_deref())
+        .map(|cache_file| (cache_file, canonical_file_path(cache_file)))
+    {
+        let containing_dir = target_dirs.iter().find(|d| {
+            d.canonicalize()
+                .is_ok_and(|d| canonical_cache_file.starts_with(d))
+        });
+        if let Some(dir) = containing_dir {
+            warn!(
+                "the cache {:?} is inside {:?}, it is left out of the search.",
+                cache_file.as_os_str(),
+                dir.as_os_str()
+            );
+        }
     }
-    let mut acc: IndexSet<MetaFile> = indexset![];
-    for target_dir in &options.target_dirs {
-        let read_dir_iterator: Box<dyn Iterator<Item = _>> = if options.recursive {
-            Box::new(RecReadDir::new(target_dir).expect("read_dir call failed"))
+    target_dirs
+}
+
+fn read_paths(path_list: &str, separator: u8) -> Vec<PathBuf> {
+    let paths = if path_list == "-" {
+        read_path_list(io::stdin().lock(), separator)
+    } else {
+        File::open(path_list).and_then(|f| read_path_list(BufReader::new(f), separator))
+    };
+    paths.unwrap_or_else(|e| {
+        error!("couldn't read paths from {}: {e}", path_list);
+        process::exit(EXIT_ERROR);
+    })
+}
+
+fn print_file_count(count: usize) {
+    info!("Found {} files.", count);
+}
+
+/// the whole list of files, for when more than their sizes is needed.
+fn build_file_list(options: &Options, errors: &mut FileErrors) -> IndexSet<MetaFile> {
+    let target_dirs = target_dirs(options);
+    info!("Building file list...");
+    let mut acc = match scan::build_file_list(&target_dirs, &options.scan, errors) {
+        Ok(acc) => acc,
+        Err(e) => {
+            error!("couldn't read input directories: {e}");
+            process::exit(EXIT_ERROR);
+        }
+    };
+    for (path_list, separator) in &options.path_lists {
+        add_to_file_list(
+            &mut acc,
+            read_paths(path_list, *separator),
+            &options.scan,
+            errors,
+        );
+    }
+    print_file_count(acc.len());
+    acc
+}
+
+/// groups files by size as the directories are walked, without keeping a
+/// list of all of them.
+fn group_by_size<'a>(options: &'a Options, errors: &mut FileErrors) -> SizeGrouper<'a> {
+    let target_dirs = target_dirs(options);
+    info!("Building file list...");
+    let mut grouper = SizeGrouper::new(&options.scan);
+    for dir in &target_dirs {
+        if let Err(e) = grouper.add_dir(dir, errors) {
+            error!("couldn't read input directories: {e}");
+            process::exit(EXIT_ERROR);
+        }
+    }
+    for (path_list, separator) in &options.path_lists {
+        grouper.add_paths(read_paths(path_list, *separator), errors);
+    }
+    print_file_count(grouper.file_count());
+    grouper
+}
+
+fn finish_grouping(options: &Options, mut grouper: SizeGrouper) -> (SizewiseDups, usize) {
+    if options.scan.verify_links {
+        for (path, other) in grouper.verify_links() {
+            warn!(
+                "{:?} shares a file identifier with {:?}, but is a different file. they are compared separately.",
+                path.as_os_str(),
+                other.as_os_str()
+            );
+        }
+    }
+    grouper.finish()
+}
+
+// reports can be tens of thousands of lines long, so they're written to a
+// single buffered handle to stdout, which is flushed once they're done.
+fn write_report(write: impl FnOnce(&mut BufWriter<io::StdoutLock>) -> io::Result<()>) {
+    let mut out = BufWriter::new(io::stdout().lock());
+    if let Err(e) = write(&mut out).and_then(|()| out.flush()) {
+        error!("couldn't write output: {e}");
+        process::exit(EXIT_ERROR);
+    }
+}
+
+fn print_namewise_dups(out: &mut impl Write, ds: &NamewiseDups) -> io::Result<()> {
+    let mut ds: Vec<_> = ds.iter().collect();
+    ds.sort_by_key(|(name, _)| *name);
+    for d in ds {
+        writeln!(out, "files named {:?}:", d.0.to_string_lossy())?;
+        let mut paths: Vec<_> = d.1.iter().collect();
+        paths.sort();
+        for p in paths {
+            writeln!(out, "  {:?}", p.as_os_str().to_string_lossy())?;
+        }
+    }
+    Ok(())
+}
+
+// a checksum as it's listed in group headers, see `Digest` and
+// --decimal-checksums.
+fn show_checksum(checksum: u32, decimal: bool) -> String {
+    if decimal {
+        checksum.to_string()
+    } else {
+        Digest(checksum).to_string()
+    }
+}
+
+// when `color` is set, the file which would be kept by an action such as
+// --delete, chosen by `keep`, is colored differently from its redundant
+// copies.
+fn print_dups(
+    out: &mut impl Write,
+    ds: &[DuplicateGroup],
+    keep: Keep,
+    annotate: bool,
+    decimal: bool,
+    color: bool,
+) -> io::Result<()> {
+    for d in ds {
+        let header = format!(
+            "files with checksum {}:",
+            show_checksum(d.checksum(), decimal)
+        );
+        writeln!(out, "{}", paint(header, HEADER_STYLE, color))?;
+        let annotations = if annotate { d.annotations() } else { vec![] };
+        let kept = keep.pick(d.files());
+        for (idx, lg) in d.files().iter().enumerate() {
+            let style = if Some(idx) == kept {
+                KEPT_STYLE
+            } else {
+                REDUNDANT_STYLE
+            };
+            write!(out, "  {}", paint_metafile(lg, style, color))?;
+            if let Some(annotation) = annotations.get(idx) {
+                write!(out, " ({annotation})")?;
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+// like `print_dups`, but each file is marked with the set it's in, see
+// `side_of`. files in set A are colored like the ones which would be kept.
+fn print_cross_set_dups(
+    out: &mut impl Write,
+    ds: &[DuplicateGroup],
+    set_a: &[PathBuf],
+    set_b: &[PathBuf],
+    decimal: bool,
+    color: bool,
+) -> io::Result<()> {
+    for d in ds {
+        let header = format!(
+            "files with checksum {}:",
+            show_checksum(d.checksum(), decimal)
+        );
+        writeln!(out, "{}", paint(header, HEADER_STYLE, color))?;
+        for lg in d.files() {
+            let (label, style) = match side_of(lg, set_a, set_b) {
+                Some(Side::A) => ("A", KEPT_STYLE),
+                Some(Side::B) => ("B", REDUNDANT_STYLE),
+                Some(Side::Both) => ("A+B", REDUNDANT_STYLE),
+                None => ("-", REDUNDANT_STYLE),
+            };
+            writeln!(out, "  {label:<3} {}", paint_metafile(lg, style, color))?;
+        }
+    }
+    Ok(())
+}
+
+// like `MetaFile`'s `Display`, but with its files in `style` and its symlinks
+// dimmed when `color` is set.
+fn paint_metafile(mf: &MetaFile, style: &str, color: bool) -> String {
+    if !color {
+        return mf.to_string();
+    }
+    let painted: Vec<String> = (mf.paths().into_iter())
+        .map(|p| {
+            let lossy = format!("{:?}", p.as_os_str().to_string_lossy());
+            if mf.symlinks().contains(p) {
+                paint(format!("symlink {lossy}"), SYMLINK_STYLE, color)
+            } else {
+                paint(lossy, style, color)
+            }
+        })
+        .collect();
+    match painted.split_first() {
+        Some((first, [])) => first.clone(),
+        Some((first, aliases)) => format!("{first} (aka {})", aliases.join(", ")),
+        None => String::new(),
+    }
+}
+
+fn print_uniques(out: &mut impl Write, uniques: &[MetaFile], print0: bool) -> io::Result<()> {
+    for unique in uniques {
+        if print0 {
+            for p in unique.files() {
+                write_path0(out, p)?;
+            }
         } else {
-            Box::new(target_dir.read_dir().expect("read_dir call failed"))
+            writeln!(out, "{unique}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_path0(out: &mut impl Write, p: &std::path::Path) -> io::Result<()> {
+    write_path(out, p, b'\0')
+}
+
+// paths are written as raw bytes where possible, since they needn't be valid
+// utf-8 and are meant to be read by another program.
+fn write_path(out: &mut impl Write, p: &std::path::Path, end: u8) -> io::Result<()> {
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(p.as_os_str());
+    #[cfg(not(unix))]
+    let lossy = p.to_string_lossy();
+    #[cfg(not(unix))]
+    let bytes = lossy.as_bytes();
+    out.write_all(bytes)?;
+    out.write_all(&[end])
+}
+
+// probable duplicates are labeled as such, so they aren't mistaken for
+// verified ones.
+fn print_probable_dups(out: &mut impl Write, ds: &[ProbableGroup], color: bool) -> io::Result<()> {
+    for d in ds {
+        let header = format!(
+            "probable duplicates (sampled) with signature {}:",
+            d.signature()
+        );
+        writeln!(out, "{}", paint(header, HEADER_STYLE, color))?;
+        for lg in d.files() {
+            writeln!(out, "  {}", paint_metafile(lg, REDUNDANT_STYLE, color))?;
+        }
+    }
+    Ok(())
+}
+
+// prints the redundant copies in each group, leaving out the one which would
+// be kept by an action such as --delete.
+fn print_dups0(out: &mut impl Write, ds: &[DuplicateGroup], keep: Keep) -> io::Result<()> {
+    for d in ds {
+        let Some((_survivor, redundant)) = split_survivor(d.files(), keep) else {
+            continue;
         };
-        let path_iterator = read_dir_iterator.filter_map(Result::ok).map(|a| a.path());
-        collect_into_metafiles(&mut acc, path_iterator, false);
+        for p in redundant.iter().flat_map(|f| f.files()) {
+            write_path0(out, p)?;
+        }
     }
-    println!("Building file list... {}      ", acc.len());
-    if !options.quiet {
-        println!("Found {} files.", acc.len());
+    Ok(())
+}
+
+// prints the paths of either the redundant copies in each group, like
+// `print_dups0`, or of the files which are kept, one per line.
+fn print_paths_only(
+    out: &mut impl Write,
+    ds: &[DuplicateGroup],
+    keep: Keep,
+    which: PathsOnly,
+) -> io::Result<()> {
+    for d in ds {
+        let Some((survivor, redundant)) = split_survivor(d.files(), keep) else {
+            continue;
+        };
+        match which {
+            PathsOnly::Keep => write_path(out, survivor.primary(), b'\n')?,
+            PathsOnly::Remove => {
+                for p in redundant.iter().flat_map(|f| f.files()) {
+                    write_path(out, p, b'\n')?;
+                }
+            }
+        }
     }
-    acc
+    Ok(())
 }
 
-/*
-   I'm using the term 'sizewise dup' to describe 2 or more files which
-   share the same size, therefore appearing to be duplicates from a
-   sizewise perspective.
-*/
-
-// a map whose keys are filesizes and whose values are sets of files with a
-// given size.
-type SizewiseDups = HashMap<u64, HashSet<MetaFile>>;
-
-fn find_sizewise_dups(files: impl IntoIterator<Item = MetaFile>) -> SizewiseDups {
-    let mut files_by_size: SizewiseDups = HashMap::new();
-    for f in files {
-        let Ok(metadata) = f.paths()[0].metadata() else { continue; };
-        // it would be an error if there were directories in the file list
-        assert!(!metadata.is_dir());
-        let file_size = metadata.len();
-        files_by_size
-            .entry(file_size)
-            .or_insert(HashSet::with_capacity(1))
-            .insert(f);
-    }
-    files_by_size.retain(|_, files| files.len() > 1);
-    files_by_size
-}
-
-fn calc_file_checksumsr(
-    files: impl IntoParallelIterator<Item = MetaFile>,
-) -> HashSet<(u32, MetaFile)> {
-    files
-        .into_par_iter()
-        .map(|f| {
-            let p = &f.paths()[0];
-            let bytes_of_file: Vec<u8> = std::fs::read(p).unwrap();
-            (adler32(bytes_of_file.as_slice()).unwrap(), f)
+// one row of the --csv report, for each path to a duplicate.
+struct CsvRow<'a> {
+    group: usize, /* the position of the group in the report, from 1 */
+    checksum: u32,
+    path: &'a Path,
+    size: u64,
+    is_primary: bool, /* whether it's a path to the file which is kept */
+    is_symlink: bool,
+}
+
+impl CsvRow<'_> {
+    const HEADER: [&'static str; 6] = [
+        "group",
+        "checksum",
+        "path",
+        "size",
+        "is_primary",
+        "is_symlink",
+    ];
+
+    // fails for paths which aren't valid UTF-8, see `path_to_str`.
+    fn fields(&self) -> io::Result<[String; 6]> {
+        Ok([
+            self.group.to_string(),
+            self.checksum.to_string(),
+            path_to_str(self.path)?.to_string(),
+            self.size.to_string(),
+            self.is_primary.to_string(),
+            self.is_symlink.to_string(),
+        ])
+    }
+}
+
+fn print_csv(out: &mut impl Write, ds: &[DuplicateGroup], keep: Keep) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(CsvRow::HEADER)?;
+    for (group, d) in ds.iter().enumerate() {
+        let kept = keep.pick(d.files());
+        for (idx, f) in d.files().iter().enumerate() {
+            for path in f.paths() {
+                let row = CsvRow {
+                    group: group + 1,
+                    checksum: d.checksum(),
+                    path,
+                    size: d.size(),
+                    is_primary: Some(idx) == kept,
+                    is_symlink: f.symlinks().contains(path),
+                };
+                writer.write_record(row.fields()?)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+// a group as --json-stream lists it as soon as it's found: with its paths
+// made relative under --output-relative, or `None` when it's left out
+// because it doesn't span --set-a and --set-b.
+fn shown_group(options: &Options, group: DuplicateGroup) -> Option<DuplicateGroup> {
+    let group = if options.set_a.is_empty() {
+        group
+    } else {
+        cross_set_groups(vec![group], &options.set_a, &options.set_b).pop()?
+    };
+    if options.output_relative {
+        Some(group.map_paths(|p| relative_path(p, &options.target_dirs)))
+    } else {
+        Some(group)
+    }
+}
+
+// one line of --json-stream, which is flushed straight away so that it can
+// be read while the scan goes on. Each path to a duplicate is listed like a
+// row of --csv.
+fn write_json_group(
+    out: &mut impl Write,
+    group: usize,
+    d: &DuplicateGroup,
+    keep: Keep,
+) -> io::Result<()> {
+    let kept = keep.pick(d.files());
+    let mut files = vec![];
+    for (idx, f) in d.files().iter().enumerate() {
+        for path in f.paths() {
+            files.push(format!(
+                r#"{{"path":{},"is_primary":{},"is_symlink":{}}}"#,
+                json_string(path_to_str(path)?),
+                Some(idx) == kept,
+                f.symlinks().contains(path)
+            ));
+        }
+    }
+    writeln!(
+        out,
+        r#"{{"group":{group},"checksum":{},"size":{},"files":[{}]}}"#,
+        d.checksum(),
+        d.size(),
+        files.join(",")
+    )?;
+    out.flush()
+}
+
+// `s` as a JSON string, with quotes, backslashes and control characters
+// escaped.
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c < ' ' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// the sizes are bucketed by powers of 1024, e.g. from 1.0 KiB to 1.0 MiB.
+fn print_analysis(out: &mut impl Write, analysis: &SizeAnalysis) -> io::Result<()> {
+    writeln!(
+        out,
+        "{} files in {} groups of files with equal sizes would be checksummed, {} in total.",
+        analysis.candidate_count,
+        analysis.group_count,
+        format_bytes(analysis.candidate_bytes)
+    )?;
+    if analysis.histogram.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "by size:")?;
+    for bucket in &analysis.histogram {
+        writeln!(
+            out,
+            "  from {} to {}: {} files, {}",
+            format_bytes(bucket.min_size),
+            format_bytes(bucket.min_size.max(1).saturating_mul(1024)),
+            bucket.file_count,
+            format_bytes(bucket.bytes)
+        )?;
+    }
+    writeln!(out, "largest groups:")?;
+    for (size, count) in &analysis.largest {
+        writeln!(
+            out,
+            "  {count} files of {}, {} in total",
+            format_bytes(*size),
+            format_bytes(size * *count as u64)
+        )?;
+    }
+    Ok(())
+}
+
+// --analyze as one JSON object, with the same fields as `SizeAnalysis`.
+fn write_analysis_json(out: &mut impl Write, analysis: &SizeAnalysis) -> io::Result<()> {
+    let histogram: Vec<String> = (analysis.histogram.iter())
+        .map(|b| {
+            format!(
+                r#"{{"min_size":{},"file_count":{},"bytes":{}}}"#,
+                b.min_size, b.file_count, b.bytes
+            )
         })
+        .collect();
+    let largest: Vec<String> = (analysis.largest.iter())
+        .map(|(size, count)| format!(r#"{{"size":{size},"file_count":{count}}}"#))
+        .collect();
+    writeln!(
+        out,
+        r#"{{"group_count":{},"candidate_count":{},"candidate_bytes":{},"histogram":[{}],"largest":[{}]}}"#,
+        analysis.group_count,
+        analysis.candidate_count,
+        analysis.candidate_bytes,
+        histogram.join(","),
+        largest.join(",")
+    )
+}
+
+fn print_baseline_diff(
+    out: &mut impl Write,
+    diff: &BaselineDiff,
+    keep: Keep,
+    decimal: bool,
+    color: bool,
+) -> io::Result<()> {
+    if !diff.new.is_empty() {
+        writeln!(out, "new since the baseline:")?;
+        print_dups(out, &diff.new, keep, false, decimal, color)?;
+    }
+    if !diff.gone.is_empty() {
+        writeln!(out, "gone since the baseline:")?;
+        for g in &diff.gone {
+            let header = format!(
+                "files with checksum {}:",
+                show_checksum(g.checksum(), decimal)
+            );
+            writeln!(out, "{}", paint(header, HEADER_STYLE, color))?;
+            for p in g.paths() {
+                writeln!(out, "  {:?}", p.as_os_str().to_string_lossy())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// the verb, past tense and capitalized past tense describing `action`.
+fn action_verbs(action: Action) -> (&'static str, &'static str, &'static str) {
+    match action {
+        Action::Delete => ("remove", "removed", "Removed"),
+        Action::Hardlink => ("hardlink", "hardlinked", "Hardlinked"),
+        Action::Reflink => ("reflink", "reflinked", "Reflinked"),
+    }
+}
+
+// lists the changes `action` would make and asks for them to be confirmed on
+// stdin. rather than waiting on input which may never come, the scan is
+// stopped when stdin isn't a terminal.
+fn confirm_action(decisions: &[Decision], action: Action) -> bool {
+    if !atty::is(Stream::Stdin) {
+        error!("no files were changed, stdin isn't a terminal to confirm the changes on. pass --yes to make them without confirming.");
+        process::exit(EXIT_ERROR);
+    }
+    let (verb, ..) = action_verbs(action);
+    let mut count: usize = 0;
+    let mut reclaimable: u64 = 0;
+    println!("Planned changes:");
+    for d in decisions {
+        println!("  keep {:?}", d.survivor().primary().as_os_str());
+        for f in d.redundant() {
+            for p in f.files() {
+                println!("    {verb} {:?}", p.as_os_str());
+                count += 1;
+            }
+        }
+        reclaimable += d.reclaimable_space();
+    }
+    println!(
+        "This will {verb} {count} files, reclaiming {}.",
+        format_bytes(reclaimable)
+    );
+    print!("Type yes to continue: ");
+    let mut answer = String::new();
+    io::stdout().flush().is_ok()
+        && io::stdin().read_line(&mut answer).is_ok()
+        && answer.trim() == "yes"
+}
+
+// which file to keep in each of `ds`, and so what `action` is applied to,
+// as --keep picks them. see `ask_decisions` for --interactive.
+fn planned_decisions(ds: &[DuplicateGroup], keep: Keep) -> Vec<Decision> {
+    // symlinks are never kept in place of a file, see `Decision::keeping`
+    ds.iter()
+        .filter_map(|d| Decision::planned(d, keep))
         .collect()
 }
 
-/*
-   I'm using the term 'dup' to describe 2 or more files which
-   share the same checksum, therefore appearing to be duplicates from a
-   checksumwise perspective.
-*/
-
-// a map whose keys are checksums and whose values are sets of files with a
-// given checksum.
-type Dups = HashMap<u32, HashSet<MetaFile>>;
-
-fn find_dups(mut sizewise_dups: SizewiseDups) -> Dups {
-    let mut calculation_count: usize = 0;
-    let grps = sizewise_dups.len();
-    let mut files_by_checksum: Dups = HashMap::new();
-    for (grp, (size, files)) in sizewise_dups.drain().enumerate() {
-        assert!(files.len() > 1);
-        eprint!(
-            "(group {}/{}): calculating checksums of {} files with size {}...\r",
-            grp,
-            grps,
-            files.len(),
-            size
+// asks on stdin which file to keep in each of `ds`, offering the one --keep
+// picks by default, or whether to leave the group alone. like
+// `confirm_action`, the scan is stopped when stdin isn't a terminal. groups
+// which haven't been asked about when stdin is closed, or when the scan is
+// interrupted, are left alone.
+fn ask_decisions(
+    ds: &[DuplicateGroup],
+    action: Action,
+    keep: Keep,
+    interrupt: &Interrupt,
+) -> Vec<Decision> {
+    if !atty::is(Stream::Stdin) {
+        error!("no files were changed, stdin isn't a terminal to ask which files to keep on.");
+        process::exit(EXIT_ERROR);
+    }
+    let (_, past_tense, _) = action_verbs(action);
+    let mut decisions = vec![];
+    for (idx, d) in ds.iter().enumerate() {
+        // symlinks are never kept in place of a file
+        let choices: Vec<usize> = (d.files().iter().enumerate())
+            .filter(|(_, f)| !f.files().is_empty())
+            .map(|(idx, _)| idx)
+            .collect();
+        let Some(default) =
+            (keep.pick(d.files())).and_then(|picked| choices.iter().position(|idx| *idx == picked))
+        else {
+            continue;
+        };
+        println!(
+            "Group {} of {}, files with checksum {} ({} each):",
+            idx + 1,
+            ds.len(),
+            Digest(d.checksum()),
+            format_bytes(d.size())
         );
-        calculation_count += files.len();
-        let mut checksums = calc_file_checksumsr(files);
-        for (checksum, f) in checksums.drain() {
-            files_by_checksum
-                .entry(checksum)
-                .or_insert(HashSet::with_capacity(1))
-                .insert(f);
+        for (n, idx) in choices.iter().enumerate() {
+            println!("  {}: {:?}", n + 1, d.files()[*idx].primary().as_os_str());
+        }
+        loop {
+            if interrupt.is_set() {
+                return decisions;
+            }
+            print!(
+                "Keep which file? the others are {past_tense}. [1-{}, enter for {}, s to skip this group, q to skip the rest]: ",
+                choices.len(),
+                default + 1
+            );
+            let mut answer = String::new();
+            if io::stdout().flush().is_err()
+                || !matches!(io::stdin().read_line(&mut answer), Ok(1..))
+            {
+                println!();
+                return decisions;
+            }
+            let kept = match answer.trim() {
+                "" => default,
+                "s" => break,
+                "q" => return decisions,
+                n => match n.parse::<usize>() {
+                    Ok(n) if (1..=choices.len()).contains(&n) => n - 1,
+                    _ => continue,
+                },
+            };
+            decisions.extend(Decision::keeping(d, choices[kept]));
+            break;
         }
     }
-    eprintln!("\nCalculated checksums of {} files.", calculation_count);
-    // collect all of the dups we found
-    files_by_checksum.retain(|_, files| files.len() > 1);
-    files_by_checksum
+    decisions
 }
 
-fn print_dups(ds: &Dups) {
-    for d in ds {
-        println!("files with checksum {}:", d.0);
-        for lg in d.1 {
-            println!("  {}", lg);
+// writes the decisions to --save-decisions. nothing has been changed yet,
+// so the scan is stopped if they can't be written.
+fn save_decisions(decisions_file: &Path, action: Action, decisions: &[Decision]) {
+    let saved = File::create(decisions_file).and_then(|f| {
+        let mut out = BufWriter::new(f);
+        write_decisions(&mut out, action, decisions)?;
+        out.flush()
+    });
+    if let Err(e) = saved {
+        error!(
+            "no files were changed, couldn't write decisions {:?}: {e}",
+            decisions_file.as_os_str()
+        );
+        process::exit(EXIT_ERROR);
+    }
+    info!(
+        "Saved decisions for {} groups to {:?}.",
+        decisions.len(),
+        decisions_file.as_os_str()
+    );
+}
+
+fn apply_action(decisions: &[Decision], action: Action, dry_run: bool) {
+    let (verb, past_tense, summary) = action_verbs(action);
+    let mut count: usize = 0;
+    for d in decisions {
+        for (p, res) in d.apply(action, dry_run) {
+            match res {
+                Ok(()) if dry_run => println!("would {verb} {:?}", p.as_os_str()),
+                Ok(()) => {
+                    println!("{past_tense} {:?}", p.as_os_str());
+                    count += 1;
+                }
+                Err(e) if contents_differ(&e) => {
+                    warn!("didn't {verb} {:?}: {e}", p.as_os_str())
+                }
+                Err(e) => error!("couldn't {verb} {:?}: {e}", p.as_os_str()),
+            }
+        }
+    }
+    if !dry_run {
+        println!("{summary} {count} files.");
+    }
+}
+
+fn print_errors(errors: &FileErrors, verbose: bool) {
+    if errors.is_empty() {
+        return;
+    }
+    if verbose {
+        warn!("skipped {} paths due to errors:", errors.len());
+    } else {
+        warn!(
+            "skipped {} paths due to errors (run with -v to list them).",
+            errors.len()
+        );
+    }
+    for (p, e) in errors {
+        debug!("  {:?}: {e}", p.as_os_str().to_string_lossy());
+    }
+}
+
+// progress is drawn to stderr, so it doesn't end up in piped output. it's
+// hidden under --quiet, and is a bar with an eta under --verbose, and a
+// spinner otherwise. updates between redraws only bump a counter, so they
+// cost next to nothing however many files there are.
+fn progress_bar(options: &Options, len: usize, message: &'static str) -> ProgressBar {
+    if options.quiet {
+        return ProgressBar::hidden();
+    }
+    let redraws_per_second = (1000 / options.progress_interval).clamp(1, u8::MAX as u64) as u8;
+    let target = ProgressDrawTarget::stderr_with_hz(redraws_per_second);
+    let (progress, template) = if options.verbose {
+        (
+            ProgressBar::with_draw_target(Some(len as u64), target),
+            "{msg} [{bar:40}] {pos}/{len} (eta {eta})",
+        )
+    } else {
+        (
+            ProgressBar::with_draw_target(None, target),
+            "{spinner} {msg}... {pos}",
+        )
+    };
+    let style = ProgressStyle::with_template(template).expect("valid progress template");
+    progress.with_style(style).with_message(message)
+}
+
+// how long one phase of a scan took, and how many files and bytes it was
+// given, or for the phases which read files, read, for --bench.
+struct Phase {
+    name: &'static str,
+    time: Duration,
+    files: usize,
+    bytes: u64,
+}
+
+// the phases of a scan, in the order they ran in.
+#[derive(Default)]
+struct Bench {
+    phases: Vec<Phase>,
+    start: Option<Instant>,
+}
+
+impl Bench {
+    fn start(&mut self) {
+        self.start = Some(Instant::now());
+    }
+
+    // ends the phase which was started last, and starts the next one.
+    fn record(&mut self, name: &'static str, files: usize, bytes: u64) {
+        let time = self.start.map_or(Duration::ZERO, |start| start.elapsed());
+        self.phases.push(Phase {
+            name,
+            time,
+            files,
+            bytes,
+        });
+        self.start();
+    }
+
+    // a phase which ran interleaved with others, e.g. comparing files block
+    // by block and checksumming them, with what was read during it and how
+    // long that took.
+    fn record_reads(&mut self, name: &'static str, reads: &ReadCount) {
+        self.phases.push(Phase {
+            name,
+            time: reads.elapsed(),
+            files: reads.files(),
+            bytes: reads.bytes(),
+        });
+    }
+
+    // printed as key=value pairs, one line per phase, so they can be parsed.
+    fn print(&self) {
+        for p in &self.phases {
+            eprintln!(
+                "bench: phase={} files={} bytes={} micros={}",
+                p.name,
+                p.files,
+                p.bytes,
+                p.time.as_micros()
+            );
         }
     }
 }
 
 use atty::Stream;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+// diagnostics are logged to stderr, so that only the report goes to stdout.
+// -q and -v pick how much is logged, and RUST_LOG overrides them.
+fn init_logger(options: &Options) {
+    let level = if options.quiet {
+        LevelFilter::Warn
+    } else if options.verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|out, record| match record.level() {
+            Level::Error => writeln!(out, "ERROR: {}", record.args()),
+            Level::Warn => writeln!(out, "WARNING: {}", record.args()),
+            _ => writeln!(out, "{}", record.args()),
+        })
+        .init();
+}
+
+// the first Ctrl-C interrupts the scan, so that whatever was found so far is
+// listed, and the second quits straight away.
+fn handle_ctrl_c(options: &Options) {
+    let interrupt = options.scan.interrupt.clone();
+    let handled = ctrlc::set_handler(move || {
+        if interrupt.is_set() {
+            process::exit(EXIT_INTERRUPTED);
+        }
+        interrupt.set();
+    });
+    if let Err(e) = handled {
+        warn!("couldn't handle Ctrl-C, it will quit without listing anything: {e}");
+    }
+}
+
+// `verify <report>`, see `verify_groups`. Exits once the report has been
+// checked.
+fn verify(mut args: env::Args) -> ! {
+    let program_name = args.next().expect("program name 0th element of args");
+    let report_file = match (args.nth(1), args.next()) {
+        (Some(report_file), None) => PathBuf::from(report_file),
+        _ => {
+            usage(&program_name);
+            eprintln!("ERROR: verify requires a report, and nothing else.");
+            process::exit(EXIT_ERROR);
+        }
+    };
+    let options = Options::default();
+    init_logger(&options);
+    let groups = File::open(&report_file)
+        .and_then(|f| read_baseline(BufReader::new(f)))
+        .unwrap_or_else(|e| {
+            error!("couldn't read report {:?}: {e}", report_file.as_os_str());
+            process::exit(EXIT_ERROR);
+        });
+    let path_count = groups.iter().map(|g| g.paths().len()).sum();
+    let progress = progress_bar(&options, path_count, "verifying");
+    let mut errors = vec![];
+    let checks = verify_groups(&groups, &options.scan, &mut errors, &progress);
+    progress.finish_and_clear();
+    let invalid_count = checks.iter().filter(|check| !check.is_valid()).count();
+    println!(
+        "{} of {} groups are still duplicates.",
+        groups.len() - invalid_count,
+        groups.len()
+    );
+    let color = options.color.enabled();
+    write_report(|out| print_group_checks(out, &groups, &checks, color));
+    print_errors(&errors, options.verbose);
+    process::exit(exit_code(invalid_count));
+}
+
+// lists the groups which are no longer valid, with what happened to each of
+// their files.
+fn print_group_checks(
+    out: &mut impl Write,
+    groups: &[BaselineGroup],
+    checks: &[GroupCheck],
+    color: bool,
+) -> io::Result<()> {
+    for (g, check) in groups.iter().zip(checks) {
+        if check.is_valid() {
+            continue;
+        }
+        let header = format!(
+            "files with checksum {} are no longer duplicates:",
+            Digest(g.checksum())
+        );
+        writeln!(out, "{}", paint(header, HEADER_STYLE, color))?;
+        for (status, paths) in [
+            ("unchanged", &check.unchanged),
+            ("changed", &check.changed),
+            ("gone", &check.gone),
+            ("unreadable", &check.unreadable),
+        ] {
+            for p in paths {
+                writeln!(out, "  {status} {:?}", p.as_os_str().to_string_lossy())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// --apply-decisions, see `read_decisions`. the files of each group are
+// checked again, see `verify_groups`, and the decisions for groups which
+// are unchanged are applied. Exits once they have been.
+fn apply_decisions(options: &Options, decisions_file: &Path) -> ! {
+    let saved = File::open(decisions_file)
+        .and_then(|f| read_decisions(BufReader::new(f)))
+        .unwrap_or_else(|e| {
+            error!(
+                "couldn't read decisions {:?}: {e}",
+                decisions_file.as_os_str()
+            );
+            process::exit(EXIT_ERROR);
+        });
+    let Some(action) = saved.first().map(SavedDecision::action) else {
+        println!("No decisions to apply.");
+        process::exit(EXIT_NO_DUPLICATES);
+    };
+    let groups: Vec<BaselineGroup> = saved.iter().map(|d| d.group().clone()).collect();
+    let path_count = groups.iter().map(|g| g.paths().len()).sum();
+    let progress = progress_bar(options, path_count, "verifying");
+    let mut errors = vec![];
+    let checks = verify_groups(&groups, &options.scan, &mut errors, &progress);
+    progress.finish_and_clear();
+    if options.bench {
+        let mut bench = Bench::default();
+        bench.record_reads("verify", options.scan.read_stats.checksummed());
+        bench.print();
+    }
+    let decisions: Vec<Decision> = (saved.iter().zip(&checks))
+        .filter(|(_, check)| check.is_valid())
+        .filter_map(|(d, _)| d.resolve(&mut errors))
+        .collect();
+    let changed_count = saved.len() - decisions.len();
+    if changed_count > 0 {
+        warn!("{changed_count} groups have changed since the decisions were saved, they are left alone.");
+        let color = options.color.enabled();
+        write_report(|out| print_group_checks(out, &groups, &checks, color));
+    }
+    let confirmed = options.dry_run
+        || options.yes
+        || decisions.is_empty()
+        || confirm_action(&decisions, action);
+    if confirmed {
+        apply_action(&decisions, action, options.dry_run);
+    } else {
+        println!("Cancelled, no files were changed.");
+    }
+    print_errors(&errors, options.verbose);
+    process::exit(exit_code(changed_count));
+}
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("verify") {
+        verify(env::args());
+    }
     let options = parse_args(env::args());
-    let mut start = Instant::now();
-    let file_list = build_file_list(&options);
-    println!("took: {:?}", start.elapsed());
-    start = Instant::now();
-    let sizewise_dups = find_sizewise_dups(file_list.clone());
-    println!(
+    init_logger(&options);
+    if let Some(decisions_file) = &options.apply_decisions {
+        apply_decisions(&options, decisions_file);
+    }
+    if let (Some(given), Some(block_size)) = (options.block_size, options.scan.block_size) {
+        if given != block_size as u64 {
+            warn!(
+                "--block-size {} is out of range, using {} instead.",
+                format_bytes(given),
+                format_bytes(block_size as u64)
+            );
+        }
+    }
+    if options.scan.no_size_filter {
+        warn!(
+            "--no-size-filter reads every file in full, which is much slower than a normal scan."
+        );
+    }
+    handle_ctrl_c(&options);
+    // the baseline is read first, so a bad one doesn't waste a scan
+    let baseline = options.baseline.as_ref().map(|baseline_file| {
+        File::open(baseline_file)
+            .and_then(|f| read_baseline(BufReader::new(f)))
+            .unwrap_or_else(|e| {
+                error!(
+                    "couldn't read baseline {:?}: {e}",
+                    baseline_file.as_os_str()
+                );
+                process::exit(EXIT_ERROR);
+            })
+    });
+    let mut bench = Bench::default();
+    bench.start();
+    let mut errors = vec![];
+    // the whole file list is only kept when it's needed for the report
+    let (file_list, grouper) = if options.names_only || options.unique {
+        let file_list = build_file_list(&options, &mut errors);
+        let bytes = file_list.iter().map(MetaFile::size).sum();
+        bench.record("walk", file_list.len(), bytes);
+        if options.names_only {
+            let mut namewise_dups = find_namewise_dups(&file_list, &options.scan);
+            if options.output_relative {
+                for paths in namewise_dups.values_mut() {
+                    *paths = (paths.iter())
+                        .map(|p| relative_path(p, &options.target_dirs))
+                        .collect();
+                }
+            }
+            println!(
+                "Found {} names shared by multiple files.",
+                namewise_dups.len()
+            );
+            write_report(|out| print_namewise_dups(out, &namewise_dups));
+            if options.bench {
+                bench.print();
+            }
+            print_errors(&errors, options.verbose);
+            if options.scan.interrupt.is_set() {
+                process::exit(EXIT_INTERRUPTED);
+            }
+            process::exit(exit_code(namewise_dups.len()));
+        }
+        let progress = progress_bar(&options, file_list.len(), "checking sizes");
+        let mut grouper = SizeGrouper::new(&options.scan);
+        for f in file_list.iter().cloned() {
+            progress.inc(1);
+            grouper.add(f);
+        }
+        progress.finish_and_clear();
+        (file_list, grouper)
+    } else {
+        let grouper = group_by_size(&options, &mut errors);
+        bench.record("walk", grouper.file_count(), grouper.total_size());
+        (IndexSet::new(), grouper)
+    };
+    let (mut sizewise_dups, empty_count) = finish_grouping(&options, grouper);
+    let sampled_dups = if options.sample {
+        split_off_sampled(&mut sizewise_dups)
+    } else {
+        SizewiseDups::new()
+    };
+    if empty_count > 0 {
+        info!("Skipped {empty_count} empty files (use --include-empty to include them).");
+    }
+    if options.analyze {
+        let analysis = analyze_sizes(&sizewise_dups, ANALYZE_GROUP_LIMIT);
+        bench.record(
+            "size-group",
+            analysis.candidate_count,
+            analysis.candidate_bytes,
+        );
+        if options.json {
+            write_report(|out| write_analysis_json(out, &analysis));
+        } else {
+            write_report(|out| print_analysis(out, &analysis));
+        }
+        if options.bench {
+            bench.print();
+        }
+        print_errors(&errors, options.verbose);
+        if options.scan.interrupt.is_set() {
+            process::exit(EXIT_INTERRUPTED);
+        }
+        process::exit(EXIT_NO_DUPLICATES);
+    }
+    let candidates = || sizewise_dups.iter().chain(sampled_dups.iter());
+    let candidate_count: usize = candidates().map(|(_, files)| files.len()).sum();
+    let candidate_bytes: u64 = candidates()
+        .map(|(size, files)| size * files.len() as u64)
+        .sum();
+    info!(
         "Found {} groups of files with equal sizes. {} files total.",
-        sizewise_dups.len(),
-        sizewise_dups.values().flatten().count()
+        sizewise_dups.len() + sampled_dups.len(),
+        candidate_count
     );
-    println!("took: {:?}", start.elapsed());
-    start = Instant::now();
-    let dups = find_dups(sizewise_dups);
-    if options.unique {
-        let dup_files: IndexSet<MetaFile> = dups
-            .iter()
-            .map(|(_checksum, files)| files)
-            .cloned()
-            .flatten()
+    bench.record("size-group", candidate_count, candidate_bytes);
+    let pool = match scan::thread_pool(&options.scan) {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("couldn't start {} threads: {e}", options.scan.threads);
+            process::exit(EXIT_ERROR);
+        }
+    };
+    let progress = progress_bar(&options, candidate_count, "calculating checksums");
+    let mut cache = options.cache_file.as_ref().map(|cache_file| {
+        ChecksumCache::load(cache_file).unwrap_or_else(|e| {
+            warn!(
+                "couldn't read cache {:?}, starting a new one: {e}",
+                cache_file.as_os_str()
+            );
+            ChecksumCache::default()
+        })
+    });
+    let mut checkpoint = options.checkpoint.as_ref().map(|checkpoint_file| {
+        let dirs: Vec<PathBuf> = (options.target_dirs.iter().cloned())
+            .chain(
+                options
+                    .path_lists
+                    .iter()
+                    .map(|(list, _)| PathBuf::from(list)),
+            )
             .collect();
-        let mut uniques: Vec<&MetaFile> = file_list.difference(&dup_files).collect();
+        let scan = describe_scan(&dirs, &options.scan);
+        Checkpoint::load(checkpoint_file, &scan).unwrap_or_else(|e| {
+            warn!(
+                "couldn't resume from checkpoint {:?}, starting a new one: {e}",
+                checkpoint_file.as_os_str()
+            );
+            Checkpoint::new(scan)
+        })
+    });
+    let dups = pool.install(|| {
+        if options.json_stream {
+            let mut out = io::stdout().lock();
+            let mut streamed = 0;
+            find_dups_streamed(
+                sizewise_dups,
+                &options.scan,
+                &mut errors,
+                cache.as_mut(),
+                &progress,
+                |group| {
+                    let Some(group) = shown_group(&options, group) else {
+                        return;
+                    };
+                    streamed += 1;
+                    if let Err(e) = write_json_group(&mut out, streamed, &group, options.keep) {
+                        error!("couldn't write output: {e}");
+                        process::exit(EXIT_ERROR);
+                    }
+                },
+            )
+        } else if let (Some(checkpoint_file), Some(checkpoint)) =
+            (&options.checkpoint, checkpoint.as_mut())
+        {
+            if !checkpoint.is_empty() {
+                info!(
+                    "Resuming from checkpoint {:?}.",
+                    checkpoint_file.as_os_str()
+                );
+            }
+            find_dups_checkpointed(
+                sizewise_dups,
+                &options.scan,
+                &mut errors,
+                cache.as_mut(),
+                &progress,
+                checkpoint,
+                |checkpoint| {
+                    if let Err(e) = checkpoint.save(checkpoint_file) {
+                        warn!(
+                            "couldn't write checkpoint {:?}: {e}",
+                            checkpoint_file.as_os_str()
+                        );
+                    }
+                },
+            )
+        } else {
+            find_dups(
+                sizewise_dups,
+                &options.scan,
+                &mut errors,
+                cache.as_mut(),
+                &progress,
+            )
+        }
+    });
+    let mut probable_dups = pool.install(|| {
+        find_probable_dups(
+            sampled_dups,
+            SAMPLE_BLOCK_SIZE,
+            &options.scan.interrupt,
+            &options.scan.read_stats,
+            &mut errors,
+            &progress,
+        )
+    });
+    progress.finish_and_clear();
+    // fewer files than there were candidates are read under --limit
+    info!("Calculated checksums of {} files.", progress.position());
+    let read_stats = &options.scan.read_stats;
+    bench.record_reads("compare", read_stats.compared());
+    bench.record_reads("hash", read_stats.checksummed());
+    bench.record_reads("sample", read_stats.sampled());
+    bench.start();
+    if let (Some(cache_file), Some(cache)) = (&options.cache_file, &cache) {
+        if let Err(e) = cache.save(cache_file) {
+            warn!("couldn't write cache {:?}: {e}", cache_file.as_os_str());
+        }
+    }
+    // nothing is listed until the scan has stopped, so an interrupt can't
+    // cut the report short
+    let interrupted = options.scan.interrupt.is_set();
+    if interrupted {
+        // files which weren't read would be listed as unique, and groups
+        // which weren't found as gone
+        if options.unique || baseline.is_some() {
+            error!("interrupted before every file was compared, so there's nothing to list.");
+            print_errors(&errors, options.verbose);
+            process::exit(EXIT_INTERRUPTED);
+        }
+        warn!("interrupted, only the duplicates found so far are listed.");
+    }
+    let mut dups = sorted_groups(dups);
+    let comparing_sets = !options.set_a.is_empty();
+    if comparing_sets {
+        dups = cross_set_groups(dups, &options.set_a, &options.set_b);
+    }
+    sort_groups(&mut dups, options.sort);
+    // paths are only made relative once groups have been sorted by them
+    let relative = |p: &Path| relative_path(p, &options.target_dirs);
+    if options.output_relative {
+        dups = dups.into_iter().map(|d| d.map_paths(relative)).collect();
+        probable_dups = (probable_dups.into_iter())
+            .map(|g| g.map_paths(relative))
+            .collect();
+    }
+    let color = options.color.enabled();
+    let mut found = dups.len() + probable_dups.len();
+    if let Some(baseline) = baseline {
+        let diff = diff_baseline(&dups, baseline);
+        found = diff.new.len() + diff.gone.len();
+        if !options.quiet || options.summary_only {
+            println!(
+                "Found {} new groups of duplicates and {} gone since the baseline.",
+                diff.new.len(),
+                diff.gone.len()
+            );
+        }
+        if !options.summary_only {
+            write_report(|out| {
+                print_baseline_diff(out, &diff, options.keep, options.decimal_checksums, color)
+            });
+        }
+    } else if options.unique {
+        let dup_files: IndexSet<MetaFile> = dups.iter().flat_map(|d| d.files()).cloned().collect();
+        let mut uniques: Vec<MetaFile> = file_list.difference(&dup_files).cloned().collect();
         uniques.sort();
-        for unique in uniques {
-            println!("{unique}");
+        if options.output_relative {
+            uniques = uniques.into_iter().map(|f| f.map_paths(relative)).collect();
         }
+        write_report(|out| print_uniques(out, &uniques, options.print0));
+    } else if options.print0 {
+        write_report(|out| print_dups0(out, &dups, options.keep));
+    } else if options.csv {
+        write_report(|out| print_csv(out, &dups, options.keep));
+    } else if let Some(which) = options.paths_only {
+        write_report(|out| print_paths_only(out, &dups, options.keep, which));
+    } else if options.json_stream {
+        // the groups were listed as they were found
     } else {
-        println!("Found {} duplicates.", dups.len());
-        if dups.len() < 25 || !atty::is(Stream::Stdout) {
-            print_dups(&dups);
+        if !options.quiet || options.summary_only {
+            let redundant_count: usize = dups.iter().map(|d| d.files().len() - 1).sum();
+            println!("Found {} duplicates.", dups.len());
+            let reclaimable =
+                format_bytes(dups.iter().map(DuplicateGroup::reclaimable_space).sum());
+            println!(
+                "{} reclaimable by removing {} redundant files across {} groups.",
+                paint(reclaimable, HEADER_STYLE, color),
+                redundant_count,
+                dups.len()
+            );
+            let hardlink_count: usize = dups.iter().map(DuplicateGroup::hardlink_count).sum();
+            if hardlink_count > 0 {
+                println!(
+                    "{hardlink_count} more paths are hard links to these files, which take up no extra space."
+                );
+            }
+        }
+        if !options.summary_only {
+            write_report(|out| {
+                if comparing_sets {
+                    print_cross_set_dups(
+                        out,
+                        &dups,
+                        &options.set_a,
+                        &options.set_b,
+                        options.decimal_checksums,
+                        color,
+                    )
+                } else {
+                    print_dups(
+                        out,
+                        &dups,
+                        options.keep,
+                        options.annotate,
+                        options.decimal_checksums,
+                        color,
+                    )
+                }
+            });
         }
+        if options.sample {
+            if !options.quiet || options.summary_only {
+                println!(
+                    "Found {} probable duplicates (sampled).",
+                    probable_dups.len()
+                );
+            }
+            if !options.summary_only {
+                write_report(|out| print_probable_dups(out, &probable_dups, color));
+            }
+        }
+        if let Some(action) = options.action {
+            let decisions = if options.interactive && !dups.is_empty() {
+                ask_decisions(&dups, action, options.keep, &options.scan.interrupt)
+            } else {
+                planned_decisions(&dups, options.keep)
+            };
+            if let Some(decisions_file) = &options.save_decisions {
+                if !options.scan.interrupt.is_set() {
+                    save_decisions(decisions_file, action, &decisions);
+                }
+            }
+            // there's nothing to confirm when no files would be changed, and
+            // answering --interactive already confirms the changes
+            let confirmed = options.dry_run
+                || options.yes
+                || options.interactive
+                || decisions.is_empty()
+                || confirm_action(&decisions, action);
+            // an interrupt while deciding or confirming is taken as declining
+            if options.scan.interrupt.is_set() {
+                println!("Interrupted, no files were changed.");
+            } else if confirmed {
+                apply_action(&decisions, action, options.dry_run);
+            } else {
+                println!("Cancelled, no files were changed.");
+            }
+        }
+    }
+    if let Some(limit) = options.scan.hash_limit {
+        warn!(
+            "only the first {} of each file were compared, files which differ after that are listed as duplicates.",
+            format_bytes(limit)
+        );
+    }
+    if options.bench {
+        bench.print();
+    }
+    print_errors(&errors, options.verbose);
+    if options.scan.interrupt.is_set() {
+        process::exit(EXIT_INTERRUPTED);
     }
-    println!("took: {:?}", start.elapsed());
+    process::exit(exit_code(found));
 }
@@ -1,10 +1,12 @@
-use std::fs;
+use std::fs::{self, Metadata};
 use std::io;
 use std::path::Path;
 
 /* id from the OS; this must be an identifier that any two
 files that are linked together (hardly or symbolically) will share;
-inode on unix, nFileIndex{Low,High} on windows */
+inode on unix, nFileIndex{Low,High} on windows. Symlinks are followed, so
+that a symlink shares the id of the file it points at rather than having its
+own; `MetaFile` keeps track of which paths are symlinks separately */
 
 #[cfg(unix)]
 pub fn get_file_identifier(fp: &Path) -> io::Result<u64> {
@@ -14,15 +16,182 @@ pub fn get_file_identifier(fp: &Path) -> io::Result<u64> {
     Ok(md.ino())
 }
 
+/* like `get_file_identifier`, but on unix, where the id is part of a file's
+metadata, `md` is used rather than stat'ing `fp` again */
+
+#[cfg(unix)]
+pub fn file_identifier_from_metadata(_fp: &Path, md: &Metadata) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(md.ino())
+}
+
 #[cfg(windows)]
-pub fn get_file_identifier(fp: &Path) -> io::Result<u64> {
-    /* on windows, we can use the nFileIndex{Low,High} as a file identifier. */
-    use std::os::windows::fs::MetadataExt;
+pub fn file_identifier_from_metadata(fp: &Path, _md: &Metadata) -> io::Result<u64> {
+    get_file_identifier(fp)
+}
+
+/* id of the device or volume a file is on, so that files on different
+filesystems can be told apart; st_dev on unix, dwVolumeSerialNumber on
+windows */
+
+#[cfg(unix)]
+pub fn get_device_identifier(fp: &Path) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
     let md = fs::metadata(fp)?;
-    // SAFETY: it is statically guaranteed that the call to `file_index` will be some.
-    // From the `file_index` docs:
-    // "This will return `None` if the `Metadata` instance was created from a call to
-    // `DirEntry::metadata`. If this `Metadata` was created by using `fs::metadata` or
-    // `File::metadata`, then this will return `Some`."
-    Ok(unsafe { md.file_index().unwrap_unchecked() })
+    Ok(md.dev())
+}
+
+/* like `get_device_identifier`, but on unix `md` is used rather than
+stat'ing `fp` again */
+
+#[cfg(unix)]
+pub fn device_identifier_from_metadata(_fp: &Path, md: &Metadata) -> io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(md.dev())
+}
+
+#[cfg(windows)]
+pub fn device_identifier_from_metadata(fp: &Path, _md: &Metadata) -> io::Result<u64> {
+    get_device_identifier(fp)
+}
+
+#[cfg(windows)]
+pub fn get_file_identifier(fp: &Path) -> io::Result<u64> {
+    /* on windows, we can use the nFileIndex{Low,High} as a file identifier.
+    `MetadataExt::file_index` exposes it, but isn't available on stable, so
+    it is read with `GetFileInformationByHandle` instead. */
+    let info = windows::file_information(fp)?;
+    Ok((u64::from(info.file_index_high) << 32) | u64::from(info.file_index_low))
+}
+
+#[cfg(windows)]
+pub fn get_device_identifier(fp: &Path) -> io::Result<u64> {
+    /* like the file index, `MetadataExt::volume_serial_number` isn't
+    available on stable. */
+    let info = windows::file_information(fp)?;
+    Ok(u64::from(info.volume_serial_number))
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    #[repr(C)]
+    pub struct FileTime {
+        low_date_time: u32,
+        high_date_time: u32,
+    }
+
+    // BY_HANDLE_FILE_INFORMATION from fileapi.h
+    #[repr(C)]
+    pub struct ByHandleFileInformation {
+        pub file_attributes: u32,
+        pub creation_time: FileTime,
+        pub last_access_time: FileTime,
+        pub last_write_time: FileTime,
+        pub volume_serial_number: u32,
+        pub file_size_high: u32,
+        pub file_size_low: u32,
+        pub number_of_links: u32,
+        pub file_index_high: u32,
+        pub file_index_low: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetFileInformationByHandle(
+            file: *mut c_void,
+            file_information: *mut ByHandleFileInformation,
+        ) -> i32;
+    }
+
+    pub fn file_information(fp: &Path) -> io::Result<ByHandleFileInformation> {
+        use std::mem::MaybeUninit;
+        use std::os::windows::fs::OpenOptionsExt;
+        use std::os::windows::io::AsRawHandle;
+
+        // needed to open directories; like `fs::metadata`, symlinks are followed.
+        // paths longer than MAX_PATH, including UNC paths, are given the
+        // `\\?\` prefix by std, so they can be opened too.
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x02000000;
+        let file = fs::OpenOptions::new()
+            .access_mode(0)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+            .open(fp)?;
+        let mut info = MaybeUninit::<ByHandleFileInformation>::uninit();
+        // SAFETY: `file` is an open handle for the duration of the call, and
+        // `info` points to writable memory of the right size and layout.
+        if unsafe { GetFileInformationByHandle(file.as_raw_handle(), info.as_mut_ptr()) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `GetFileInformationByHandle` succeeded, so it filled in `info`.
+        Ok(unsafe { info.assume_init() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    use super::{get_device_identifier, get_file_identifier};
+
+    #[test]
+    fn hard_links_share_identifier() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-file-id");
+        fs::create_dir(root)?;
+        fs::write(root.join("file1"), "meow")?;
+        fs::write(root.join("file2"), "meow")?;
+        fs::hard_link(root.join("file1"), root.join("file1-hardlink"))?;
+        /* test */
+        let id = get_file_identifier(&root.join("file1"))?;
+        assert_eq!(get_file_identifier(&root.join("file1-hardlink"))?, id);
+        assert_ne!(get_file_identifier(&root.join("file2"))?, id);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_share_identifier() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-symlink-id");
+        fs::create_dir(root)?;
+        fs::write(root.join("file"), "meow")?;
+        std::os::unix::fs::symlink("file", root.join("file-symlink"))?;
+        /* test */
+        assert_eq!(
+            get_file_identifier(&root.join("file-symlink"))?,
+            get_file_identifier(&root.join("file"))?
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn device_identifier() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-device-id");
+        fs::create_dir_all(root.join("nested"))?;
+        fs::write(root.join("nested").join("file"), "meow")?;
+        /* test */
+        let device = get_device_identifier(root)?;
+        assert_eq!(get_device_identifier(&root.join("nested"))?, device);
+        assert_eq!(
+            get_device_identifier(&root.join("nested").join("file"))?,
+            device
+        );
+        // procfs is always its own filesystem, where there is one
+        #[cfg(target_os = "linux")]
+        if Path::new("/proc/self").exists() {
+            assert_ne!(get_device_identifier(Path::new("/proc/self"))?, device);
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
 }
@@ -1,85 +1,273 @@
 use std::cmp::Ordering;
 use std::fmt;
-use std::fs;
+use std::fs::{self, Metadata};
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 mod c_command;
 mod file_id;
-use file_id::get_file_identifier;
+pub(crate) use file_id::get_device_identifier;
+use file_id::{
+    device_identifier_from_metadata, file_identifier_from_metadata, get_file_identifier,
+};
 
 use indexmap::{indexset, IndexSet};
+use rayon::prelude::*;
+
+/// what a file's metadata said when it was found. Each path is stat'd once,
+/// when it's added to a `MetaFile`, and later phases read from this rather
+/// than stat'ing it again.
+/// ## Note:
+/// Symlinks are followed, and special files are never added, so a file is
+/// either a regular file or, when directories are kept, a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub id: u64, /* id from the OS; this must be an identifier that any two
+                 files that are linked together (hardly or symbolically) will
+                 share; inode on unix, nFileIndex{Low,High} on windows.
+                 Symlinks are followed, so a symlink has the id of the file
+                 it points at */
+    pub size: u64,
+    pub mtime: Option<SystemTime>, /* `None` where the platform doesn't
+                                   record it */
+    pub is_dir: bool,
+}
+
+impl FileStat {
+    pub fn from_metadata(p: &Path, metadata: &Metadata) -> io::Result<Self> {
+        Ok(Self {
+            id: file_identifier_from_metadata(p, metadata)?,
+            size: metadata.len(),
+            mtime: metadata.modified().ok(),
+            is_dir: metadata.is_dir(),
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct MetaFile {
-    id: u64,                     /* id from the OS; this must be an identifier that any two
-                                 files that are linked together (hardly or symbolicaly) will share;
-                                 inode on unix, nFileIndex{Low,High} on windows */
-    files: IndexSet<PathBuf>, /* paths to files which share `id` as their identifier */
-    symlinks: IndexSet<PathBuf>, /* paths to symlinks which share `id` as their identifier */
+    stat: FileStat,              /* from the first of its paths to be found */
+    files: IndexSet<PathBuf>,    /* paths to files which share `stat.id` as their identifier */
+    symlinks: IndexSet<PathBuf>, /* paths to symlinks which share `stat.id` as their identifier */
 }
 
 impl MetaFile {
-    pub fn new(id: u64, files: IndexSet<PathBuf>, symlinks: IndexSet<PathBuf>) -> Self {
-        Self {
+    /// a file whose modification time isn't known, e.g. for tests. Files
+    /// which are found by stat'ing them get theirs from `from_stat`.
+    pub fn new(id: u64, size: u64, files: IndexSet<PathBuf>, symlinks: IndexSet<PathBuf>) -> Self {
+        let stat = FileStat {
             id,
+            size,
+            mtime: None,
+            is_dir: false,
+        };
+        Self {
+            stat,
             files,
             symlinks,
         }
     }
 
-    pub fn from_id_and_path(id: u64, file: PathBuf) -> Self {
-        let mut files = indexset![];
-        let mut symlinks = indexset![];
-        if file.is_symlink() {
-            symlinks.insert(file);
-        } else {
-            files.insert(file);
-        }
-        Self::new(id, files, symlinks)
-    }
-
-    pub fn from_id(id: u64) -> Self {
+    /// a `MetaFile` without any paths, for a file which was stat'd.
+    pub fn from_stat(stat: FileStat) -> Self {
         Self {
-            id,
+            stat,
             files: indexset![],
             symlinks: indexset![],
         }
     }
 
+    pub fn from_id_and_path(id: u64, size: u64, file: PathBuf) -> Self {
+        let mut mf = Self::new(id, size, indexset![], indexset![]);
+        mf.add_path(file);
+        mf
+    }
+
+    /// a `MetaFile` without any paths, e.g. for looking up the `MetaFile`
+    /// with a given id in a set.
+    pub fn from_id(id: u64) -> Self {
+        Self::new(id, 0, indexset![], indexset![])
+    }
+
+    #[allow(clippy::result_unit_err)]
     pub fn try_add_path(&mut self, p: PathBuf) -> Result<bool, ()> {
-        if get_file_identifier(&p).is_ok_and(|id| id == self.id) {
-            if p.is_symlink() {
-                Ok(self.symlinks.insert(p))
-            } else {
-                Ok(self.files.insert(p))
-            }
+        if get_file_identifier(&p).is_ok_and(|id| id == self.stat.id) {
+            Ok(self.add_path(p))
         } else {
             Err(())
         }
     }
 
+    /// like `try_add_path`, for callers which already know the identifier of
+    /// `p`, e.g. from stat'ing it, so it isn't looked up again. `id` is only
+    /// checked against `self.id` in debug builds.
+    pub fn add_path_with_id(&mut self, p: PathBuf, id: u64) -> bool {
+        debug_assert_eq!(id, self.stat.id, "{p:?} isn't a path to this file");
+        self.add_path(p)
+    }
+
+    // for paths which are already known to have `self.id` as their identifier
+    fn add_path(&mut self, p: PathBuf) -> bool {
+        let is_symlink = p.is_symlink();
+        self.add_path_of_kind(p, is_symlink)
+    }
+
+    fn add_path_of_kind(&mut self, p: PathBuf, is_symlink: bool) -> bool {
+        if is_symlink {
+            self.add_symlink(p)
+        } else {
+            self.add_file(p)
+        }
+    }
+
+    /// adds `p`, a path which isn't a symlink, without checking that it is a
+    /// path to this file. Returns whether it was new.
+    pub fn add_file(&mut self, p: PathBuf) -> bool {
+        self.files.insert(p)
+    }
+
+    /// adds `p`, a path to a symlink which points at this file, without
+    /// checking either. Returns whether it was new.
+    pub fn add_symlink(&mut self, p: PathBuf) -> bool {
+        self.symlinks.insert(p)
+    }
+
     pub fn id(&self) -> u64 {
-        self.id
+        self.stat.id
+    }
+
+    /// the size of the file when it was found.
+    pub fn size(&self) -> u64 {
+        self.stat.size
+    }
+
+    /// when the file was last modified, as of when it was found.
+    pub fn mtime(&self) -> Option<SystemTime> {
+        self.stat.mtime
+    }
+
+    pub fn stat(&self) -> &FileStat {
+        &self.stat
+    }
+
+    pub fn files(&self) -> &IndexSet<PathBuf> {
+        &self.files
+    }
+
+    pub fn symlinks(&self) -> &IndexSet<PathBuf> {
+        &self.symlinks
+    }
+
+    /// adds the paths of `other`, which must be the same file, after the
+    /// paths of `self`.
+    pub fn merge(&mut self, other: MetaFile) {
+        debug_assert_eq!(self.stat.id, other.stat.id);
+        self.files.extend(other.files);
+        self.symlinks.extend(other.symlinks);
+    }
+
+    /// the same file, with each of its paths replaced by `f` of it, e.g. to
+    /// print them relative to a directory. See `scan::relative_path`.
+    pub fn map_paths(self, f: impl Fn(&Path) -> PathBuf) -> Self {
+        Self {
+            files: self.files.iter().map(|p| f(p)).collect(),
+            symlinks: self.symlinks.iter().map(|p| f(p)).collect(),
+            ..self
+        }
+    }
+
+    /// the same file, without the paths of the symlinks to it.
+    pub fn without_symlinks(mut self) -> Self {
+        self.symlinks.clear();
+        self
     }
 
+    /// stats each path again, and splits off the paths which turn out not to
+    /// be links to the same file as the first one, e.g. on network
+    /// filesystems which report identifiers inconsistently, or on different
+    /// devices which happen to share an identifier. The first part keeps
+    /// `self.stat`, and the others get their ids from `fresh_id` and the rest
+    /// of their stats from the new stat. Paths which can't be stat'd are kept
+    /// in the first part.
+    pub fn split_mislinked(self, mut fresh_id: impl FnMut() -> u64) -> Vec<MetaFile> {
+        let paths = (self.files.into_iter().map(|p| (p, false)))
+            .chain(self.symlinks.into_iter().map(|p| (p, true)));
+        // the device and file identifiers of the first path in each part
+        let mut identities: Vec<Option<(u64, u64)>> = vec![];
+        let mut parts: Vec<MetaFile> = vec![];
+        for (p, is_symlink) in paths {
+            let metadata = fs::metadata(&p).ok();
+            let identity = metadata.as_ref().and_then(|md| {
+                let device = device_identifier_from_metadata(&p, md).ok()?;
+                Some((device, file_identifier_from_metadata(&p, md).ok()?))
+            });
+            let part = if parts.is_empty() {
+                None
+            } else if identity.is_none() {
+                Some(0)
+            } else {
+                identities.iter().position(|i| *i == identity)
+            };
+            let part = part.unwrap_or_else(|| {
+                let stat = match (parts.is_empty(), &metadata) {
+                    (false, Some(md)) => FileStat {
+                        id: fresh_id(),
+                        size: md.len(),
+                        mtime: md.modified().ok(),
+                        is_dir: md.is_dir(),
+                    },
+                    _ => self.stat,
+                };
+                identities.push(identity);
+                parts.push(MetaFile::from_stat(stat));
+                parts.len() - 1
+            });
+            parts[part].add_path_of_kind(p, is_symlink);
+        }
+        parts
+    }
+
+    /// the path which stands for the file, e.g. when it's read, listed or
+    /// kept: the smallest of its paths which isn't a symlink, so that it
+    /// doesn't depend on the order its paths were found in. Files which were
+    /// only found through symlinks use the smallest symlink instead.
+    /// ## Panics:
+    /// If `self` has no paths, like a `MetaFile` from `from_id`.
+    pub fn primary(&self) -> &PathBuf {
+        self.primary_path().expect("a MetaFile with paths")
+    }
+
+    fn primary_path(&self) -> Option<&PathBuf> {
+        self.files
+            .iter()
+            .min()
+            .or_else(|| self.symlinks.iter().min())
+    }
+
+    /// the primary path first, followed by the other paths to the file and
+    /// then the symlinks to it, in the order they were found.
     pub fn paths(&self) -> IndexSet<&PathBuf> {
-        self.files.union(&self.symlinks).collect()
+        (self.primary_path().into_iter())
+            .chain(&self.files)
+            .chain(&self.symlinks)
+            .collect()
     }
 
     pub fn c_commands(&self, other: &Self) -> bool {
-        c_command::c_commands(self.paths()[0], other.paths()[0])
+        c_command::c_commands(self.primary(), other.primary())
     }
 }
 
 impl Hash for MetaFile {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
+        self.stat.id.hash(state);
     }
 }
 
 impl PartialEq for MetaFile {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.stat.id == other.stat.id
     }
 }
 
@@ -87,7 +275,7 @@ impl Eq for MetaFile {}
 
 impl Ord for MetaFile {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.paths()[0].cmp(&other.paths()[0])
+        self.primary().cmp(other.primary())
     }
 }
 
@@ -99,52 +287,134 @@ impl PartialOrd for MetaFile {
 
 impl fmt::Display for MetaFile {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.paths()[0].as_os_str().to_string_lossy())?;
-        if self.paths().len() > 1 {
-            write!(f, " (aka ")?;
+        // symlinks are marked as such, so they aren't mistaken for copies
+        let paths = self.paths();
+        if paths.is_empty() {
+            return Ok(());
         }
-        for idx in 1..(self.paths().len() - 1) {
-            let de = &self.paths()[idx];
-            write!(f, "{:?}, ", de.as_os_str().to_string_lossy())?;
+        let describe = |p: &PathBuf| {
+            let kind = if self.symlinks.contains(p) {
+                "symlink "
+            } else {
+                ""
+            };
+            format!("{kind}{:?}", p.as_os_str().to_string_lossy())
+        };
+        write!(f, "{}", describe(paths[0]))?;
+        for (idx, alias) in paths.iter().skip(1).enumerate() {
+            let sep = if idx == 0 { " (aka " } else { ", " };
+            write!(f, "{sep}{}", describe(alias))?;
         }
-        if self.paths().len() > 1 {
-            write!(
-                f,
-                "{:?})",
-                self.paths()[self.paths().len() - 1]
-                    .as_os_str()
-                    .to_string_lossy()
-            )?;
+        if paths.len() > 1 {
+            write!(f, ")")?;
         }
         Ok(())
     }
 }
 
+/// a list of paths which were left out of a scan, along with why.
+pub type FileErrors = Vec<(PathBuf, io::Error)>;
+
+/// a path to add to a set of `MetaFile`s, along with whether it's a symlink
+/// when that's already known, e.g. from the `DirEntry` it was found through,
+/// so that it isn't looked up again.
+#[derive(Debug, Clone)]
+pub struct FoundPath {
+    pub path: PathBuf,
+    pub is_symlink: Option<bool>,
+}
+
+impl From<PathBuf> for FoundPath {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            path,
+            is_symlink: None,
+        }
+    }
+}
+
+// what's needed to add a path to a set of `MetaFile`s: its stat, and
+// whether it's a symlink.
+struct PathInfo {
+    stat: FileStat,
+    is_symlink: bool,
+}
+
+// special files, like named pipes, sockets and devices, are an error rather
+// than being left out quietly: reading one could block forever or never end.
+fn path_info(
+    p: &Path,
+    is_symlink: Option<bool>,
+    keep_dirs: bool,
+    keep: impl Fn(&Metadata) -> io::Result<bool>,
+) -> io::Result<Option<PathInfo>> {
+    let metadata = fs::metadata(p)?;
+    if metadata.is_dir() && !keep_dirs {
+        return Ok(None);
+    }
+    if !metadata.is_dir() && !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "not a regular file, e.g. a pipe or a device",
+        ));
+    }
+    if !keep(&metadata)? {
+        return Ok(None);
+    }
+    Ok(Some(PathInfo {
+        stat: FileStat::from_metadata(p, &metadata)?,
+        is_symlink: is_symlink.unwrap_or_else(|| p.is_symlink()),
+    }))
+}
+
+/// paths whose file identifier can't be determined, and paths to special
+/// files, are left out and added to `errors`. Each path is stat'd once, and
+/// the stat of each `MetaFile` is taken from the first of its paths to be
+/// found, see `FileStat`. Paths which aren't known to be symlinks or not, see `FoundPath`,
+/// are checked with an extra `lstat`.
+/// ## Note:
+/// Paths are stat'd in parallel, and then added to `acc` in the order they
+/// were given in, so the result is the same as adding them one by one.
 pub fn collect_into_metafiles(
     acc: &mut IndexSet<MetaFile>,
-    paths: impl IntoIterator<Item = PathBuf>,
+    paths: impl IntoIterator<Item = impl Into<FoundPath>>,
     keep_dirs: bool,
+    errors: &mut FileErrors,
 ) {
-    for p in paths {
-        if !keep_dirs && fs::metadata(&p).map_or(false, |d| d.is_dir()) {
-            continue;
-        }
-        let id = match get_file_identifier(&p) {
-            Ok(id) => id,
+    collect_into_metafiles_filtered(acc, paths, keep_dirs, |_| Ok(true), errors);
+}
+
+/// like `collect_into_metafiles`, but paths are only added when `keep`
+/// returns true for their metadata. Paths for which `keep` returns an error
+/// are left out and added to `errors`.
+pub fn collect_into_metafiles_filtered(
+    acc: &mut IndexSet<MetaFile>,
+    paths: impl IntoIterator<Item = impl Into<FoundPath>>,
+    keep_dirs: bool,
+    keep: impl Fn(&Metadata) -> io::Result<bool> + Sync,
+    errors: &mut FileErrors,
+) {
+    let paths: Vec<FoundPath> = paths.into_iter().map(Into::into).collect();
+    let infos: Vec<io::Result<Option<PathInfo>>> = paths
+        .par_iter()
+        .map(|p| path_info(&p.path, p.is_symlink, keep_dirs, &keep))
+        .collect();
+    for (FoundPath { path: p, .. }, info) in paths.into_iter().zip(infos) {
+        let info = match info {
+            Ok(Some(info)) => info,
+            Ok(None) => continue,
             Err(e) => {
-                eprintln!("Skipping error:\n {e}");
+                errors.push((p, e));
                 continue;
             }
         };
-        match acc.take(&MetaFile::from_id(id)) {
-            Some(mut mf) => {
-                assert!(mf.try_add_path(p).is_ok());
-                assert!(acc.insert(mf));
-            }
-            None => {
-                assert!(acc.insert(MetaFile::from_id_and_path(id, p)));
-            }
-        }
+        let mut mf = acc
+            .take(&MetaFile::from_id(info.stat.id))
+            .unwrap_or_else(|| MetaFile::from_stat(info.stat));
+        mf.add_path_of_kind(p, info.is_symlink);
+        // any `MetaFile` with the same id was just taken out
+        let inserted = acc.insert(mf);
+        debug_assert!(inserted);
     }
 }
 
@@ -156,15 +426,89 @@ mod test {
 
     use indexmap::indexset;
 
-    use super::collect_into_metafiles;
+    use super::{collect_into_metafiles, get_file_identifier, FoundPath, MetaFile};
+
+    #[test]
+    fn display() {
+        let one = MetaFile::new(0, 4, indexset![PathBuf::from("a")], indexset![]);
+        assert_eq!(one.to_string(), r#""a""#);
+        let two = MetaFile::new(
+            0,
+            4,
+            indexset![PathBuf::from("a")],
+            indexset![PathBuf::from("b")],
+        );
+        assert_eq!(two.to_string(), r#""a" (aka symlink "b")"#);
+        let three = MetaFile::new(
+            0,
+            4,
+            indexset![PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")],
+            indexset![],
+        );
+        assert_eq!(three.to_string(), r#""a" (aka "b", "c")"#);
+    }
+
+    #[test]
+    fn primary() {
+        let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect();
+        // the smallest file, whatever order the paths were found in
+        let mf = MetaFile::new(0, 4, paths(&["c", "b", "d"]), paths(&["a"]));
+        assert_eq!(mf.primary(), &PathBuf::from("b"));
+        assert_eq!(
+            mf.paths().into_iter().collect::<Vec<_>>(),
+            ["b", "c", "d", "a"]
+                .map(PathBuf::from)
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(mf.to_string(), r#""b" (aka "c", "d", symlink "a")"#);
+        let reversed = MetaFile::new(1, 4, paths(&["d", "b", "c"]), paths(&["a"]));
+        assert_eq!(reversed.primary(), mf.primary());
+        assert_eq!(reversed.cmp(&mf), std::cmp::Ordering::Equal);
+        // symlinks are only primary when there's nothing else
+        let symlinks_only = MetaFile::new(2, 4, paths(&[]), paths(&["z", "y"]));
+        assert_eq!(symlinks_only.primary(), &PathBuf::from("y"));
+        assert!(MetaFile::from_id(3).paths().is_empty());
+    }
+
+    #[test]
+    fn stat_once() -> io::Result<()> {
+        /* setup */
+        let root = PathBuf::from("test-tmp-stat-once");
+        fs::create_dir(&root)?;
+        let (file, dir) = (root.join("file"), root.join("dir"));
+        fs::write(&file, "meow")?;
+        fs::create_dir(&dir)?;
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            [file.clone(), dir.clone()],
+            true,
+            &mut vec![],
+        );
+        /* test */
+        let fresh = fs::metadata(&file)?;
+        let stat = metafiles[0].stat();
+        assert_eq!(stat.size, fresh.len());
+        assert_eq!(metafiles[0].size(), fresh.len());
+        assert_eq!(stat.id, get_file_identifier(&file)?);
+        assert_eq!(metafiles[0].mtime(), Some(fresh.modified()?));
+        assert!(!stat.is_dir);
+        assert!(metafiles[1].stat().is_dir);
+        // the file isn't stat'd again once it has been found
+        fs::write(&file, "nyaa!")?;
+        assert_eq!(metafiles[0].size(), 4);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
 
     #[test]
     fn metafiles_hard_link() -> io::Result<()> {
         /* setup */
-        let file2 = PathBuf::from("test-tmp/file2");
-        let file1 = PathBuf::from("test-tmp/file1");
-        let link = PathBuf::from("test-tmp/file1-hardlink");
-        fs::create_dir("test-tmp")?;
+        let file2 = PathBuf::from("test-tmp-hard-link/file2");
+        let file1 = PathBuf::from("test-tmp-hard-link/file1");
+        let link = PathBuf::from("test-tmp-hard-link/file1-hardlink");
+        fs::create_dir("test-tmp-hard-link")?;
         fs::write(&file1, "meow")?;
         fs::write(&file2, "nya")?;
         fs::hard_link(&file1, &link)?;
@@ -174,58 +518,177 @@ mod test {
             &mut metafiles,
             [file1.clone(), file2.clone(), link.clone()],
             false,
+            &mut vec![],
         );
         dbg!(&metafiles);
 
         assert_eq!(metafiles.len(), 2);
         for file in &metafiles {
-            assert!(file.paths() == indexset![&file2] || file.paths() == indexset![&file1, &link])
+            assert!(file.paths() == indexset![&file2] || file.paths() == indexset![&file1, &link]);
+            assert_eq!(file.size(), if file.paths()[0] == &file2 { 3 } else { 4 });
         }
         /* cleanup */
-        fs::remove_dir_all("test-tmp")
+        fs::remove_dir_all("test-tmp-hard-link")
+    }
+
+    #[test]
+    fn add_path_with_id() -> io::Result<()> {
+        /* setup */
+        let root = PathBuf::from("test-tmp-add-path-with-id");
+        let file1 = root.join("file1");
+        let file2 = root.join("file2");
+        let link = root.join("file1-hardlink");
+        fs::create_dir(&root)?;
+        fs::write(&file1, "meow")?;
+        fs::write(&file2, "nya")?;
+        fs::hard_link(&file1, &link)?;
+        /* test */
+        let id = get_file_identifier(&file1)?;
+        let mut validated = MetaFile::from_id_and_path(id, 4, file1.clone());
+        assert_eq!(validated.try_add_path(link.clone()), Ok(true));
+        assert_eq!(validated.try_add_path(file2.clone()), Err(()));
+        let mut fast = MetaFile::from_id_and_path(id, 4, file1.clone());
+        assert!(fast.add_path_with_id(link.clone(), get_file_identifier(&link)?));
+        assert!(!fast.add_path_with_id(link.clone(), id));
+        assert_eq!(fast.paths(), validated.paths());
+        assert_eq!(fast.paths(), indexset![&file1, &link]);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn split_mislinked() -> io::Result<()> {
+        /* setup */
+        let root = PathBuf::from("test-tmp-split-mislinked");
+        let file1 = root.join("file1");
+        let file2 = root.join("file2");
+        let link = root.join("file1-hardlink");
+        fs::create_dir(&root)?;
+        fs::write(&file1, "meow")?;
+        fs::write(&file2, "nyaa!")?;
+        fs::hard_link(&file1, &link)?;
+        /* test */
+        let id = get_file_identifier(&file1)?;
+        // files which really are linked aren't split
+        let linked = MetaFile::new(id, 4, indexset![file1.clone(), link.clone()], indexset![]);
+        let parts = linked.split_mislinked(|| unreachable!());
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].paths(), indexset![&file1, &link]);
+        // as if file2 had been reported with the identifier of file1
+        let collided = MetaFile::new(
+            id,
+            4,
+            indexset![file1.clone(), file2.clone(), link.clone()],
+            indexset![],
+        );
+        let parts = collided.split_mislinked(|| 42);
+        assert_eq!(parts.len(), 2);
+        assert_eq!((parts[0].id(), parts[0].size()), (id, 4));
+        assert_eq!(parts[0].paths(), indexset![&file1, &link]);
+        assert_eq!((parts[1].id(), parts[1].size()), (42, 5));
+        assert_eq!(parts[1].paths(), indexset![&file2]);
+        /* cleanup */
+        fs::remove_dir_all(root)
     }
 
-    #[ignore]
     #[test]
     fn metafiles_symlink() -> io::Result<()> {
         /* setup */
-        let file2 = PathBuf::from("test-tmp/file2");
-        let file1 = PathBuf::from("test-tmp/file1");
-        let link = PathBuf::from("test-tmp/file1-symlink");
-        fs::create_dir("test-tmp")?;
+        let file2 = PathBuf::from("test-tmp-symlink/file2");
+        let file1 = PathBuf::from("test-tmp-symlink/file1");
+        let link = PathBuf::from("test-tmp-symlink/file1-symlink");
+        fs::create_dir("test-tmp-symlink")?;
         fs::write(&file1, "meow")?;
         fs::write(&file2, "nya")?;
+        // the target is relative to the link's directory
         #[cfg(unix)]
-        {
-            std::os::unix::fs::symlink(&file1, &link)?
-        }
+        std::os::unix::fs::symlink("file1", &link)?;
         #[cfg(windows)]
-        {
-            dbg!(std::process::Command::new("powershell")
-                .arg("-Command")
-                .arg("New-Item")
-                .arg("-ItemType")
-                .arg("SymbolicLink")
-                .arg("-Path")
-                .arg("test-tmp\\file1-symlink")
-                .arg("-Target")
-                .arg("test-tmp\\file1")
-                .output()?);
+        match std::os::windows::fs::symlink_file("file1", &link) {
+            Ok(()) => {}
+            // ERROR_PRIVILEGE_NOT_HELD: creating symlinks requires developer
+            // mode or an elevated prompt
+            Err(e) if e.raw_os_error() == Some(1314) => {
+                return fs::remove_dir_all("test-tmp-symlink");
+            }
+            Err(e) => return Err(e),
         }
         /* test */
         let mut metafiles = indexset![];
+        let mut errors = vec![];
         collect_into_metafiles(
             &mut metafiles,
             [file1.clone(), file2.clone(), link.clone()],
             false,
+            &mut errors,
         );
         dbg!(&metafiles);
 
+        assert!(errors.is_empty());
+        // the symlink is followed, so it shares the identifier of the file
+        // it points at, and is grouped with it
         assert_eq!(metafiles.len(), 2);
         for file in &metafiles {
             assert!(file.paths() == indexset![&file2] || file.paths() == indexset![&file1, &link])
         }
+        // the symlink is kept apart from the file it points at
+        let file1_mf = metafiles.iter().find(|mf| mf.files().contains(&file1));
+        assert_eq!(file1_mf.unwrap().symlinks(), &indexset![link.clone()]);
+        assert!(file1_mf
+            .unwrap()
+            .clone()
+            .without_symlinks()
+            .symlinks()
+            .is_empty());
+        /* cleanup */
+        fs::remove_dir_all("test-tmp-symlink")
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn known_file_types() -> io::Result<()> {
+        /* setup */
+        let root = PathBuf::from("test-tmp-known-file-types");
+        fs::create_dir(&root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::hard_link(root.join("a"), root.join("a-hardlink"))?;
+        fs::write(root.join("b"), "nyaa")?;
+        std::os::unix::fs::symlink("a", root.join("a-symlink"))?;
+        std::os::unix::fs::symlink("b", root.join("b-symlink"))?;
+        std::os::unix::fs::symlink("b-symlink", root.join("b-symlink-symlink"))?;
+        /* test */
+        // the types given by the directory entries bucket each path the same
+        // way as looking them up does
+        let mut known = vec![];
+        for de in fs::read_dir(&root)? {
+            let de = de?;
+            known.push(FoundPath {
+                is_symlink: Some(de.file_type()?.is_symlink()),
+                path: de.path(),
+            });
+        }
+        let probed: Vec<PathBuf> = known.iter().map(|p| p.path.clone()).collect();
+        let (mut with_types, mut without_types) = (indexset![], indexset![]);
+        collect_into_metafiles(&mut with_types, known, false, &mut vec![]);
+        collect_into_metafiles(&mut without_types, probed, false, &mut vec![]);
+        assert_eq!(with_types.len(), 2);
+        for (with_types, without_types) in with_types.iter().zip(&without_types) {
+            assert_eq!(with_types.id(), without_types.id());
+            assert_eq!(with_types.files(), without_types.files());
+            assert_eq!(with_types.symlinks(), without_types.symlinks());
+        }
+        let b = with_types
+            .iter()
+            .find(|mf| mf.files().contains(&root.join("b")));
+        assert_eq!(b.unwrap().symlinks().len(), 2);
+        // and paths can be added with their types given explicitly
+        let mut mf = MetaFile::from_id(0);
+        assert!(mf.add_file(root.join("a")));
+        assert!(mf.add_symlink(root.join("a-symlink")));
+        assert!(!mf.add_file(root.join("a")));
+        assert_eq!(mf.files(), &indexset![root.join("a")]);
+        assert_eq!(mf.symlinks(), &indexset![root.join("a-symlink")]);
         /* cleanup */
-        fs::remove_dir_all("test-tmp")
+        fs::remove_dir_all(root)
     }
 }
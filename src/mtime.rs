@@ -0,0 +1,118 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// parses a point in time, either relative to `now` as a number of seconds,
+/// minutes, hours, days or weeks ago with a `s`, `m`, `h`, `d` or `w`
+/// suffix, e.g. `7d` or `24h`, or as a date in the form `YYYY-MM-DD`, e.g.
+/// `2024-01-01`, which is taken to be the start of that day in UTC.
+pub fn parse_time(s: &str, now: SystemTime) -> Option<SystemTime> {
+    if let Some(date) = parse_date(s) {
+        return Some(date);
+    }
+    let (digits, unit) = match s.char_indices().last()? {
+        (idx, 's') => (&s[..idx], 1),
+        (idx, 'm') => (&s[..idx], 60),
+        (idx, 'h') => (&s[..idx], 60 * 60),
+        (idx, 'd') => (&s[..idx], SECS_PER_DAY),
+        (idx, 'w') => (&s[..idx], 7 * SECS_PER_DAY),
+        _ => return None,
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let secs = digits.parse::<u64>().ok()?.checked_mul(unit)?;
+    now.checked_sub(Duration::from_secs(secs))
+}
+
+fn parse_date(s: &str) -> Option<SystemTime> {
+    let mut fields = s.splitn(3, '-');
+    let mut number = |len: usize| {
+        let field = fields.next()?;
+        if field.len() != len || !field.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        field.parse::<i64>().ok()
+    };
+    let (year, month, day) = (number(4)?, number(2)?, number(2)?);
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let days = days_since_epoch(year, month, day);
+    let since_epoch = Duration::from_secs(days.unsigned_abs() * SECS_PER_DAY);
+    if days >= 0 {
+        UNIX_EPOCH.checked_add(since_epoch)
+    } else {
+        UNIX_EPOCH.checked_sub(since_epoch)
+    }
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    let is_leap_year = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// the number of days between 1970-01-01 and a date in the proleptic
+/// gregorian calendar. See http://howardhinnant.github.io/date_algorithms.html
+fn days_since_epoch(year: i64, month: i64, day: i64) -> i64 {
+    // years are counted from march, so that leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::parse_time;
+
+    #[test]
+    fn relative() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let ago = |secs| Some(now - Duration::from_secs(secs));
+        assert_eq!(parse_time("0s", now), Some(now));
+        assert_eq!(parse_time("90s", now), ago(90));
+        assert_eq!(parse_time("15m", now), ago(15 * 60));
+        assert_eq!(parse_time("24h", now), ago(24 * 60 * 60));
+        assert_eq!(parse_time("7d", now), ago(7 * 24 * 60 * 60));
+        assert_eq!(parse_time("1w", now), ago(7 * 24 * 60 * 60));
+    }
+
+    #[test]
+    fn dates() {
+        let now = UNIX_EPOCH;
+        let days = |days: u64| Some(UNIX_EPOCH + Duration::from_secs(days * 24 * 60 * 60));
+        assert_eq!(parse_time("1970-01-01", now), days(0));
+        assert_eq!(parse_time("2000-03-01", now), days(11_017));
+        assert_eq!(parse_time("2024-01-01", now), days(19_723));
+        assert_eq!(parse_time("2024-02-29", now), days(19_782));
+        assert_eq!(
+            parse_time("1969-12-31", now),
+            Some(UNIX_EPOCH - Duration::from_secs(24 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert_eq!(parse_time("", now), None);
+        assert_eq!(parse_time("d", now), None);
+        assert_eq!(parse_time("7", now), None);
+        assert_eq!(parse_time("-7d", now), None);
+        assert_eq!(parse_time("1.5h", now), None);
+        assert_eq!(parse_time("7y", now), None);
+        assert_eq!(parse_time("2023-02-29", now), None);
+        assert_eq!(parse_time("2024-13-01", now), None);
+        assert_eq!(parse_time("2024-1-01", now), None);
+        assert_eq!(parse_time("2024-01-01-01", now), None);
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+
+/// the most files a scan opens at once by default, however high the limit on
+/// open files is. See `default_max_open_files`.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 256;
+
+/// a limit on how many files a scan has open for reading at once, whatever
+/// the number of threads, so that reading big groups of files can't run out
+/// of file descriptors. Clones share the limit.
+#[derive(Debug, Clone)]
+pub struct OpenFileLimit {
+    max: usize,
+    open: Arc<(Mutex<usize>, Condvar)>, /* how many files are open, and a
+                                        signal for when one is closed */
+}
+
+impl OpenFileLimit {
+    /// a limit of `max` open files, or 1 if `max` is 0.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            open: Arc::default(),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// waits until fewer than `max` files are open, then counts one more
+    /// file as open until the returned permit is dropped.
+    pub fn acquire(&self) -> OpenFilePermit<'_> {
+        let (open, closed) = &*self.open;
+        let open = open.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut open = closed
+            .wait_while(open, |open| *open >= self.max)
+            .unwrap_or_else(PoisonError::into_inner);
+        *open += 1;
+        OpenFilePermit { limit: self }
+    }
+
+    /// like `acquire`, but rather than waiting, returns `None` unless
+    /// another file could still be opened afterwards, so that files which
+    /// are kept open for a long time, e.g. while they're compared block by
+    /// block, can't keep the others from being read.
+    pub fn try_acquire(&self) -> Option<OpenFilePermit<'_>> {
+        let (open, _) = &*self.open;
+        let mut open = open.lock().unwrap_or_else(PoisonError::into_inner);
+        if *open + 1 >= self.max {
+            return None;
+        }
+        *open += 1;
+        Some(OpenFilePermit { limit: self })
+    }
+}
+
+impl Default for OpenFileLimit {
+    fn default() -> Self {
+        Self::new(default_max_open_files())
+    }
+}
+
+/// one file counted as open by `OpenFileLimit::acquire`, until it's dropped.
+#[derive(Debug)]
+pub struct OpenFilePermit<'a> {
+    limit: &'a OpenFileLimit,
+}
+
+impl Drop for OpenFilePermit<'_> {
+    fn drop(&mut self) {
+        let (open, closed) = &*self.limit.open;
+        *open.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        closed.notify_one();
+    }
+}
+
+/// a quarter of the soft limit on open files, leaving the rest for the
+/// directories being walked and whatever else the process has open, and at
+/// most `DEFAULT_MAX_OPEN_FILES`. Where the limit can't be read, it's
+/// `DEFAULT_MAX_OPEN_FILES`.
+pub fn default_max_open_files() -> usize {
+    soft_open_file_limit().map_or(DEFAULT_MAX_OPEN_FILES, |soft_limit| {
+        (soft_limit / 4).clamp(1, DEFAULT_MAX_OPEN_FILES as u64) as usize
+    })
+}
+
+#[cfg(unix)]
+fn soft_open_file_limit() -> Option<u64> {
+    let mut rlim = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `rlim` points to writable memory of the right size and layout
+    // for the duration of the call.
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlim) } != 0 {
+        return None;
+    }
+    // rlim_t is only 32 bits on some targets
+    #[allow(clippy::unnecessary_cast)]
+    Some(rlim.rlim_cur as u64)
+}
+
+#[cfg(not(unix))]
+fn soft_open_file_limit() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{default_max_open_files, OpenFileLimit, DEFAULT_MAX_OPEN_FILES};
+
+    #[test]
+    fn open_file_limit() {
+        let limit = OpenFileLimit::new(2);
+        let (open, most_open) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = limit.acquire();
+                    let now_open = open.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_open.fetch_max(now_open, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(most_open.load(Ordering::SeqCst), 2);
+        // one file is always left for `acquire`
+        let kept_open = limit.try_acquire();
+        assert!(kept_open.is_some());
+        assert!(limit.try_acquire().is_none());
+        drop(limit.acquire());
+        drop(kept_open);
+        assert!(OpenFileLimit::new(1).try_acquire().is_none());
+        assert_eq!(OpenFileLimit::new(0).max(), 1);
+        assert!((1..=DEFAULT_MAX_OPEN_FILES).contains(&default_max_open_files()));
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{self, BufRead};
+use std::path::{is_separator, Path};
+
+/// a shell-style wildcard pattern which is matched against paths.
+/// `*` matches any sequence of characters (including path separators) and
+/// `?` matches exactly one character.
+/// ## Note:
+/// A pattern without any path separators in it, like `*.tmp` or
+/// `node_modules`, is also matched against the file name alone, so it
+/// excludes matching entries anywhere in the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    chars: Vec<char>,
+    has_separator: bool,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            chars: pattern.chars().collect(),
+            has_separator: pattern.chars().any(is_separator),
+        }
+    }
+
+    pub fn matches(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if self.matches_str(&path.to_string_lossy()) {
+            return true;
+        }
+        !self.has_separator
+            && path
+                .file_name()
+                .is_some_and(|name| self.matches_str(&name.to_string_lossy()))
+    }
+
+    fn matches_str(&self, s: &str) -> bool {
+        /*
+            Iterative wildcard matching: when a `*` is found, remember where
+            it was and how much of `s` it had consumed, so that on a
+            mismatch we can backtrack and let the `*` consume one more char.
+        */
+        let s: Vec<char> = s.chars().collect();
+        let (mut p_idx, mut s_idx) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        while s_idx < s.len() {
+            match self.chars.get(p_idx) {
+                Some('*') => {
+                    backtrack = Some((p_idx, s_idx));
+                    p_idx += 1;
+                }
+                Some('?') => {
+                    p_idx += 1;
+                    s_idx += 1;
+                }
+                Some(c) if *c == s[s_idx] => {
+                    p_idx += 1;
+                    s_idx += 1;
+                }
+                _ => match backtrack {
+                    Some((star_p_idx, star_s_idx)) => {
+                        backtrack = Some((star_p_idx, star_s_idx + 1));
+                        p_idx = star_p_idx + 1;
+                        s_idx = star_s_idx + 1;
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.chars[p_idx..].iter().all(|c| *c == '*')
+    }
+}
+
+/// reads a list of patterns, one per line. Blank lines and lines starting
+/// with `#` are skipped, and trailing whitespace (such as the `\r` of a
+/// `\r\n` line ending) is trimmed from each pattern.
+pub fn read_patterns(reader: impl BufRead) -> io::Result<Vec<Pattern>> {
+    let mut patterns = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end();
+        if !line.is_empty() && !line.starts_with('#') {
+            patterns.push(Pattern::new(line));
+        }
+    }
+    Ok(patterns)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_patterns, Pattern};
+
+    #[test]
+    fn literal() {
+        assert!(Pattern::new("a/b").matches("a/b"));
+        assert!(!Pattern::new("a/b").matches("a/bc"));
+        assert!(!Pattern::new("a/b").matches("x/a/b"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(Pattern::new("*.tmp").matches("dir/file.tmp"));
+        assert!(!Pattern::new("*.tmp").matches("dir/file.tmpl"));
+        assert!(Pattern::new("file?.txt").matches("file1.txt"));
+        assert!(!Pattern::new("file?.txt").matches("file10.txt"));
+        assert!(Pattern::new("*/build/*").matches("project/build/out.o"));
+        assert!(Pattern::new("a*b*c").matches("aXXbYYbZZc"));
+        assert!(Pattern::new("*").matches(""));
+    }
+
+    #[test]
+    fn file_name_only() {
+        assert!(Pattern::new("node_modules").matches("project/node_modules"));
+        assert!(Pattern::new(".git").matches("project/.git"));
+        assert!(!Pattern::new("project/.git").matches("other/project/.git"));
+    }
+
+    #[test]
+    fn pattern_list() -> std::io::Result<()> {
+        let list = "# build output\r\n*.o\r\n\r\n  \n#*.rs\nnode_modules\n";
+        let patterns = read_patterns(list.as_bytes())?;
+        assert_eq!(
+            patterns,
+            [Pattern::new("*.o"), Pattern::new("node_modules")]
+        );
+        let excluded = |p: &str| patterns.iter().any(|pattern| pattern.matches(p));
+        assert!(excluded("src/main.o"));
+        assert!(excluded("web/node_modules"));
+        // commented out patterns don't exclude anything
+        assert!(!excluded("src/main.rs"));
+        assert!(!excluded("# build output"));
+        Ok(())
+    }
+}
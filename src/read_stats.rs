@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// how many files a scan has read, how many bytes of them, and how long it
+/// took, for each way files are read, so that how fast a scan reads can be
+/// measured, e.g. for `--bench`. Clones share the counts.
+/// ## Note:
+/// Only what was actually read is counted, so files whose checksums were
+/// cached, files which were left out under `ScanOptions::limit` or once the
+/// scan was interrupted, and blocks past where files were found to differ,
+/// aren't.
+#[derive(Debug, Clone, Default)]
+pub struct ReadStats {
+    checksummed: Arc<ReadCount>, /* read in full, or up to the hash limit,
+                                 to calculate their checksums */
+    compared: Arc<ReadCount>, /* read block by block, see
+                              `find_blockwise_dups` */
+    sampled: Arc<ReadCount>, /* read in a few places, see
+                             `find_probable_dups` */
+}
+
+impl ReadStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn checksummed(&self) -> &ReadCount {
+        &self.checksummed
+    }
+
+    pub fn compared(&self) -> &ReadCount {
+        &self.compared
+    }
+
+    pub fn sampled(&self) -> &ReadCount {
+        &self.sampled
+    }
+}
+
+/// the files and bytes read one way, and how long reading them took.
+#[derive(Debug, Default)]
+pub struct ReadCount {
+    files: AtomicUsize,
+    bytes: AtomicU64,
+    micros: AtomicU64,
+}
+
+impl ReadCount {
+    pub fn add_file(&self) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_time(&self, time: Duration) {
+        self.micros
+            .fetch_add(time.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// runs `f`, adding how long it took to the time spent reading.
+    pub fn time<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add_time(start.elapsed());
+        result
+    }
+
+    pub fn files(&self) -> usize {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.micros.load(Ordering::Relaxed))
+    }
+}
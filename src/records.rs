@@ -0,0 +1,170 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// reads a file written by `write_records`, whose header lines have to be
+/// the first of each of `header`'s pairs, with the second being the error
+/// when they aren't. `parse` is called with the numbers and the path of
+/// each record, and returns `None` if they're out of range, in which case
+/// the record is malformed, like one which doesn't have `N` numbers. A
+/// missing file is `None`.
+/// ## Note:
+/// Each record is of the form `<number> ... <number> <path>` and terminated
+/// by a NUL character, so paths may contain spaces and newlines.
+pub fn read_records<const N: usize, T>(
+    records_file: impl AsRef<Path>,
+    header: &[(&str, &str)],
+    malformed: &str,
+    mut parse: impl FnMut([u64; N], PathBuf) -> Option<T>,
+) -> io::Result<Option<Vec<T>>> {
+    let contents = match fs::read(records_file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut rest = &contents[..];
+    for (line, mismatch) in header {
+        rest = (rest.strip_prefix(line.as_bytes()))
+            .and_then(|rest| rest.strip_prefix(b"\n"))
+            .ok_or_else(|| invalid_data(mismatch))?;
+    }
+    (rest.split(|b| *b == b'\0').filter(|r| !r.is_empty()))
+        .map(|record| {
+            let (numbers, path) = parse_record(record)?;
+            parse(numbers, path)
+        })
+        .map(|record| record.ok_or_else(|| invalid_data(malformed)))
+        .collect::<io::Result<_>>()
+        .map(Some)
+}
+
+/// writes `header` followed by `records` to `records_file`, see
+/// `read_records`, replacing it only once the new file has been written in
+/// full.
+pub fn write_records<'a, const N: usize>(
+    records_file: impl AsRef<Path>,
+    header: &[&str],
+    records: impl IntoIterator<Item = ([u64; N], &'a Path)>,
+) -> io::Result<()> {
+    let records_file = records_file.as_ref();
+    let mut tmp_name = records_file.as_os_str().to_os_string();
+    tmp_name.push(".tmp");
+    let mut out = BufWriter::new(File::create(&tmp_name)?);
+    for line in header {
+        writeln!(out, "{line}")?;
+    }
+    for (numbers, path) in records {
+        for number in numbers {
+            write!(out, "{number} ")?;
+        }
+        out.write_all(&path_to_bytes(path))?;
+        out.write_all(b"\0")?;
+    }
+    out.into_inner()?.sync_all()?;
+    fs::rename(tmp_name, records_file)
+}
+
+fn parse_record<const N: usize>(record: &[u8]) -> Option<([u64; N], PathBuf)> {
+    let mut fields = record.splitn(N + 1, |b| *b == b' ');
+    let mut numbers = [0; N];
+    for number in &mut numbers {
+        *number = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+    }
+    let path = path_from_bytes(fields.next()?.to_vec()).ok()?;
+    Some((numbers, path))
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// `path` as a string, for text formats such as CSV and JSON. A path which
+/// isn't valid UTF-8 is an error rather than being written lossily, since
+/// it couldn't be told apart from the path it would be written as.
+pub fn path_to_str(path: &Path) -> io::Result<&str> {
+    path.to_str().ok_or_else(|| {
+        invalid_data(&format!(
+            "{:?} isn't valid UTF-8, so it can't be written as text",
+            path.as_os_str()
+        ))
+    })
+}
+
+#[cfg(unix)]
+pub fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    String::from_utf8(bytes)
+        .map(PathBuf::from)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::{path_to_str, read_records, write_records};
+
+    #[test]
+    fn records() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-records");
+        fs::create_dir(root)?;
+        let records_file = root.join("records");
+        let paths = [PathBuf::from("/a b"), PathBuf::from("/c\nd")];
+        let header = [("meow", "not meow"), ("nyaa", "not nyaa")];
+        let read = |header: &[(&str, &str)]| {
+            read_records(&records_file, header, "malformed", |[n, m], path| {
+                Some((n, u8::try_from(m).ok()?, path))
+            })
+        };
+        /* test */
+        assert!(read(&header)?.is_none());
+        let records = [([1, 2], paths[0].as_path()), ([3, 4], paths[1].as_path())];
+        write_records(&records_file, &["meow", "nyaa"], records)?;
+        assert_eq!(
+            read(&header)?.unwrap(),
+            [(1, 2, paths[0].clone()), (3, 4, paths[1].clone())]
+        );
+        let error = read(&[("meow", "not meow"), ("purr", "not purr")]).unwrap_err();
+        assert_eq!(error.to_string(), "not purr");
+        // numbers which are out of range for what they're parsed into
+        write_records(
+            &records_file,
+            &["meow", "nyaa"],
+            [([1, 256], paths[0].as_path())],
+        )?;
+        assert_eq!(read(&header).unwrap_err().to_string(), "malformed");
+        assert_eq!(path_to_str(&paths[1])?, "/c\nd");
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+            let non_utf8 = Path::new(OsStr::from_bytes(b"/\xff"));
+            assert_eq!(
+                path_to_str(non_utf8).unwrap_err().kind(),
+                io::ErrorKind::InvalidData
+            );
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
@@ -1,25 +1,140 @@
 use std::{
+    collections::HashSet,
+    ffi::OsStr,
     fs, io,
     path::{Path, PathBuf},
+    rc::Rc,
 };
 
+use crate::gitignore::Gitignore;
+use crate::metafile::get_device_identifier;
+use crate::pattern::Pattern;
+
 #[derive(Debug)]
 pub struct RecReadDir {
-    dirs: Vec<PathBuf>,
+    dirs: Vec<(PathBuf, usize, Option<Rc<Gitignore>>)>, /* with the rules from
+                                                        the dir's parents */
     current: fs::ReadDir,
+    current_dir: PathBuf,
+    current_depth: usize,
+    max_depth: usize,
+    excludes: Vec<Pattern>,
+    skip_hidden: bool,
+    follow_symlinks: bool,
+    device: u64, /* of `start` */
+    one_file_system: bool,
+    gitignore: Option<Rc<Gitignore>>, /* the rules which apply in `current_dir`,
+                                      if .gitignore files are respected */
+    visited: HashSet<PathBuf>, /* canonical paths of directories which have been
+                               pushed onto `dirs`, used to break symlink cycles */
 }
 
 impl RecReadDir {
     pub fn new(start: impl AsRef<Path>) -> io::Result<RecReadDir> {
+        Self::with_max_depth(start, usize::MAX)
+    }
+
+    /// like `new`, but directories more than `max_depth` levels below
+    /// `start` are yielded without being descended into. A `max_depth` of 0
+    /// only reads `start` itself, like `fs::read_dir`.
+    pub fn with_max_depth(start: impl AsRef<Path>, max_depth: usize) -> io::Result<RecReadDir> {
         Ok(RecReadDir {
             dirs: vec![],
             current: start.as_ref().read_dir()?,
+            current_dir: start.as_ref().to_path_buf(),
+            current_depth: 0,
+            max_depth,
+            excludes: vec![],
+            skip_hidden: false,
+            follow_symlinks: false,
+            device: get_device_identifier(start.as_ref())?,
+            one_file_system: false,
+            gitignore: None,
+            visited: HashSet::from([start.as_ref().canonicalize()?]),
         })
     }
+
+    /// entries whose paths match any of `excludes` are not yielded, and
+    /// excluded directories are not descended into.
+    pub fn excluding(mut self, excludes: Vec<Pattern>) -> Self {
+        self.excludes = excludes;
+        self
+    }
+
+    /// when `skip_hidden` is set, hidden entries, see `is_hidden`, are not
+    /// yielded, and hidden directories are not descended into.
+    pub fn skipping_hidden(mut self, skip_hidden: bool) -> Self {
+        self.skip_hidden = skip_hidden;
+        self
+    }
+
+    /// when `follow_symlinks` is set, symlinks to directories are descended
+    /// into. Each directory is only ever descended into once, so symlinks
+    /// pointing back to an ancestor directory can't cause an infinite loop.
+    /// By default symlinks are yielded but not followed.
+    pub fn following_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// when `one_file_system` is set, directories on a different filesystem
+    /// to `start`, such as mount points, are yielded but not descended into,
+    /// like `find -xdev`.
+    pub fn staying_on_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+
+    /// when `respect_gitignore` is set, entries ignored by the `.gitignore`
+    /// files in `start` and the directories below it are not yielded, and
+    /// ignored directories are not descended into. `.git` directories are
+    /// skipped too.
+    pub fn respecting_gitignore(mut self, respect_gitignore: bool) -> Self {
+        self.gitignore = if respect_gitignore {
+            let git_dirs = Rc::new(Gitignore::parse(&self.current_dir, ".git/", None));
+            Gitignore::read(&self.current_dir, Some(git_dirs))
+        } else {
+            None
+        };
+        self
+    }
+
+    fn should_descend(&mut self, de: &fs::DirEntry) -> bool {
+        if self.current_depth >= self.max_depth {
+            return false;
+        }
+        // if the file type of an entry can't be determined (e.g. it was
+        // removed mid-scan), it is still yielded but is not treated as a
+        // directory.
+        let Ok(file_type) = de.file_type() else {
+            return false;
+        };
+        if !self.follow_symlinks {
+            return file_type.is_dir() && self.is_on_start_device(de);
+        }
+        // `DirEntry::file_type` doesn't traverse symlinks, `fs::metadata` does.
+        let is_dir = file_type.is_dir()
+            || (file_type.is_symlink() && fs::metadata(de.path()).is_ok_and(|md| md.is_dir()));
+        is_dir
+            && self.is_on_start_device(de)
+            && de
+                .path()
+                .canonicalize()
+                .is_ok_and(|canonical| self.visited.insert(canonical))
+    }
+
+    fn is_on_start_device(&self, de: &fs::DirEntry) -> bool {
+        !self.one_file_system
+            || get_device_identifier(&de.path()).is_ok_and(|device| device == self.device)
+    }
 }
 
+/// directories which can't be read, e.g. because permission to read them is
+/// denied, are yielded as errors along with their paths, and the walk carries
+/// on without them. So are entries which can't be read, along with the path
+/// of the directory they're in.
 impl Iterator for RecReadDir {
-    type Item = io::Result<fs::DirEntry>;
+    type Item = Result<fs::DirEntry, (PathBuf, io::Error)>;
 
     fn next(&mut self) -> Option<Self::Item> {
         /*
@@ -30,33 +145,66 @@ impl Iterator for RecReadDir {
             directories are found, they are added to the stack. This results in
             a recursive traversal.
         */
-        if let Some(dir_entry) = self.current.next() {
+        for dir_entry in self.current.by_ref() {
             if let Ok(ref de) = dir_entry {
-                if de.file_type().expect("couldn't get file type").is_dir() {
-                    self.dirs.push(de.path());
+                let path = de.path();
+                let is_dir = || de.file_type().is_ok_and(|file_type| file_type.is_dir());
+                if self.excludes.iter().any(|pattern| pattern.matches(&path))
+                    || (self.skip_hidden && is_hidden(&de.file_name()))
+                    || self
+                        .gitignore
+                        .as_ref()
+                        .is_some_and(|gitignore| gitignore.is_ignored(&path, is_dir()))
+                {
+                    continue;
+                }
+                if self.should_descend(de) {
+                    self.dirs
+                        .push((path, self.current_depth + 1, self.gitignore.clone()));
                 }
             }
-            Some(dir_entry)
-        } else {
-            while let Some(path) = self.dirs.pop() {
-                if let Ok(read_dir) = fs::read_dir(path) {
-                    self.current = read_dir;
-                    return self.next();
+            return Some(dir_entry.map_err(|e| (self.current_dir.clone(), e)));
+        }
+        let (path, depth, gitignore) = self.dirs.pop()?;
+        // on windows, std adds the `\\?\` prefix to paths longer than
+        // MAX_PATH itself, so deep directories and long UNC paths can be
+        // read like any other.
+        match fs::read_dir(&path) {
+            Ok(read_dir) => {
+                self.current = read_dir;
+                self.current_depth = depth;
+                if gitignore.is_some() {
+                    self.gitignore = Gitignore::read(&path, gitignore);
                 }
+                self.current_dir = path;
+                self.next()
+            }
+            Err(e) => {
+                let e = io::Error::new(e.kind(), format!("couldn't read directory: {e}"));
+                Some(Err((path, e)))
             }
-            None
         }
     }
 }
 
+/// whether a file name is that of a hidden file or directory, i.e. whether
+/// it starts with a `.`, like `.cache` or `.bashrc`.
+pub fn is_hidden(file_name: &OsStr) -> bool {
+    file_name.as_encoded_bytes().starts_with(b".")
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
     use std::fs;
     use std::io;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+
+    use indexmap::indexset;
 
     use super::RecReadDir;
+    use crate::metafile::{collect_into_metafiles, get_device_identifier};
+    use crate::pattern::Pattern;
 
     #[test]
     fn recursively_read_dir() -> io::Result<()> {
@@ -75,14 +223,245 @@ mod test {
         assert_eq!(
             entries,
             HashSet::from([
-                PathBuf::from("test-tmp\\file2"),
-                PathBuf::from("test-tmp\\file1"),
-                PathBuf::from("test-tmp\\nested"),
-                PathBuf::from("test-tmp\\nested\\file3"),
-                PathBuf::from("test-tmp\\nested\\file4"),
+                Path::new("test-tmp").join("file2"),
+                Path::new("test-tmp").join("file1"),
+                Path::new("test-tmp").join("nested"),
+                Path::new("test-tmp").join("nested").join("file3"),
+                Path::new("test-tmp").join("nested").join("file4"),
             ]),
         );
         /* cleanup */
         fs::remove_dir_all("test-tmp")
     }
+
+    #[test]
+    fn skips_hidden() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-hidden");
+        fs::create_dir_all(root.join(".cache").join("nested"))?;
+        fs::create_dir_all(root.join("visible"))?;
+        fs::write(root.join(".bashrc"), "meow")?;
+        fs::write(root.join("file"), "meow")?;
+        fs::write(root.join(".cache").join("file"), "meow")?;
+        fs::write(root.join(".cache").join("nested").join("file"), "meow")?;
+        fs::write(root.join("visible").join(".hidden"), "meow")?;
+        fs::write(root.join("visible").join("file"), "meow")?;
+        let read = |skip_hidden| -> io::Result<HashSet<PathBuf>> {
+            Ok(RecReadDir::new(root)?
+                .skipping_hidden(skip_hidden)
+                .map(Result::unwrap)
+                .map(|a| a.path())
+                .collect())
+        };
+        /* test */
+        assert_eq!(
+            read(true)?,
+            HashSet::from([
+                root.join("file"),
+                root.join("visible"),
+                root.join("visible").join("file"),
+            ]),
+        );
+        // hidden directories are descended into when they aren't skipped
+        assert_eq!(read(false)?.len(), 9);
+        assert!(read(false)?.contains(&root.join(".cache").join("nested").join("file")));
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn max_depth() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-max-depth");
+        fs::create_dir_all(root.join("l1").join("l2").join("l3"))?;
+        fs::write(root.join("file0"), "meow0")?;
+        fs::write(root.join("l1").join("file1"), "meow1")?;
+        fs::write(root.join("l1").join("l2").join("file2"), "meow2")?;
+        fs::write(root.join("l1").join("l2").join("l3").join("file3"), "meow3")?;
+        /* test */
+        let entries: HashSet<PathBuf> = RecReadDir::with_max_depth(root, 1)?
+            .map(Result::unwrap)
+            .map(|a| a.path())
+            .collect();
+        assert_eq!(
+            entries,
+            HashSet::from([
+                root.join("file0"),
+                root.join("l1"),
+                root.join("l1").join("file1"),
+                root.join("l1").join("l2"),
+            ]),
+        );
+        let entries: HashSet<PathBuf> = RecReadDir::with_max_depth(root, 0)?
+            .map(Result::unwrap)
+            .map(|a| a.path())
+            .collect();
+        assert_eq!(
+            entries,
+            HashSet::from([root.join("file0"), root.join("l1")])
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn deep_tree() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-deep-tree");
+        // well past MAX_PATH, the 260 character limit on windows
+        let deepest = (0..20).fold(root.to_path_buf(), |dir, depth| {
+            dir.join(format!("directory-at-depth-{depth:02}"))
+        });
+        assert!(deepest.as_os_str().len() > 400);
+        fs::create_dir_all(&deepest)?;
+        fs::write(deepest.join("file"), "meow")?;
+        /* test */
+        let entries: Vec<PathBuf> = RecReadDir::new(root)?
+            .map(Result::unwrap)
+            .map(|a| a.path())
+            .collect();
+        assert_eq!(entries.len(), 21);
+        assert!(entries.contains(&deepest.join("file")));
+        let mut metafiles = indexset![];
+        let mut errors = vec![];
+        collect_into_metafiles(&mut metafiles, entries, false, &mut errors);
+        assert!(errors.is_empty());
+        assert_eq!(metafiles.len(), 1);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle_terminates() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-symlink-cycle");
+        fs::create_dir_all(root.join("nested"))?;
+        fs::write(root.join("nested").join("file"), "meow")?;
+        std::os::unix::fs::symlink("..", root.join("nested").join("parent"))?;
+        /* test */
+        let expected = HashSet::from([
+            root.join("nested"),
+            root.join("nested").join("file"),
+            root.join("nested").join("parent"),
+        ]);
+        for follow_symlinks in [false, true] {
+            let entries: HashSet<PathBuf> = RecReadDir::new(root)?
+                .following_symlinks(follow_symlinks)
+                .map(Result::unwrap)
+                .map(|a| a.path())
+                .collect();
+            assert_eq!(entries, expected);
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_symlinks_to_sibling_dir() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-follow-symlinks");
+        let scan = root.join("scan");
+        fs::create_dir_all(&scan)?;
+        fs::create_dir(root.join("target"))?;
+        fs::write(root.join("target").join("file"), "meow")?;
+        std::os::unix::fs::symlink("../target", scan.join("dir-link"))?;
+        std::os::unix::fs::symlink("../target/file", scan.join("file-link"))?;
+        /* test */
+        let walk = |follow_symlinks| -> io::Result<HashSet<PathBuf>> {
+            let mut metafiles = indexset![];
+            collect_into_metafiles(
+                &mut metafiles,
+                RecReadDir::new(&scan)?
+                    .following_symlinks(follow_symlinks)
+                    .map(Result::unwrap)
+                    .map(|a| a.path()),
+                false,
+                &mut vec![],
+            );
+            // both links lead to the same file, so they share a metafile
+            assert_eq!(metafiles.len(), 1);
+            Ok(metafiles[0].paths().into_iter().cloned().collect())
+        };
+        assert_eq!(walk(false)?, HashSet::from([scan.join("file-link")]));
+        assert_eq!(
+            walk(true)?,
+            HashSet::from([scan.join("dir-link").join("file"), scan.join("file-link")]),
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn excluded_dir_is_not_descended() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-exclude");
+        fs::create_dir(root)?;
+        fs::write(root.join("keep"), "meow1")?;
+        fs::write(root.join("scratch.tmp"), "meow2")?;
+        fs::create_dir(root.join("node_modules"))?;
+        fs::write(root.join("node_modules").join("dep"), "meow3")?;
+        fs::create_dir(root.join("nested"))?;
+        fs::write(root.join("nested").join("file"), "meow4")?;
+        /* test */
+        let excludes = vec![Pattern::new("node_modules"), Pattern::new("*.tmp")];
+        let mut metafiles = indexset![];
+        collect_into_metafiles(
+            &mut metafiles,
+            RecReadDir::new(root)?
+                .excluding(excludes)
+                .map(Result::unwrap)
+                .map(|a| a.path()),
+            false,
+            &mut vec![],
+        );
+        let paths: HashSet<PathBuf> = metafiles
+            .iter()
+            .flat_map(|mf| mf.paths())
+            .cloned()
+            .collect();
+        assert_eq!(
+            paths,
+            HashSet::from([root.join("keep"), root.join("nested").join("file")]),
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn one_file_system() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-one-file-system");
+        fs::create_dir_all(root.join("nested"))?;
+        fs::write(root.join("nested").join("file"), "meow")?;
+        /* test */
+        // directories on the same filesystem are still descended into
+        let entries: HashSet<PathBuf> = RecReadDir::new(root)?
+            .staying_on_file_system(true)
+            .map(Result::unwrap)
+            .map(|a| a.path())
+            .collect();
+        assert_eq!(
+            entries,
+            HashSet::from([root.join("nested"), root.join("nested").join("file")])
+        );
+        // procfs is mounted on its own filesystem, so it isn't
+        #[cfg(target_os = "linux")]
+        if get_device_identifier(Path::new("/proc")).is_ok_and(|device| {
+            get_device_identifier(Path::new("/")).is_ok_and(|root_device| device != root_device)
+        }) {
+            let entries: Vec<PathBuf> = RecReadDir::with_max_depth("/", 1)?
+                .staying_on_file_system(true)
+                .filter_map(Result::ok)
+                .map(|a| a.path())
+                .collect();
+            assert!(entries.contains(&PathBuf::from("/proc")));
+            assert!(!entries
+                .iter()
+                .any(|p| p.parent() == Some(Path::new("/proc"))));
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
 }
@@ -0,0 +1,3540 @@
+use std::cmp::Ordering;
+use std::collections::{hash_map, HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+use adler32::{adler32, RollingAdler32};
+use indexmap::{indexset, IndexSet};
+use indicatif::ProgressBar;
+use log::warn;
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::cache::{ChecksumCache, Fingerprint};
+use crate::interrupt::Interrupt;
+use crate::metafile::{collect_into_metafiles_filtered, FileErrors, FileStat, FoundPath, MetaFile};
+use crate::open_files::{OpenFileLimit, OpenFilePermit};
+use crate::pattern::Pattern;
+use crate::read_stats::ReadStats;
+use crate::records::path_from_bytes;
+use crate::recursive_dir_reader::{is_hidden, RecReadDir};
+
+/// parameters controlling which files are searched for duplicates.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub recursive: bool,
+    pub max_depth: usize,
+    pub follow_symlinks: bool,
+    pub symlinks_as_duplicates: bool, /* list symlinks to duplicates as
+                                      aliases of them, see
+                                      `find_sizewise_dups` */
+    pub one_file_system: bool,
+    pub respect_gitignore: bool,
+    pub include_hidden: bool, /* files and directories whose names start
+                              with a `.` are skipped unless this is set */
+    pub ignore_case: bool, /* paths which only differ in case are taken to
+                           be the same path, see `add_to_file_list` */
+    pub verify_links: bool, /* check that paths sharing a file identifier
+                            are the same file, see
+                            `SizeGrouper::verify_links` */
+    pub excludes: Vec<Pattern>,
+    pub excluded_files: Vec<PathBuf>, /* files to leave out, such as the
+                                      checksum cache; see
+                                      `canonical_file_path` */
+    pub extensions: Option<HashSet<String>>, /* lowercase, see `parse_extensions` */
+    pub excluded_extensions: HashSet<String>,
+    pub min_size: u64,
+    pub max_size: u64,
+    pub newer_than: Option<SystemTime>, /* only files modified at or after */
+    pub older_than: Option<SystemTime>, /* only files modified before */
+    pub limit: Option<usize>,           /* stop once this many groups of duplicates
+                                        have been found, see `find_dups` */
+    pub include_empty: bool, /* empty files all share a size and checksum,
+                             so they're skipped unless this is set */
+    pub mmap: bool, /* memory map files to checksum them, rather than reading
+                    them */
+    pub hash_limit: Option<u64>, /* only compare this many bytes at the start
+                                 of each file, see `find_dups` */
+    pub block_size: Option<usize>, /* how many bytes to read files in at a
+                                   time, see `clamp_block_size`; by default
+                                   `READ_BLOCK_SIZE` when checksumming and
+                                   1 MiB when comparing block by block */
+    pub threads: usize, /* how many threads to calculate checksums with;
+                        0 means one per cpu core */
+    pub open_files: OpenFileLimit, /* how many files can be open at once
+                                   while they're checksummed */
+    pub no_size_filter: bool, /* checksum every file in full, even ones
+                              which don't share a size with any other, and
+                              compare files of every size with each other,
+                              see `find_dups` */
+    pub interrupt: Interrupt, /* once set, no new files are searched or
+                              read, see `find_dups` */
+    pub read_stats: ReadStats, /* how much has been read, and how long it
+                               took, see `ReadStats` */
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            max_depth: usize::MAX,
+            follow_symlinks: false,
+            symlinks_as_duplicates: false,
+            one_file_system: false,
+            respect_gitignore: false,
+            include_hidden: false,
+            ignore_case: false,
+            verify_links: false,
+            excludes: Vec::new(),
+            excluded_files: Vec::new(),
+            extensions: None,
+            excluded_extensions: HashSet::new(),
+            min_size: 0,
+            max_size: u64::MAX,
+            newer_than: None,
+            older_than: None,
+            limit: None,
+            include_empty: false,
+            mmap: false,
+            hash_limit: None,
+            block_size: None,
+            threads: 0,
+            open_files: OpenFileLimit::default(),
+            no_size_filter: false,
+            interrupt: Interrupt::new(),
+            read_stats: ReadStats::new(),
+        }
+    }
+}
+
+/// two or more files which share the same contents.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    size: u64,
+    checksum: u32,
+    files: Vec<MetaFile>, /* sorted by path */
+}
+
+impl DuplicateGroup {
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    pub fn files(&self) -> &[MetaFile] {
+        &self.files
+    }
+
+    /// the same group, with the paths of its files replaced by `f` of them,
+    /// see `MetaFile::map_paths`. The files are kept in the same order.
+    pub fn map_paths(self, f: impl Fn(&Path) -> PathBuf) -> Self {
+        Self {
+            files: self.files.into_iter().map(|mf| mf.map_paths(&f)).collect(),
+            ..self
+        }
+    }
+
+    /// the number of bytes which would be freed if all but one of the files
+    /// in the group were removed.
+    /// ## Note:
+    /// Hard links to a file are paths of the same `MetaFile`, so they are
+    /// counted once, as they take up no space of their own.
+    pub fn reclaimable_space(&self) -> u64 {
+        (self.files.len() as u64 - 1) * self.size
+    }
+
+    /// the number of paths in the group which are extra hard links to one of
+    /// its files, rather than copies of it.
+    pub fn hardlink_count(&self) -> usize {
+        self.files
+            .iter()
+            .map(|f| f.files().len().saturating_sub(1))
+            .sum()
+    }
+
+    /// describes where the rest of the group is relative to each file, in
+    /// the same order as `files`. See `Annotation`.
+    pub fn annotations(&self) -> Vec<Annotation> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(idx, f)| {
+                let (mut dup_inside, mut dup_outside) = (false, false);
+                for (other_idx, other) in self.files.iter().enumerate() {
+                    if other_idx == idx {
+                        continue;
+                    }
+                    if f.c_commands(other) {
+                        dup_inside = true;
+                    } else {
+                        dup_outside = true;
+                    }
+                }
+                Annotation {
+                    dup_inside,
+                    dup_outside,
+                }
+            })
+            .collect()
+    }
+}
+
+/// a checksum as it's listed in reports: the name of the algorithm it was
+/// calculated with, then the digest as zero-padded hex, e.g.
+/// `adler32:0a1b2c3d`, so that digests of any width line up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Digest(pub u32);
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "adler32:{:08x}", self.0)
+    }
+}
+
+/// where the other members of a group of duplicates are, relative to one of
+/// them. A duplicate is 'inside' when it is in the same directory as the file
+/// or in one of that directory's subdirectories (i.e. when the file
+/// c-commands it), and 'outside' otherwise. A file in a group of three or
+/// more can have duplicates both inside and outside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Annotation {
+    pub dup_inside: bool,
+    pub dup_outside: bool,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |dup| if dup { "dup" } else { "unique" };
+        write!(
+            f,
+            "{} inside, {} outside",
+            describe(self.dup_inside),
+            describe(self.dup_outside)
+        )
+    }
+}
+
+/// searches `dirs` for duplicate files, running the whole pipeline: files
+/// are grouped by size, and then files which share a size are grouped by
+/// checksum.
+pub fn find_duplicates(dirs: &[PathBuf], options: &ScanOptions) -> io::Result<Vec<DuplicateGroup>> {
+    let mut errors = vec![];
+    let (dirs, _overlapping) = dedup_target_dirs(dirs, options)?;
+    let (sizewise_dups, _empty_count) = stream_sizewise_dups(&dirs, options, &mut errors)?;
+    let progress = ProgressBar::hidden();
+    let dups = thread_pool(options)?
+        .install(|| find_dups(sizewise_dups, options, &mut errors, None, &progress));
+    Ok(sorted_groups(dups))
+}
+
+/// like `find_duplicates`, but the files are checksummed lazily as the
+/// iterator is advanced, one size at a time, so groups can be acted on
+/// before the rest have been found. Only the checksum stage is lazy: the
+/// directories are walked and all of their files grouped by size on the
+/// first call to `next`, and those groups are held in memory until their
+/// size is checksummed. If the walk fails, the error is the only item.
+/// Files which can't be read are left out, see `Duplicates::errors`.
+/// ## Note:
+/// The groups come in no particular order: sizes are compared in whichever
+/// order they're stored in, and can differ from one scan to the next. The
+/// files within each group are sorted by path. Collected, they're the same
+/// groups as `find_duplicates` returns, which sorts them.
+pub fn iter_duplicates<'a>(dirs: &[PathBuf], options: &'a ScanOptions) -> Duplicates<'a> {
+    Duplicates {
+        options,
+        dirs: Some(dirs.to_vec()),
+        pool: None,
+        sizes: None,
+        found: VecDeque::new(),
+        yielded: 0,
+        errors: vec![],
+    }
+}
+
+/// the groups of duplicates found by `iter_duplicates`.
+#[derive(Debug)]
+pub struct Duplicates<'a> {
+    options: &'a ScanOptions,
+    dirs: Option<Vec<PathBuf>>, /* until they're walked */
+    pool: Option<rayon::ThreadPool>,
+    sizes: Option<hash_map::IntoIter<u64, HashSet<MetaFile>>>, /* the files of
+                                                               the sizes which
+                                                               are left */
+    found: VecDeque<DuplicateGroup>, /* groups of the last size which haven't
+                                     been yielded yet */
+    yielded: usize,
+    errors: FileErrors,
+}
+
+impl Duplicates<'_> {
+    /// the files which couldn't be read so far.
+    pub fn errors(&self) -> &FileErrors {
+        &self.errors
+    }
+
+    fn walk(&mut self, dirs: &[PathBuf]) -> io::Result<()> {
+        let (dirs, _overlapping) = dedup_target_dirs(dirs, self.options)?;
+        let (sizewise_dups, _empty_count) =
+            stream_sizewise_dups(&dirs, self.options, &mut self.errors)?;
+        self.pool = Some(thread_pool(self.options)?);
+        self.sizes = Some(sizewise_dups.into_iter());
+        Ok(())
+    }
+}
+
+impl Iterator for Duplicates<'_> {
+    type Item = io::Result<DuplicateGroup>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(dirs) = self.dirs.take() {
+            if let Err(e) = self.walk(&dirs) {
+                return Some(Err(e));
+            }
+        }
+        let options = self.options;
+        loop {
+            if options.limit.is_some_and(|limit| self.yielded >= limit) {
+                return None;
+            }
+            if let Some(group) = self.found.pop_front() {
+                self.yielded += 1;
+                return Some(Ok(group));
+            }
+            if options.interrupt.is_set() {
+                return None;
+            }
+            let (Some(pool), Some(sizes)) = (&self.pool, self.sizes.as_mut()) else {
+                return None;
+            };
+            // files of every size are compared with each other under
+            // `options.no_size_filter`, so they're all checksummed at once
+            let sizewise_dups: SizewiseDups = if options.no_size_filter {
+                sizes.collect()
+            } else {
+                HashMap::from([sizes.next()?])
+            };
+            if sizewise_dups.is_empty() {
+                return None;
+            }
+            let errors = &mut self.errors;
+            let dups = pool.install(|| {
+                find_dups(sizewise_dups, options, errors, None, &ProgressBar::hidden())
+            });
+            self.found.extend(dups.into_iter().map(into_group));
+        }
+    }
+}
+
+/// builds a thread pool with as many threads as `options` asks for, for
+/// `find_dups` to be run in.
+pub fn thread_pool(options: &ScanOptions) -> io::Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .build()
+        .map_err(io::Error::other)
+}
+
+/// target dirs which were left out of a search, each along with the dir which
+/// covers it.
+pub type OverlappingDirs = Vec<(PathBuf, PathBuf)>;
+
+/// `dir` with the symlink resolved if it is one, since target dirs are named
+/// by the user: the files in it are found, and listed, through the directory
+/// the link points to. Other dirs are returned as given. Symlinks found while
+/// walking a dir are only followed with `ScanOptions::follow_symlinks`.
+pub fn resolve_target_dir(dir: &Path) -> io::Result<PathBuf> {
+    // without a trailing slash, which would make the link be followed
+    let link: PathBuf = dir.components().collect();
+    if link.symlink_metadata()?.is_symlink() {
+        dir.canonicalize()
+    } else {
+        Ok(dir.to_path_buf())
+    }
+}
+
+/// drops target dirs which would be searched twice: those which are the same
+/// directory as an earlier one, and, when searching recursively without a
+/// depth limit, those inside another one. Dirs are compared by their
+/// canonical paths, but the kept dirs are returned as given. Returns the kept
+/// dirs and the dropped ones.
+pub fn dedup_target_dirs(
+    dirs: &[PathBuf],
+    options: &ScanOptions,
+) -> io::Result<(Vec<PathBuf>, OverlappingDirs)> {
+    let canonical_dirs = dirs
+        .iter()
+        .map(|d| d.canonicalize())
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    let nested_is_covered = options.recursive && options.max_depth == usize::MAX;
+    let covers = |outer: &PathBuf, inner: &PathBuf| {
+        outer == inner || (nested_is_covered && inner.starts_with(outer))
+    };
+    // a dir is kept unless it's inside another one, or is the same as an
+    // earlier one
+    let kept_idxs: Vec<usize> = (0..dirs.len())
+        .filter(|&idx| {
+            let dir = &canonical_dirs[idx];
+            !canonical_dirs.iter().enumerate().any(|(other_idx, other)| {
+                if other == dir {
+                    other_idx < idx
+                } else {
+                    covers(other, dir)
+                }
+            })
+        })
+        .collect();
+    let mut dropped = vec![];
+    for (idx, dir) in canonical_dirs.iter().enumerate() {
+        if kept_idxs.contains(&idx) {
+            continue;
+        }
+        let covering_idx = kept_idxs
+            .iter()
+            .find(|&&kept_idx| covers(&canonical_dirs[kept_idx], dir))
+            .expect("every dropped dir is covered by a kept one");
+        dropped.push((dirs[idx].clone(), dirs[*covering_idx].clone()));
+    }
+    let kept = kept_idxs.into_iter().map(|idx| dirs[idx].clone()).collect();
+    Ok((kept, dropped))
+}
+
+/// files which can't be added to the list, and directories which can't be
+/// read, are left out and added to `errors`.
+pub fn build_file_list(
+    dirs: &[PathBuf],
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+) -> io::Result<IndexSet<MetaFile>> {
+    let mut acc: IndexSet<MetaFile> = indexset![];
+    for target_dir in dirs {
+        let mut unreadable = vec![];
+        let paths = walk(target_dir, options, &mut unreadable)?;
+        add_to_file_list(&mut acc, paths, options, errors);
+        errors.append(&mut unreadable);
+    }
+    Ok(acc)
+}
+
+/// the paths of the entries in `dir`, and when searching recursively, in its
+/// subdirectories, as they are found, along with whether they're symlinks.
+/// Subdirectories which can't be read, e.g. because permission to read them
+/// is denied, are skipped and added to `errors` as they're found. The walk
+/// stops once `options.interrupt` is set. If `dir` is a symlink, it's
+/// resolved first, see `resolve_target_dir`.
+pub fn walk<'a>(
+    dir: &Path,
+    options: &ScanOptions,
+    errors: &'a mut FileErrors,
+) -> io::Result<impl Iterator<Item = FoundPath> + 'a> {
+    let dir = &resolve_target_dir(dir)?;
+    let max_depth = if options.recursive {
+        options.max_depth
+    } else {
+        0
+    };
+    let read_dir_iterator = RecReadDir::with_max_depth(dir, max_depth)?
+        .excluding(options.excludes.clone())
+        .following_symlinks(options.follow_symlinks)
+        .staying_on_file_system(options.one_file_system)
+        .respecting_gitignore(options.respect_gitignore)
+        .skipping_hidden(!options.include_hidden);
+    let interrupt = options.interrupt.clone();
+    Ok(read_dir_iterator
+        .take_while(move |_| !interrupt.is_set())
+        .filter_map(|entry| match entry {
+            Ok(de) => Some(FoundPath {
+                is_symlink: de.file_type().ok().map(|file_type| file_type.is_symlink()),
+                path: de.path(),
+            }),
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        }))
+}
+
+/// like `find_sizewise_dups` on the result of `build_file_list`, but files
+/// are grouped by size as the directories are walked, so the whole file list
+/// is never held in memory at once. Returns the same groups.
+pub fn stream_sizewise_dups(
+    dirs: &[PathBuf],
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+) -> io::Result<(SizewiseDups, usize)> {
+    let mut grouper = SizeGrouper::new(options);
+    for target_dir in dirs {
+        grouper.add_dir(target_dir, errors)?;
+    }
+    if options.verify_links {
+        grouper.verify_links();
+    }
+    Ok(grouper.finish())
+}
+
+/// adds an explicit list of files to a file list, e.g. one read by
+/// `read_path_list`. Paths matching one of the excludes in `options`, or
+/// whose extension isn't allowed by it, are left out, as are hidden files
+/// unless `options.include_hidden` is set, see `is_hidden`, the files in
+/// `options.excluded_files` and files outside of its modification time
+/// window, see `has_allowed_mtime`.
+/// ## Note:
+/// When `options.ignore_case` is set, paths which are equal to a path that
+/// has already been added when case is ignored are left out, for
+/// case-insensitive filesystems. This keeps a file which is reached through
+/// differently cased paths from being listed as its own alias, and it keeps
+/// one which is reported with a different identifier for each casing, as
+/// some network filesystems do, from being listed as its own duplicate. On
+/// case-sensitive filesystems, distinct files whose paths only differ in
+/// case are left out too, so a warning is logged for each path which is
+/// left out without leading to the same file as the path it was taken for.
+pub fn add_to_file_list(
+    acc: &mut IndexSet<MetaFile>,
+    paths: impl IntoIterator<Item = impl Into<FoundPath>>,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+) {
+    let mut seen: HashMap<String, PathBuf> = if options.ignore_case {
+        acc.iter()
+            .flat_map(|f| f.paths())
+            .map(|p| (case_folded(p), p.clone()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    add_unseen_to_file_list(acc, paths, options, &mut seen, errors);
+}
+
+// like `add_to_file_list`, where `seen` holds the paths which have been
+// added so far, by their case folded form, when `options.ignore_case` is
+// set.
+fn add_unseen_to_file_list(
+    acc: &mut IndexSet<MetaFile>,
+    paths: impl IntoIterator<Item = impl Into<FoundPath>>,
+    options: &ScanOptions,
+    seen: &mut HashMap<String, PathBuf>,
+    errors: &mut FileErrors,
+) {
+    let path_iterator =
+        paths
+            .into_iter()
+            .map(Into::into)
+            .filter(|FoundPath { path: p, .. }| {
+                !options.excludes.iter().any(|pattern| pattern.matches(p))
+                    && (options.include_hidden || !p.file_name().is_some_and(is_hidden))
+                    && has_allowed_extension(p, options)
+                    && !is_excluded_file(p, options)
+                    && (!options.ignore_case || is_unseen(p, seen))
+            });
+    let keep = |md: &Metadata| has_allowed_mtime(md, options);
+    collect_into_metafiles_filtered(acc, path_iterator, false, keep, errors);
+}
+
+fn case_folded(p: &Path) -> String {
+    p.to_string_lossy().to_lowercase()
+}
+
+// whether no path which is equal to `p` when case is ignored has been seen
+// yet, in which case `p` is added to `seen`. See `add_to_file_list`.
+fn is_unseen(p: &Path, seen: &mut HashMap<String, PathBuf>) -> bool {
+    let seen_path = match seen.entry(case_folded(p)) {
+        hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        hash_map::Entry::Vacant(entry) => {
+            entry.insert(p.to_path_buf());
+            return true;
+        }
+    };
+    if seen_path != p && !is_same_file(p, seen_path) {
+        warn!(
+            "left out {:?}, as its path only differs in case from {:?}",
+            p.as_os_str(),
+            seen_path.as_os_str()
+        );
+    }
+    false
+}
+
+// whether `a` and `b` lead to the same file, going by their identifiers.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    let id = |p: &Path| FileStat::from_metadata(p, &fs::metadata(p)?).map(|stat| stat.id);
+    matches!((id(a), id(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// whether a file with metadata `md` was modified within the window set by
+/// `options.newer_than` and `options.older_than`. Files modified in the
+/// future are newer than any time. When a window is set, files whose
+/// modification time isn't available are an error.
+pub fn has_allowed_mtime(md: &Metadata, options: &ScanOptions) -> io::Result<bool> {
+    if options.newer_than.is_none() && options.older_than.is_none() {
+        return Ok(true);
+    }
+    let mtime = md.modified()?;
+    Ok(options
+        .newer_than
+        .is_none_or(|newer_than| mtime >= newer_than)
+        && options
+            .older_than
+            .is_none_or(|older_than| mtime < older_than))
+}
+
+/// the canonical form of `p`, for `ScanOptions::excluded_files`. `p` needn't
+/// exist yet, as long as the directory it would be in does.
+pub fn canonical_file_path(p: &Path) -> io::Result<PathBuf> {
+    match p.canonicalize() {
+        Ok(p) => Ok(p),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let (Some(dir), Some(name)) = (p.parent(), p.file_name()) else {
+                return Err(e);
+            };
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            Ok(dir.canonicalize()?.join(name))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// only paths with the same file name as an excluded file are canonicalized,
+// so that excluding files doesn't slow down building the file list.
+fn is_excluded_file(p: &Path, options: &ScanOptions) -> bool {
+    options.excluded_files.iter().any(|excluded| {
+        excluded.file_name() == p.file_name() && p.canonicalize().is_ok_and(|p| &p == excluded)
+    })
+}
+
+/// parses a comma separated list of file extensions, such as `jpg,.PNG`, into
+/// the lowercase form used by `ScanOptions`.
+pub fn parse_extensions(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// whether `path` passes the extension filters in `options`, ignoring case.
+/// Files without an extension, including dotfiles such as `.bashrc`, only
+/// pass when no extensions are required.
+pub fn has_allowed_extension(path: &Path, options: &ScanOptions) -> bool {
+    let Some(ext) = path.extension() else {
+        return options.extensions.is_none();
+    };
+    let ext = ext.to_string_lossy().to_lowercase();
+    let required = options.extensions.as_ref();
+    required.is_none_or(|exts| exts.contains(&ext)) && !options.excluded_extensions.contains(&ext)
+}
+
+/// reads a list of paths separated by `separator`. Blank entries are skipped,
+/// and when the list is newline separated, trailing whitespace (such as the
+/// `\r` of a `\r\n` line ending) is trimmed from each entry.
+pub fn read_path_list(reader: impl BufRead, separator: u8) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in reader.split(separator) {
+        let mut entry = entry?;
+        if separator == b'\n' {
+            let trimmed_len = entry.trim_ascii_end().len();
+            entry.truncate(trimmed_len);
+        }
+        if !entry.is_empty() {
+            paths.push(path_from_bytes(entry)?);
+        }
+    }
+    Ok(paths)
+}
+
+/*
+   I'm using the term 'namewise dup' to describe 2 or more paths which
+   share the same file name, regardless of what their contents are.
+*/
+
+// a map whose keys are file names and whose values are sets of paths with a
+// given file name.
+pub type NamewiseDups = HashMap<OsString, IndexSet<PathBuf>>;
+
+/// groups the paths to `files` by their file names, leaving out names which
+/// only one of them has. When `options.ignore_case` is set, names which only
+/// differ in case are grouped together, under the first of them to be
+/// found.
+pub fn find_namewise_dups<'a>(
+    files: impl IntoIterator<Item = &'a MetaFile>,
+    options: &ScanOptions,
+) -> NamewiseDups {
+    let mut paths_by_name: NamewiseDups = HashMap::new();
+    // the name which each case folded name is grouped under
+    let mut names_by_folded: HashMap<String, OsString> = HashMap::new();
+    for f in files {
+        for p in f.paths() {
+            let Some(name) = p.file_name() else {
+                continue;
+            };
+            let name = match options.ignore_case {
+                true => (names_by_folded.entry(case_folded(Path::new(name))))
+                    .or_insert_with(|| name.to_os_string())
+                    .clone(),
+                false => name.to_os_string(),
+            };
+            paths_by_name
+                .entry(name)
+                .or_insert(IndexSet::with_capacity(1))
+                .insert(p.clone());
+        }
+    }
+    paths_by_name.retain(|_, paths| paths.len() > 1);
+    paths_by_name
+}
+
+/*
+   I'm using the term 'sizewise dup' to describe 2 or more files which
+   share the same size, therefore appearing to be duplicates from a
+   sizewise perspective.
+*/
+
+// a map whose keys are filesizes and whose values are sets of files with a
+// given size.
+pub type SizewiseDups = HashMap<u64, HashSet<MetaFile>>;
+
+/// files are grouped by the size they had when the file list was built, so
+/// no files are stat'd. `progress` is incremented once per file. See
+/// `SizeGrouper::finish`.
+pub fn find_sizewise_dups(
+    files: impl IntoIterator<Item = MetaFile>,
+    options: &ScanOptions,
+    progress: &ProgressBar,
+) -> (SizewiseDups, usize) {
+    let mut grouper = SizeGrouper::new(options);
+    for f in files {
+        if options.interrupt.is_set() {
+            break;
+        }
+        progress.inc(1);
+        grouper.add(f);
+    }
+    if options.verify_links {
+        grouper.verify_links();
+    }
+    grouper.finish()
+}
+
+/// groups files by size as they're found, so that paths can be fed to it
+/// straight from a directory walk. Files whose size isn't within the limits
+/// in `options` are dropped as soon as they're found.
+#[derive(Debug)]
+pub struct SizeGrouper<'a> {
+    options: &'a ScanOptions,
+    sizes: HashMap<u64, u64>, /* the size of every file found so far, by id,
+                              so later paths to them can be matched up */
+    files_by_size: SizewiseDups,
+    seen: HashMap<String, PathBuf>, /* paths added so far by their case
+                                    folded form, when case is ignored */
+}
+
+/// how many paths `SizeGrouper::add_paths` stats at once.
+const STREAM_BATCH_SIZE: usize = 4096;
+
+impl<'a> SizeGrouper<'a> {
+    pub fn new(options: &'a ScanOptions) -> Self {
+        Self {
+            options,
+            sizes: HashMap::new(),
+            files_by_size: HashMap::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// adds the paths found by `walk` in `dir`. Subdirectories which can't
+    /// be read are added to `errors`, like files which can't be added.
+    pub fn add_dir(&mut self, dir: &Path, errors: &mut FileErrors) -> io::Result<()> {
+        let mut unreadable = vec![];
+        self.add_paths(walk(dir, self.options, &mut unreadable)?, errors);
+        errors.append(&mut unreadable);
+        Ok(())
+    }
+
+    /// adds paths like `add_to_file_list`, statting them in batches. No more
+    /// batches are started once `options.interrupt` is set.
+    pub fn add_paths(
+        &mut self,
+        paths: impl IntoIterator<Item = impl Into<FoundPath>>,
+        errors: &mut FileErrors,
+    ) {
+        let mut paths = paths.into_iter().peekable();
+        while paths.peek().is_some() && !self.options.interrupt.is_set() {
+            let mut batch: IndexSet<MetaFile> = indexset![];
+            let batch_paths = paths.by_ref().take(STREAM_BATCH_SIZE);
+            add_unseen_to_file_list(
+                &mut batch,
+                batch_paths,
+                self.options,
+                &mut self.seen,
+                errors,
+            );
+            for f in batch {
+                self.add(f);
+            }
+        }
+    }
+
+    /// adds a file, or when another path to the same file has already been
+    /// added, adds the paths of `f` to it.
+    pub fn add(&mut self, f: MetaFile) {
+        let Some(size) = self.sizes.get(&f.id()) else {
+            self.sizes.insert(f.id(), f.size());
+            if (self.options.min_size..=self.options.max_size).contains(&f.size()) {
+                self.files_by_size.entry(f.size()).or_default().insert(f);
+            }
+            return;
+        };
+        let Some(files) = self.files_by_size.get_mut(size) else {
+            return;
+        };
+        if let Some(mut existing) = files.take(&f) {
+            existing.merge(f);
+            files.insert(existing);
+        }
+    }
+
+    /// stats the paths of the files which were found through more than one
+    /// path again, and splits off the ones which turn out to be different
+    /// files, see `MetaFile::split_mislinked`. The files which are split off
+    /// are regrouped by their own sizes. Returns each path which was split
+    /// off, along with the first path of the file it had been taken for.
+    pub fn verify_links(&mut self) -> Vec<(PathBuf, PathBuf)> {
+        let mut linked = vec![];
+        for files in self.files_by_size.values_mut() {
+            let (multiple, single): (HashSet<MetaFile>, HashSet<MetaFile>) =
+                files.drain().partition(|f| f.paths().len() > 1);
+            *files = single;
+            linked.extend(multiple);
+        }
+        let mut mislinked = vec![];
+        // split off files get unused ids, counting down from the top
+        let mut next_id = u64::MAX;
+        for f in linked {
+            let sizes = &self.sizes;
+            let parts = f.split_mislinked(|| {
+                while sizes.contains_key(&next_id) {
+                    next_id -= 1;
+                }
+                next_id -= 1;
+                next_id + 1
+            });
+            for part in &parts[1..] {
+                mislinked.push((part.primary().clone(), parts[0].primary().clone()));
+            }
+            for part in parts {
+                self.sizes.insert(part.id(), part.size());
+                if (self.options.min_size..=self.options.max_size).contains(&part.size()) {
+                    self.files_by_size
+                        .entry(part.size())
+                        .or_default()
+                        .insert(part);
+                }
+            }
+        }
+        self.files_by_size.retain(|_, files| !files.is_empty());
+        mislinked
+    }
+
+    /// the number of files which have been added, counting each file once
+    /// however many paths it was found through.
+    pub fn file_count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// the total size of the files which have been added.
+    pub fn total_size(&self) -> u64 {
+        self.sizes.values().sum()
+    }
+
+    /// returns the groups of files which share a size. Empty files are left
+    /// out unless `options.include_empty` is set, and the number of them
+    /// which were left out is returned along with the groups. Files which
+    /// don't share a size with any other are left out too, unless
+    /// `options.no_size_filter` is set.
+    /// ## Note:
+    /// Symlinks are pointers rather than contents, so files which were only
+    /// found through symlinks are left out, and files are always read
+    /// through one of their other paths. Symlinks to the remaining files are
+    /// dropped from them unless `options.symlinks_as_duplicates` is set, in
+    /// which case they're kept as aliases of the files they point at.
+    pub fn finish(self) -> (SizewiseDups, usize) {
+        let options = self.options;
+        let mut files_by_size = self.files_by_size;
+        let mut empty_count = 0;
+        for (size, files) in files_by_size.iter_mut() {
+            *files = files
+                .drain()
+                .filter(|f| !f.files().is_empty())
+                .map(|f| {
+                    if options.symlinks_as_duplicates {
+                        f
+                    } else {
+                        f.without_symlinks()
+                    }
+                })
+                .collect();
+            if *size == 0 && !options.include_empty {
+                empty_count += files.len();
+                files.clear();
+            }
+        }
+        if options.no_size_filter {
+            files_by_size.retain(|_, files| !files.is_empty());
+        } else {
+            files_by_size.retain(|_, files| files.len() > 1);
+        }
+        (files_by_size, empty_count)
+    }
+}
+
+/// how much checksumming the groups of files with equal sizes would take,
+/// for a quick estimate before a full scan. See `analyze_sizes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeAnalysis {
+    pub group_count: usize,
+    pub candidate_count: usize, /* the files in the groups, which would be
+                                checksummed */
+    pub candidate_bytes: u64,
+    pub histogram: Vec<SizeBucket>, /* the sizes which have candidates, from
+                                    smallest to largest */
+    pub largest: Vec<(u64, usize)>, /* the size and file count of the groups
+                                    with the most bytes, most first */
+}
+
+/// the candidates whose size is at least `min_size` but less than 1024 times
+/// it. The first bucket starts at 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeBucket {
+    pub min_size: u64,
+    pub file_count: usize,
+    pub bytes: u64,
+}
+
+/// sums up `sizewise_dups` without reading any files, keeping the `top`
+/// groups with the most bytes in `SizeAnalysis::largest`. Ties are broken by
+/// size, so the result doesn't depend on the order of the map.
+pub fn analyze_sizes(sizewise_dups: &SizewiseDups, top: usize) -> SizeAnalysis {
+    let mut histogram: Vec<SizeBucket> = vec![];
+    let mut groups: Vec<(u64, usize)> = vec![];
+    for (&size, files) in sizewise_dups {
+        let bytes = size * files.len() as u64;
+        let min_size = match size {
+            0..=1023 => 0,
+            _ => 1024u64.pow(size.ilog(1024)),
+        };
+        match histogram.iter_mut().find(|b| b.min_size == min_size) {
+            Some(bucket) => {
+                bucket.file_count += files.len();
+                bucket.bytes += bytes;
+            }
+            None => histogram.push(SizeBucket {
+                min_size,
+                file_count: files.len(),
+                bytes,
+            }),
+        }
+        groups.push((size, files.len()));
+    }
+    histogram.sort_by_key(|b| b.min_size);
+    groups.sort_by(|(a_size, a_count), (b_size, b_count)| {
+        let bytes = |size: &u64, count: &usize| size * *count as u64;
+        (bytes(b_size, b_count).cmp(&bytes(a_size, a_count))).then(b_size.cmp(a_size))
+    });
+    SizeAnalysis {
+        group_count: sizewise_dups.len(),
+        candidate_count: histogram.iter().map(|b| b.file_count).sum(),
+        candidate_bytes: histogram.iter().map(|b| b.bytes).sum(),
+        histogram,
+        largest: groups.into_iter().take(top).collect(),
+    }
+}
+
+// a file's checksum, and when a cache is in use, the fingerprint it was
+// calculated for, or why it couldn't be calculated.
+type ChecksumResult = Result<(u32, MetaFile, Option<Fingerprint>), (PathBuf, io::Error)>;
+
+fn calc_file_checksumsr(
+    files: impl IntoParallelIterator<Item = MetaFile>,
+    size: u64,
+    options: &ScanOptions,
+    cache: Option<&ChecksumCache>,
+    progress: &ProgressBar,
+) -> Vec<ChecksumResult> {
+    let checksummed = options.read_stats.checksummed();
+    checksummed.time(|| {
+        files
+            .into_par_iter()
+            // files which are left out once interrupted are neither
+            // checksummed nor errors
+            .filter(|_| !options.interrupt.is_set())
+            .map(|f| {
+                progress.inc(1);
+                let p = f.primary();
+                // the fingerprint is from when the file was found, before
+                // it's read, so that if it changes in between, its checksum
+                // is cached with an outdated fingerprint and is recalculated
+                // next time.
+                let fingerprint = match cache {
+                    Some(_) => Fingerprint::from_stat(f.stat()),
+                    None => None,
+                };
+                let cached = match (cache, &fingerprint) {
+                    (Some(cache), Some(fingerprint)) => cache.get(p, fingerprint),
+                    _ => None,
+                };
+                if let Some(checksum) = cached {
+                    return Ok((checksum, f, None));
+                }
+                let len = options.hash_limit.map_or(size, |limit| limit.min(size));
+                let _open = options.open_files.acquire();
+                let checksum = if options.mmap && len > 0 {
+                    calc_mapped_file_checksum(p, size, len).transpose()
+                } else {
+                    None
+                };
+                let block_size = options.block_size.unwrap_or(READ_BLOCK_SIZE);
+                let checksum = checksum
+                    .unwrap_or_else(|| calc_streamed_file_checksum(p, size, len, block_size));
+                match checksum {
+                    Ok(checksum) => {
+                        checksummed.add_file();
+                        checksummed.add_bytes(len);
+                        Ok((checksum, f, fingerprint))
+                    }
+                    Err(e) => Err((p.clone(), e)),
+                }
+            })
+            .collect()
+    })
+}
+
+/// the checksum of each of `files`, which should all be `size` bytes long,
+/// calculated as `find_dups` does without a cache, even when there's only
+/// one of them. Files which can't be read, or which have changed size, are
+/// left out and added to `errors`.
+pub fn checksum_files(
+    files: impl IntoParallelIterator<Item = MetaFile>,
+    size: u64,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    progress: &ProgressBar,
+) -> Vec<(u32, MetaFile)> {
+    calc_file_checksumsr(files, size, options, None, progress)
+        .into_iter()
+        .filter_map(|checksum| match checksum {
+            Ok((checksum, f, _)) => Some((checksum, f)),
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn changed_size_error() -> io::Error {
+    io::Error::other("file changed size during the scan")
+}
+
+/// how many bytes files are read in at a time when they're checksummed,
+/// unless `ScanOptions::block_size` says otherwise.
+pub const READ_BLOCK_SIZE: usize = 64 * 1024;
+/// the range `clamp_block_size` keeps block sizes in.
+pub const MIN_BLOCK_SIZE: usize = 4 * 1024;
+pub const MAX_BLOCK_SIZE: usize = 64 * 1024 * 1024;
+
+/// `block_size` within `MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE`. Smaller blocks mean
+/// a read per few pages, and bigger ones a buffer per thread which can be
+/// bigger than the files being read.
+pub fn clamp_block_size(block_size: u64) -> usize {
+    block_size.clamp(MIN_BLOCK_SIZE as u64, MAX_BLOCK_SIZE as u64) as usize
+}
+
+/// calculates the checksum of the first `len` bytes of `p`, which should be
+/// `size` bytes long, reading `block_size` bytes at a time. The checksum is
+/// the same whatever the block size.
+fn calc_streamed_file_checksum(
+    p: &Path,
+    size: u64,
+    len: u64,
+    block_size: usize,
+) -> io::Result<u32> {
+    let mut file = File::open(p)?;
+    if file.metadata()?.len() != size {
+        return Err(changed_size_error());
+    }
+    let mut checksum = RollingAdler32::new();
+    let mut buffer = vec![0; block_size.min(len as usize)];
+    let mut remaining = len;
+    while remaining > 0 {
+        let block = &mut buffer[..block_size.min(remaining as usize)];
+        file.read_exact(block).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => changed_size_error(),
+            _ => e,
+        })?;
+        checksum.update_buffer(block);
+        remaining -= block.len() as u64;
+    }
+    if len == size && file.read(&mut [0])? != 0 {
+        return Err(changed_size_error());
+    }
+    Ok(checksum.hash())
+}
+
+/// calculates the checksum of the first `len` bytes of the file. Returns
+/// `None` if the file can't be mapped, in which case it should be read
+/// instead. Empty files can't be mapped.
+/// ## Note:
+/// Accessing a mapping of a file which has been truncated since it was
+/// mapped kills the process with SIGBUS on unix. To make that less likely,
+/// files whose size differs from `size`, the size they had when they were
+/// grouped, are skipped with an error rather than mapped, and the size is
+/// checked again once the checksum has been calculated. A file which is
+/// truncated while its checksum is being calculated can still crash the
+/// process, which is why mapping files is opt-in.
+fn calc_mapped_file_checksum(p: &Path, size: u64, len: u64) -> io::Result<Option<u32>> {
+    let file = File::open(p)?;
+    let check_size = |file: &File| match file.metadata()?.len() {
+        len if len == size => Ok(()),
+        _ => Err(changed_size_error()),
+    };
+    check_size(&file)?;
+    // SAFETY: the size of the file was just checked, see the note above.
+    let Ok(mapped_file) = (unsafe { Mmap::map(&file) }) else {
+        return Ok(None);
+    };
+    let checksum = adler32(&mapped_file[..len as usize])?;
+    check_size(&file)?;
+    Ok(Some(checksum))
+}
+
+/*
+   I'm using the term 'dup' to describe 2 or more files which
+   share the same checksum, therefore appearing to be duplicates from a
+   checksumwise perspective.
+*/
+
+// groups of files which share a size and a checksum. Files which were compared
+// block by block can form more than one group with the same size and
+// checksum, so the groups aren't keyed by them.
+pub type Dups = Vec<(u64, u32, HashSet<MetaFile>)>;
+
+/// groups of files at least this big are compared block by block, rather
+/// than checksummed. See `find_blockwise_dups`.
+const BLOCKWISE_MIN_SIZE: u64 = 16 * BLOCK_SIZE as u64;
+// the default size of the blocks they're compared in
+const BLOCK_SIZE: usize = 1 << 20;
+
+/// files which can't be read, or whose size has changed since the file list
+/// was built, e.g. because they were removed or edited mid-scan, or replaced
+/// with directories, are left out and added to `errors`. `progress` is
+/// incremented once per file, from whichever thread calculated its checksum.
+/// When a `cache` is given, files whose size and modification time haven't
+/// changed since they were cached aren't read, and the checksums of the
+/// files which are read in full are added to it. Big files are compared
+/// block by block either way, see `find_blockwise_dups`, and for them the
+/// cache only narrows down which files are compared.
+/// ## Note:
+/// When `options.hash_limit` is set, only that many bytes at the start of
+/// each file are compared, so files which only differ further on are
+/// grouped together as duplicates. The cache holds checksums of whole files,
+/// so it isn't used then.
+///
+/// When `options.limit` is set, no more files are read once that many groups
+/// of duplicates have been found, and only that many are returned. Files
+/// are compared one size at a time, in no particular order, so which groups
+/// those are can differ from one scan to the next.
+///
+/// When `options.interrupt` is set, no more files are read, and the groups
+/// found so far are returned. Files which hadn't been read by then are left
+/// out of them, so each group is made up of duplicates, but not necessarily
+/// all of them.
+///
+/// When `options.no_size_filter` is set, every file is checksummed in full,
+/// even if it's the only one of its size, and files of every size are
+/// grouped together by how many bytes were read from them and their
+/// checksum, see `find_dups_across_sizes`. It's much slower, and is meant
+/// for checking that grouping files by size first doesn't hide any
+/// duplicates, e.g. of files whose size changed after they were grouped by
+/// it. The cache isn't used then, and all of the groups are found before
+/// `found` is called with any of them.
+pub fn find_dups(
+    sizewise_dups: SizewiseDups,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    cache: Option<&mut ChecksumCache>,
+    progress: &ProgressBar,
+) -> Dups {
+    find_dups_streamed(sizewise_dups, options, errors, cache, progress, |_| {})
+}
+
+/// like `find_dups`, but `found` is called with each group as soon as it has
+/// been found, before the files of any other size are read, e.g. to report
+/// groups as the scan goes. Only the groups which are returned are passed to
+/// `found`, so under `options.limit` it's called at most that many times.
+pub fn find_dups_streamed(
+    mut sizewise_dups: SizewiseDups,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    mut cache: Option<&mut ChecksumCache>,
+    progress: &ProgressBar,
+    mut found: impl FnMut(DuplicateGroup),
+) -> Dups {
+    if options.no_size_filter {
+        let mut dups = find_dups_across_sizes(sizewise_dups, options, errors, progress);
+        if let Some(limit) = options.limit {
+            dups.truncate(limit);
+        }
+        for dup in &dups {
+            found(into_group(dup.clone()));
+        }
+        return dups;
+    }
+    if options.hash_limit.is_some() {
+        cache = None;
+    }
+    let mut dups: Dups = vec![];
+    let mut add_dups = |dups: &mut Dups, new_dups: Dups| {
+        for dup in new_dups {
+            if options.limit.is_some_and(|limit| dups.len() >= limit) {
+                break;
+            }
+            found(into_group(dup.clone()));
+            dups.push(dup);
+        }
+    };
+    for (size, files) in sizewise_dups.drain() {
+        if options.limit.is_some_and(|limit| dups.len() >= limit) || options.interrupt.is_set() {
+            break;
+        }
+        // a file on its own can't have duplicates
+        if files.len() < 2 {
+            progress.inc(files.len() as u64);
+            continue;
+        }
+        if size >= BLOCKWISE_MIN_SIZE {
+            let candidates = match cache.as_deref() {
+                Some(cache) => split_by_cached_checksum(files, cache, progress),
+                None => vec![files],
+            };
+            for files in candidates {
+                let groups = find_blockwise_dups(files, size, options, errors, progress);
+                if let Some(cache) = cache.as_deref_mut() {
+                    for (checksum, files) in &groups {
+                        for f in files {
+                            if let Some(fingerprint) = Fingerprint::from_stat(f.stat()) {
+                                cache.insert(f.primary(), fingerprint, *checksum);
+                            }
+                        }
+                    }
+                }
+                let groups = groups
+                    .into_iter()
+                    .map(|(checksum, files)| (size, checksum, files));
+                add_dups(&mut dups, groups.collect());
+            }
+            continue;
+        }
+        let mut files_by_checksum: HashMap<u32, HashSet<MetaFile>> = HashMap::new();
+        for checksum in calc_file_checksumsr(files, size, options, cache.as_deref(), progress) {
+            let (checksum, f, fingerprint) = match checksum {
+                Ok(checksum) => checksum,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            if let (Some(cache), Some(fingerprint)) = (cache.as_deref_mut(), fingerprint) {
+                cache.insert(f.primary(), fingerprint, checksum);
+            }
+            files_by_checksum
+                .entry(checksum)
+                .or_insert(HashSet::with_capacity(1))
+                .insert(f);
+        }
+        // collect all of the dups we found
+        let groups = files_by_checksum
+            .into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(checksum, files)| (size, checksum, files));
+        add_dups(&mut dups, groups.collect());
+    }
+    dups
+}
+
+/// like `find_dups` when `options.no_size_filter` is set. Every file is read
+/// to its end, whatever size it was found with, and files are grouped by how
+/// many bytes were read from them and their checksum, so files which only
+/// match after their size has changed are grouped together too. When
+/// `options.hash_limit` is set, only that many bytes of each file are
+/// checksummed, and files are grouped by their size as they're read.
+fn find_dups_across_sizes(
+    sizewise_dups: SizewiseDups,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    progress: &ProgressBar,
+) -> Dups {
+    let files: Vec<MetaFile> = sizewise_dups.into_values().flatten().collect();
+    let block_size = options.block_size.unwrap_or(READ_BLOCK_SIZE);
+    let checksummed = options.read_stats.checksummed();
+    let checksums: Vec<(MetaFile, io::Result<(u64, u32)>)> = checksummed.time(|| {
+        files
+            .into_par_iter()
+            // files which are left out once interrupted are neither
+            // checksummed nor errors
+            .filter(|_| !options.interrupt.is_set())
+            .map(|f| {
+                progress.inc(1);
+                let _open = options.open_files.acquire();
+                let checksum =
+                    calc_unsized_file_checksum(f.primary(), options.hash_limit, block_size);
+                if let Ok((size, _)) = checksum {
+                    checksummed.add_file();
+                    checksummed.add_bytes(options.hash_limit.map_or(size, |limit| limit.min(size)));
+                }
+                (f, checksum)
+            })
+            .collect()
+    });
+    let mut files_by_checksum: HashMap<(u64, u32), HashSet<MetaFile>> = HashMap::new();
+    for (f, checksum) in checksums {
+        match checksum {
+            Ok(key) => {
+                files_by_checksum.entry(key).or_default().insert(f);
+            }
+            Err(e) => errors.push((f.primary().clone(), e)),
+        }
+    }
+    files_by_checksum
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((size, checksum), files)| (size, checksum, files))
+        .collect()
+}
+
+/// reads `p` to its end, or up to `limit` bytes if given, `block_size` bytes
+/// at a time, and returns its size along with the checksum of what was
+/// read. The size is how many bytes were read, or when `limit` is given,
+/// the size the file has once they have been.
+fn calc_unsized_file_checksum(
+    p: &Path,
+    limit: Option<u64>,
+    block_size: usize,
+) -> io::Result<(u64, u32)> {
+    let file = File::open(p)?;
+    let mut reader = (&file).take(limit.unwrap_or(u64::MAX));
+    let mut checksum = RollingAdler32::new();
+    let mut buffer = vec![0; block_size];
+    let mut len = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        checksum.update_buffer(&buffer[..n]);
+        len += n as u64;
+    }
+    let size = match limit {
+        Some(_) => file.metadata()?.len(),
+        None => len,
+    };
+    Ok((size, checksum.hash()))
+}
+
+/// splits `files` up by their cached checksums, for `find_blockwise_dups` to
+/// compare each group of them. Files whose checksum doesn't match any other
+/// file's can't have duplicates, and are left out. If any of the files
+/// isn't cached, it could be a duplicate of any of the others, so they're
+/// all kept together.
+fn split_by_cached_checksum(
+    files: HashSet<MetaFile>,
+    cache: &ChecksumCache,
+    progress: &ProgressBar,
+) -> Vec<HashSet<MetaFile>> {
+    let cached = |f: &MetaFile| cache.get(f.primary(), &Fingerprint::from_stat(f.stat())?);
+    if !files.iter().all(|f| cached(f).is_some()) {
+        return vec![files];
+    }
+    let mut files_by_checksum: HashMap<u32, HashSet<MetaFile>> = HashMap::new();
+    for f in files {
+        if let Some(checksum) = cached(&f) {
+            files_by_checksum.entry(checksum).or_default().insert(f);
+        }
+    }
+    (files_by_checksum.into_values())
+        .filter(|files| {
+            if files.len() < 2 {
+                progress.inc(files.len() as u64);
+            }
+            files.len() > 1
+        })
+        .collect()
+}
+
+/// compares `files`, which are all `size` bytes long, by reading them
+/// `options.block_size` bytes at a time in lockstep, 1 MiB by default, up to
+/// `options.hash_limit` bytes if given. After each block, the files are
+/// split up by the contents of that block, and files which no longer match
+/// any other file aren't read any further. Unlike grouping by checksum,
+/// only files whose contents are identical are grouped together, and files
+/// which differ early on are only read up to where they differ. Returns the
+/// groups along with the checksum of their contents. Files which can't be
+/// read are left out and added to `errors`, and `progress` is incremented
+/// once per file, when it's dropped or found to be a duplicate. Once
+/// `options.interrupt` is set, no more blocks are read, and since none of
+/// the files have been compared in full, no groups are returned.
+/// ## Note:
+/// Blocks are read one per thread at a time, and only the first block of
+/// each part a group is split into is kept for the blocks of the other
+/// files to be compared with, so memory grows with how many different
+/// blocks there are rather than with how many files are compared. Files are
+/// kept open from one block to the next as far as `options.open_files`
+/// allows, see `OpenFileLimit::try_acquire`, and are opened for each block
+/// otherwise.
+fn find_blockwise_dups(
+    files: HashSet<MetaFile>,
+    size: u64,
+    options: &ScanOptions,
+    errors: &mut FileErrors,
+    progress: &ProgressBar,
+) -> Vec<(u32, HashSet<MetaFile>)> {
+    let end = options.hash_limit.map_or(size, |limit| limit.min(size));
+    let block_size = options.block_size.unwrap_or(BLOCK_SIZE);
+    let compared = options.read_stats.compared();
+    let start = Instant::now();
+    // each group of files which have matched so far, along with the
+    // checksum of what has been read of them
+    let files = files
+        .into_iter()
+        .map(|file| Candidate { file, handle: None });
+    let mut groups = vec![(RollingAdler32::new(), files.collect::<Vec<_>>())];
+    let mut offset = 0;
+    while offset < end && !groups.is_empty() {
+        if options.interrupt.is_set() {
+            groups.clear();
+            break;
+        }
+        let len = block_size.min((end - offset) as usize);
+        let is_last_block = offset + len as u64 == size;
+        let mut next_groups = vec![];
+        for (checksum, mut files) in groups {
+            // the first block of each part the group is split into, which the
+            // blocks of the other files in it are compared with
+            let mut split: Vec<(Vec<u8>, RollingAdler32, Vec<Candidate>)> = vec![];
+            while !files.is_empty() {
+                let batch_len = rayon::current_num_threads().min(files.len());
+                let mut batch: Vec<Candidate> = files.drain(..batch_len).collect();
+                let blocks: Vec<io::Result<Vec<u8>>> = batch
+                    .par_iter_mut()
+                    .map(|f| f.read_block(&options.open_files, offset, len, is_last_block))
+                    .collect();
+                for (f, block) in batch.into_iter().zip(blocks) {
+                    let block = match block {
+                        Ok(block) => {
+                            if offset == 0 {
+                                compared.add_file();
+                            }
+                            compared.add_bytes(len as u64);
+                            block
+                        }
+                        Err(e) => {
+                            progress.inc(1);
+                            errors.push((f.file.primary().clone(), e));
+                            continue;
+                        }
+                    };
+                    match split.iter_mut().find(|(first, ..)| *first == block) {
+                        Some((_, _, matching)) => matching.push(f),
+                        None => {
+                            let mut checksum = checksum.clone();
+                            checksum.update_buffer(&block);
+                            split.push((block, checksum, vec![f]));
+                        }
+                    }
+                }
+            }
+            for (_, checksum, files) in split {
+                if files.len() > 1 {
+                    next_groups.push((checksum, files));
+                } else {
+                    progress.inc(files.len() as u64);
+                }
+            }
+        }
+        groups = next_groups;
+        offset += len as u64;
+    }
+    compared.add_time(start.elapsed());
+    groups
+        .into_iter()
+        .map(|(checksum, files)| {
+            progress.inc(files.len() as u64);
+            (checksum.hash(), files.into_iter().map(|f| f.file).collect())
+        })
+        .collect()
+}
+
+// a file being compared by `find_blockwise_dups`, along with the handle
+// it's read through while it's kept open.
+struct Candidate<'a> {
+    file: MetaFile,
+    handle: Option<(File, OpenFilePermit<'a>)>,
+}
+
+impl<'a> Candidate<'a> {
+    // like `read_block`, but through the file's handle, which is opened and
+    // kept open if `open_files` allows it.
+    fn read_block(
+        &mut self,
+        open_files: &'a OpenFileLimit,
+        offset: u64,
+        len: usize,
+        is_last_block: bool,
+    ) -> io::Result<Vec<u8>> {
+        if self.handle.is_none() {
+            if let Some(permit) = open_files.try_acquire() {
+                self.handle = Some((File::open(self.file.primary())?, permit));
+            }
+        }
+        match &mut self.handle {
+            Some((file, _)) => read_block_from(file, offset, len, is_last_block),
+            None => {
+                let _open = open_files.acquire();
+                let mut file = File::open(self.file.primary())?;
+                read_block_from(&mut file, offset, len, is_last_block)
+            }
+        }
+    }
+}
+
+/// reads `len` bytes of `p`, starting at `offset`. When `is_last_block` is
+/// set, the file must end right after them.
+fn read_block(p: &Path, offset: u64, len: usize, is_last_block: bool) -> io::Result<Vec<u8>> {
+    read_block_from(&mut File::open(p)?, offset, len, is_last_block)
+}
+
+// like `read_block`, for a file which is already open.
+fn read_block_from(
+    file: &mut File,
+    offset: u64,
+    len: usize,
+    is_last_block: bool,
+) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut block = vec![0; len];
+    file.read_exact(&mut block).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => changed_size_error(),
+        _ => e,
+    })?;
+    if is_last_block && file.read(&mut [0])? != 0 {
+        return Err(changed_size_error());
+    }
+    Ok(block)
+}
+
+/*
+   I'm using the term 'probable dup' to describe 2 or more files which
+   share the same size and the same contents in a few sampled regions,
+   therefore probably being duplicates, without having been read in full.
+*/
+
+/// files at least this big are sampled by `find_probable_dups`, rather than
+/// read in full, when sampling is asked for. See `split_off_sampled`.
+pub const SAMPLE_MIN_SIZE: u64 = 16 * SAMPLE_BLOCK_SIZE as u64;
+pub const SAMPLE_BLOCK_SIZE: usize = 64 * 1024;
+
+/// two or more files which share a size and the contents of their first,
+/// middle and last blocks, and so are probably duplicates.
+/// ## Note:
+/// Only a few blocks of each file are read, so files which differ anywhere
+/// else, e.g. two versions of a video whose metadata was edited in the
+/// middle, are still grouped together. Probable duplicates must be checked
+/// before acting on them, and are never passed to an `Action`.
+#[derive(Debug, Clone)]
+pub struct ProbableGroup {
+    size: u64,
+    signature: u32,
+    files: Vec<MetaFile>, /* sorted by path */
+}
+
+impl ProbableGroup {
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// the checksum of the sampled blocks.
+    pub fn signature(&self) -> u32 {
+        self.signature
+    }
+
+    pub fn files(&self) -> &[MetaFile] {
+        &self.files
+    }
+
+    /// like `DuplicateGroup::map_paths`.
+    pub fn map_paths(self, f: impl Fn(&Path) -> PathBuf) -> Self {
+        Self {
+            files: self.files.into_iter().map(|mf| mf.map_paths(&f)).collect(),
+            ..self
+        }
+    }
+}
+
+/// removes the groups of files which are big enough to be sampled from
+/// `sizewise_dups` and returns them, for `find_probable_dups`.
+pub fn split_off_sampled(sizewise_dups: &mut SizewiseDups) -> SizewiseDups {
+    let sampled_sizes: Vec<u64> = sizewise_dups
+        .keys()
+        .copied()
+        .filter(|size| *size >= SAMPLE_MIN_SIZE)
+        .collect();
+    sampled_sizes
+        .into_iter()
+        .filter_map(|size| Some((size, sizewise_dups.remove(&size)?)))
+        .collect()
+}
+
+/// groups files which share a size by the checksum of their first, middle
+/// and last `block_size` bytes, see `ProbableGroup`. Files smaller than
+/// three blocks are read in full. The groups are sorted by descending size,
+/// and then by the path of each group's first file. Files which can't be
+/// read are left out and added to `errors`, and `progress` is incremented
+/// once per file. Once `interrupt` is set, no more sizes are sampled. What
+/// is read is counted in `read_stats`, see `ReadStats::sampled`.
+pub fn find_probable_dups(
+    mut sizewise_dups: SizewiseDups,
+    block_size: usize,
+    interrupt: &Interrupt,
+    read_stats: &ReadStats,
+    errors: &mut FileErrors,
+    progress: &ProgressBar,
+) -> Vec<ProbableGroup> {
+    let sampled = read_stats.sampled();
+    let mut groups = vec![];
+    for (size, files) in sizewise_dups.drain() {
+        if interrupt.is_set() {
+            break;
+        }
+        let signatures: Vec<(MetaFile, io::Result<u32>)> = sampled.time(|| {
+            files
+                .into_par_iter()
+                .map(|f| {
+                    progress.inc(1);
+                    let signature = sample_signature(f.primary(), size, block_size);
+                    if signature.is_ok() {
+                        sampled.add_file();
+                        sampled.add_bytes(size.min(3 * block_size as u64));
+                    }
+                    (f, signature)
+                })
+                .collect()
+        });
+        let mut files_by_signature: HashMap<u32, Vec<MetaFile>> = HashMap::new();
+        for (f, signature) in signatures {
+            match signature {
+                Ok(signature) => files_by_signature.entry(signature).or_default().push(f),
+                Err(e) => errors.push((f.primary().clone(), e)),
+            }
+        }
+        groups.extend(
+            files_by_signature
+                .into_iter()
+                .filter(|(_, files)| files.len() > 1)
+                .map(|(signature, mut files)| {
+                    files.sort();
+                    ProbableGroup {
+                        size,
+                        signature,
+                        files,
+                    }
+                }),
+        );
+    }
+    groups.sort_by(|a, b| {
+        b.size
+            .cmp(&a.size)
+            .then_with(|| a.files[0].cmp(&b.files[0]))
+    });
+    groups
+}
+
+// the checksum of the first, middle and last `block_size` bytes of a file
+// which is `size` bytes long, or of the whole file if it's smaller than
+// that.
+fn sample_signature(p: &Path, size: u64, block_size: usize) -> io::Result<u32> {
+    let block_len = block_size as u64;
+    if size < 3 * block_len {
+        return read_block(p, 0, size as usize, true).and_then(|bytes| adler32(bytes.as_slice()));
+    }
+    let mut signature = RollingAdler32::new();
+    for (offset, is_last_block) in [
+        (0, false),
+        ((size - block_len) / 2, false),
+        (size - block_len, true),
+    ] {
+        signature.update_buffer(&read_block(p, offset, block_size, is_last_block)?);
+    }
+    Ok(signature.hash())
+}
+
+/// an order for groups of duplicates. Ties are broken by the path of each
+/// group's first file, so that the order is deterministic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// by descending reclaimable space.
+    #[default]
+    Size,
+    /// by descending number of files.
+    Count,
+    /// by the path of each group's first file.
+    Path,
+}
+
+/// collects `dups` into groups in a deterministic order: by descending
+/// reclaimable space, and then by the path of each group's first file. The
+/// files within each group are sorted by path. See `sort_groups` for other
+/// orders.
+pub fn sorted_groups(dups: Dups) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = dups.into_iter().map(into_group).collect();
+    sort_groups(&mut groups, SortOrder::Size);
+    groups
+}
+
+// a group from `find_dups`, with its files sorted by path.
+fn into_group((size, checksum, files): (u64, u32, HashSet<MetaFile>)) -> DuplicateGroup {
+    let mut files: Vec<MetaFile> = files.into_iter().collect();
+    files.sort();
+    DuplicateGroup {
+        size,
+        checksum,
+        files,
+    }
+}
+
+/// sorts groups returned by `sorted_groups` into `order`.
+pub fn sort_groups(groups: &mut [DuplicateGroup], order: SortOrder) {
+    groups.sort_by(|a, b| {
+        let by_order = match order {
+            SortOrder::Size => b.reclaimable_space().cmp(&a.reclaimable_space()),
+            SortOrder::Count => b.files.len().cmp(&a.files.len()),
+            SortOrder::Path => Ordering::Equal,
+        };
+        by_order.then_with(|| a.files[0].cmp(&b.files[0]))
+    });
+}
+
+/// `p` relative to the first of `roots` it's in, e.g. `2023/a.jpg` for
+/// `photos/2023/a.jpg` found in `photos`. Paths which aren't in any of them,
+/// such as ones read from a list, are returned as they are.
+/// ## Note:
+/// Like `side_of`, paths are compared with the directories as they were
+/// given, which the paths found by walking them start with. Directories
+/// which are symlinks have to be resolved first, see `resolve_target_dir`.
+pub fn relative_path(p: &Path, roots: &[PathBuf]) -> PathBuf {
+    roots
+        .iter()
+        .find_map(|root| p.strip_prefix(root).ok())
+        .unwrap_or(p)
+        .to_path_buf()
+}
+
+/// which of two sets of directories a file was found in, see
+/// `cross_set_groups`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+    /// a file with paths in both sets, e.g. through hard links.
+    Both,
+}
+
+/// which of `set_a` and `set_b` the paths of `f` are in, or `None` if none
+/// of them are in either, e.g. when it was read from a list of paths.
+/// ## Note:
+/// Paths are compared with the directories as they were given, without
+/// canonicalizing them, since the paths found by walking a directory start
+/// with it. Directories which are symlinks have to be resolved first, see
+/// `resolve_target_dir`.
+pub fn side_of(f: &MetaFile, set_a: &[PathBuf], set_b: &[PathBuf]) -> Option<Side> {
+    let is_in = |set: &[PathBuf]| {
+        f.paths()
+            .iter()
+            .any(|p| set.iter().any(|d| p.starts_with(d)))
+    };
+    match (is_in(set_a), is_in(set_b)) {
+        (true, true) => Some(Side::Both),
+        (true, false) => Some(Side::A),
+        (false, true) => Some(Side::B),
+        (false, false) => None,
+    }
+}
+
+/// keeps the groups which have a file in `set_a` and a different file in
+/// `set_b`, leaving out those whose files are all in one of them. See
+/// `side_of`.
+pub fn cross_set_groups(
+    groups: Vec<DuplicateGroup>,
+    set_a: &[PathBuf],
+    set_b: &[PathBuf],
+) -> Vec<DuplicateGroup> {
+    groups
+        .into_iter()
+        .filter(|g| {
+            let sides: Vec<Option<Side>> =
+                g.files.iter().map(|f| side_of(f, set_a, set_b)).collect();
+            let in_a = |side: &Option<Side>| matches!(side, Some(Side::A | Side::Both));
+            let in_b = |side: &Option<Side>| matches!(side, Some(Side::B | Side::Both));
+            sides.iter().enumerate().any(|(a_idx, a)| {
+                in_a(a) && (sides.iter().enumerate()).any(|(b_idx, b)| b_idx != a_idx && in_b(b))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    use adler32::adler32;
+    use indexmap::indexset;
+    use indicatif::ProgressBar;
+
+    use crate::cache::{ChecksumCache, Fingerprint};
+    use crate::interrupt::Interrupt;
+    use crate::metafile::{collect_into_metafiles, MetaFile};
+    use crate::open_files::OpenFileLimit;
+    use crate::read_stats::ReadStats;
+
+    use super::{
+        add_to_file_list, analyze_sizes, build_file_list, calc_mapped_file_checksum,
+        calc_streamed_file_checksum, canonical_file_path, clamp_block_size, cross_set_groups,
+        dedup_target_dirs, find_blockwise_dups, find_duplicates, find_dups, find_namewise_dups,
+        find_probable_dups, find_sizewise_dups, has_allowed_extension, is_same_file,
+        iter_duplicates, parse_extensions, read_path_list, side_of, sort_groups, split_off_sampled,
+        stream_sizewise_dups, Annotation, Digest, DuplicateGroup, ScanOptions, Side, SizeBucket,
+        SizeGrouper, SizewiseDups, SortOrder, BLOCKWISE_MIN_SIZE, MAX_BLOCK_SIZE, MIN_BLOCK_SIZE,
+        SAMPLE_MIN_SIZE,
+    };
+
+    #[test]
+    fn finds_duplicates() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-find-duplicates");
+        fs::create_dir_all(root.join("nested"))?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("nested").join("b"), "meow")?;
+        fs::write(root.join("c"), "nyaa")?;
+        fs::write(root.join("d"), "mrrp!")?;
+        /* test */
+        let options = ScanOptions {
+            recursive: true,
+            ..ScanOptions::default()
+        };
+        let groups = find_duplicates(&[PathBuf::from(root)], &options)?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].checksum(), adler32(&b"meow"[..])?);
+        let mut paths: Vec<&PathBuf> = groups[0].files().iter().map(|f| f.paths()[0]).collect();
+        paths.sort();
+        assert_eq!(paths, [&root.join("a"), &root.join("nested").join("b")]);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn skips_vanished_files() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-vanished");
+        fs::create_dir(root)?;
+        for name in ["a", "b", "c", "d", "e"] {
+            fs::write(root.join(name), "meow")?;
+        }
+        let options = ScanOptions::default();
+        let mut errors = vec![];
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        assert!(errors.is_empty());
+        /* test */
+        // sizes are recorded when the file list is built, so files which
+        // vanish or change size afterwards are only noticed when read
+        fs::remove_file(root.join("a"))?;
+        let progress = ProgressBar::hidden();
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        fs::remove_file(root.join("b"))?;
+        fs::write(root.join("c"), "meowmeow")?;
+        let dups = find_dups(sizewise_dups, &options, &mut errors, None, &progress);
+        errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let skipped: Vec<(&PathBuf, io::ErrorKind)> =
+            errors.iter().map(|(p, e)| (p, e.kind())).collect();
+        assert_eq!(
+            skipped,
+            [
+                (&root.join("a"), io::ErrorKind::NotFound),
+                (&root.join("b"), io::ErrorKind::NotFound),
+                (&root.join("c"), io::ErrorKind::Other)
+            ]
+        );
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].2.len(), 2);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn skips_directories() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-skips-directories");
+        fs::create_dir_all(root.join("dir1"))?;
+        fs::create_dir_all(root.join("dir2"))?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        let mut file_list = indexset![];
+        let mut errors = vec![];
+        collect_into_metafiles(
+            &mut file_list,
+            ["dir1", "dir2", "a", "b"].map(|name| root.join(name)),
+            true,
+            &mut errors,
+        );
+        assert_eq!(file_list.len(), 4);
+        /* test */
+        // directories which make it into the file list, e.g. because a file
+        // was replaced with one mid-scan, are left out once they're read
+        let options = ScanOptions::default();
+        let progress = ProgressBar::hidden();
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        let dups = find_dups(sizewise_dups, &options, &mut errors, None, &progress);
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].2.len(), 2);
+        // the sizes of directories vary between filesystems, so whether they
+        // end up in the same group, and are read, does too
+        assert!(errors.iter().all(|(p, _)| p.is_dir()));
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn deterministic_order() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-deterministic-order");
+        fs::create_dir(root)?;
+        for (name, contents) in [
+            ("a1", "meow"),
+            ("a2", "meow"),
+            ("b1", "nyaa"),
+            ("b2", "nyaa"),
+            ("b3", "nyaa"),
+            ("c1", "meowmeowmeow"),
+            ("c2", "meowmeowmeow"),
+            ("d1", "mrrp"),
+            ("d2", "mrrp"),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        /* test */
+        let scan = || -> io::Result<Vec<(u32, Vec<PathBuf>)>> {
+            let groups = find_duplicates(&[PathBuf::from(root)], &ScanOptions::default())?;
+            Ok(groups
+                .iter()
+                .map(|g| {
+                    let paths = g.files().iter().map(|f| f.paths()[0].clone()).collect();
+                    (g.checksum(), paths)
+                })
+                .collect())
+        };
+        let first = scan()?;
+        assert_eq!(first, scan()?);
+        let first_paths: Vec<&PathBuf> = first.iter().map(|(_, paths)| &paths[0]).collect();
+        // c: 12 bytes reclaimable, b: 8 bytes, a and d: 4 bytes, by path
+        assert_eq!(
+            first_paths,
+            [
+                &root.join("c1"),
+                &root.join("b1"),
+                &root.join("a1"),
+                &root.join("d1")
+            ]
+        );
+        assert_eq!(
+            first[1].1,
+            [root.join("b1"), root.join("b2"), root.join("b3")]
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn sort_orders() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-sort-orders");
+        fs::create_dir(root)?;
+        for (name, contents) in [
+            ("a1", "meowmeowmeowmeow"),
+            ("a2", "meowmeowmeowmeow"),
+            ("b1", "nyaa"),
+            ("b2", "nyaa"),
+            ("b3", "nyaa"),
+            ("b4", "nyaa"),
+            ("c1", "mrrpmrrp"),
+            ("c2", "mrrpmrrp"),
+            ("c3", "mrrpmrrp"),
+            ("d1", "purr"),
+            ("d2", "purr"),
+            ("d3", "purr"),
+            ("d4", "purr"),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        let mut groups = find_duplicates(&[PathBuf::from(root)], &ScanOptions::default())?;
+        /* test */
+        let mut first_paths = |order| -> Vec<PathBuf> {
+            sort_groups(&mut groups, order);
+            groups
+                .iter()
+                .map(|g| g.files()[0].paths()[0].clone())
+                .collect()
+        };
+        let paths = |names: [&str; 4]| names.map(|name| root.join(name));
+        // reclaimable space: a and c 16 bytes, b and d 12 bytes
+        assert_eq!(
+            first_paths(SortOrder::Size),
+            paths(["a1", "c1", "b1", "d1"])
+        );
+        // files: b and d 4, c 3, a 2
+        assert_eq!(
+            first_paths(SortOrder::Count),
+            paths(["b1", "d1", "c1", "a1"])
+        );
+        assert_eq!(
+            first_paths(SortOrder::Path),
+            paths(["a1", "b1", "c1", "d1"])
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn iter_duplicates_matches_find_duplicates() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-iter-duplicates");
+        fs::create_dir(root)?;
+        for idx in 0..24 {
+            let contents = "meow".repeat(idx % 4 + 1) + &(idx % 8).to_string();
+            fs::write(root.join(format!("file{idx}")), contents)?;
+        }
+        fs::write(root.join("unique"), "purr")?;
+        let dirs = [PathBuf::from(root)];
+        let options = ScanOptions::default();
+        /* test */
+        let summarize = |groups: Vec<DuplicateGroup>| {
+            let mut groups: Vec<(u64, u32, Vec<PathBuf>)> = (groups.iter())
+                .map(|g| {
+                    let paths = g.files().iter().map(|f| f.paths()[0].clone()).collect();
+                    (g.size(), g.checksum(), paths)
+                })
+                .collect();
+            groups.sort();
+            groups
+        };
+        let batch = summarize(find_duplicates(&dirs, &options)?);
+        assert_eq!(batch.len(), 8);
+        let mut duplicates = iter_duplicates(&dirs, &options);
+        let lazy = summarize(duplicates.by_ref().collect::<io::Result<_>>()?);
+        assert_eq!(lazy, batch);
+        assert!(duplicates.errors().is_empty());
+        // the files of each group are sorted, like find_duplicates' are
+        assert!(lazy.iter().all(|(_, _, paths)| paths.is_sorted()));
+        // no more groups are yielded than --limit allows
+        let limited = ScanOptions {
+            limit: Some(3),
+            ..ScanOptions::default()
+        };
+        assert_eq!(iter_duplicates(&dirs, &limited).count(), 3);
+        // and if the directories can't be walked, that's the only item
+        let mut missing = iter_duplicates(&[root.join("missing")], &options);
+        assert!(missing.next().is_some_and(|group| group.is_err()));
+        assert!(missing.next().is_none());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn thread_count_doesnt_change_results() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-threads");
+        fs::create_dir(root)?;
+        for idx in 0..16 {
+            fs::write(root.join(format!("file{idx}")), format!("meow{}", idx % 3))?;
+        }
+        /* test */
+        let scan = |threads| -> io::Result<Vec<(u32, Vec<PathBuf>)>> {
+            let options = ScanOptions {
+                threads,
+                ..ScanOptions::default()
+            };
+            let groups = find_duplicates(&[PathBuf::from(root)], &options)?;
+            Ok(groups
+                .iter()
+                .map(|g| {
+                    let paths = g.files().iter().map(|f| f.paths()[0].clone()).collect();
+                    (g.checksum(), paths)
+                })
+                .collect())
+        };
+        let sequential = scan(1)?;
+        assert_eq!(sequential.len(), 3);
+        assert_eq!(scan(2)?, sequential);
+        assert_eq!(scan(0)?, sequential);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn path_list() -> io::Result<()> {
+        let list = b"a/b\n\nc d  \r\n  \n e\n";
+        assert_eq!(
+            read_path_list(&list[..], b'\n')?,
+            [
+                PathBuf::from("a/b"),
+                PathBuf::from("c d"),
+                PathBuf::from(" e")
+            ]
+        );
+        let list = b"a\nb\0\0c \0";
+        assert_eq!(
+            read_path_list(&list[..], b'\0')?,
+            [PathBuf::from("a\nb"), PathBuf::from("c ")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_target_dirs() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-nested-targets");
+        let nested = root.join("nested");
+        fs::create_dir_all(&nested)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(nested.join("b"), "meow")?;
+        let dirs = [
+            nested.clone(),
+            PathBuf::from(root),
+            root.join(".").join("nested"),
+        ];
+        let recursive = ScanOptions {
+            recursive: true,
+            ..ScanOptions::default()
+        };
+        /* test */
+        let (kept, dropped) = dedup_target_dirs(&dirs, &recursive)?;
+        assert_eq!(kept, [PathBuf::from(root)]);
+        assert_eq!(
+            dropped,
+            [
+                (nested.clone(), PathBuf::from(root)),
+                (root.join(".").join("nested"), PathBuf::from(root))
+            ]
+        );
+        // without recursion, the nested dir isn't covered by its parent
+        let (kept, dropped) = dedup_target_dirs(&dirs, &ScanOptions::default())?;
+        assert_eq!(kept, [nested.clone(), PathBuf::from(root)]);
+        assert_eq!(dropped, [(root.join(".").join("nested"), nested.clone())]);
+        let groups = find_duplicates(&dirs, &recursive)?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files().len(), 2);
+        assert!(groups[0].files().iter().all(|f| f.paths().len() == 1));
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn excluded_files() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-excluded-files");
+        fs::create_dir_all(root.join("nested"))?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("nested").join("cache"), "meow")?;
+        fs::write(root.join("nested").join("b"), "meow")?;
+        /* test */
+        // the excluded file is matched by its canonical path, however it's
+        // reached
+        let options = ScanOptions {
+            recursive: true,
+            excluded_files: vec![canonical_file_path(&root.join("nested").join("cache"))?],
+            ..ScanOptions::default()
+        };
+        let mut errors = vec![];
+        let dir = root.join("nested").join("..");
+        let file_list = build_file_list(std::slice::from_ref(&dir), &options, &mut errors)?;
+        let mut paths: Vec<PathBuf> = file_list.iter().map(|f| f.paths()[0].clone()).collect();
+        paths.sort();
+        assert_eq!(paths, [dir.join("a"), dir.join("nested").join("b")]);
+        // and needn't exist yet
+        assert_eq!(
+            canonical_file_path(&root.join("missing"))?,
+            root.canonicalize()?.join("missing")
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn mtime_filters() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-mtime-filters");
+        fs::create_dir(root)?;
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+        for (name, mtime) in [
+            ("old", now - 30 * day),
+            ("recent", now - day),
+            ("future", now + 30 * day),
+        ] {
+            fs::write(root.join(name), "meow")?;
+            fs::File::options()
+                .write(true)
+                .open(root.join(name))?
+                .set_modified(mtime)?;
+        }
+        /* test */
+        let scan = |newer_than, older_than| -> io::Result<Vec<PathBuf>> {
+            let options = ScanOptions {
+                newer_than,
+                older_than,
+                ..ScanOptions::default()
+            };
+            let mut errors = vec![];
+            let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+            assert!(errors.is_empty());
+            let mut paths: Vec<PathBuf> = file_list.iter().map(|f| f.paths()[0].clone()).collect();
+            paths.sort();
+            Ok(paths)
+        };
+        let paths =
+            |names: &[&str]| -> Vec<PathBuf> { names.iter().map(|n| root.join(n)).collect() };
+        assert_eq!(scan(None, None)?, paths(&["future", "old", "recent"]));
+        assert_eq!(
+            scan(Some(now - 7 * day), None)?,
+            paths(&["future", "recent"])
+        );
+        assert_eq!(scan(None, Some(now - 7 * day))?, paths(&["old"]));
+        assert_eq!(scan(Some(now - 7 * day), Some(now))?, paths(&["recent"]));
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn streaming_matches_batch() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-streaming");
+        fs::create_dir_all(root.join("nested"))?;
+        for (name, contents) in [
+            ("a1", "meow"),
+            ("a2", "nyaa"),
+            ("nested/a3", "meow"),
+            ("b1", "meowmeow"),
+            ("b2", "meowmeow"),
+            ("c", "purr"),
+            ("empty1", ""),
+            ("nested/empty2", ""),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        fs::hard_link(root.join("b1"), root.join("nested").join("b1-hardlink"))?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("../a1", root.join("nested").join("a1-symlink"))?;
+        /* test */
+        // the paths of each group of files with equal sizes, in order
+        let normalize = |(dups, empty_count): (SizewiseDups, usize)| {
+            let mut groups: Vec<(u64, Vec<Vec<PathBuf>>)> = dups
+                .into_iter()
+                .map(|(size, files)| {
+                    let mut files: Vec<Vec<PathBuf>> = files
+                        .iter()
+                        .map(|f| f.paths().into_iter().cloned().collect())
+                        .collect();
+                    files.sort();
+                    (size, files)
+                })
+                .collect();
+            groups.sort();
+            (groups, empty_count)
+        };
+        for symlinks_as_duplicates in [false, true] {
+            let options = ScanOptions {
+                recursive: true,
+                symlinks_as_duplicates,
+                ..ScanOptions::default()
+            };
+            let dirs = [PathBuf::from(root)];
+            let mut errors = vec![];
+            let file_list = build_file_list(&dirs, &options, &mut errors)?;
+            let progress = ProgressBar::hidden();
+            let batch = normalize(find_sizewise_dups(file_list.clone(), &options, &progress));
+            let streamed = normalize(stream_sizewise_dups(&dirs, &options, &mut errors)?);
+            assert_eq!(streamed, batch);
+            assert_eq!(batch.1, 2);
+            assert_eq!(batch.0.len(), 2);
+            // adding paths one at a time, so that paths to the same file are
+            // added separately
+            let mut grouper = SizeGrouper::new(&options);
+            for f in &file_list {
+                for p in f.paths() {
+                    grouper.add_paths([p.clone()], &mut errors);
+                }
+            }
+            assert_eq!(grouper.file_count(), file_list.len());
+            assert_eq!(normalize(grouper.finish()), batch);
+            assert!(errors.is_empty());
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn namewise_dups() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-namewise-dups");
+        for dir in ["a", "b", "c"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        fs::write(root.join("a").join("README.md"), "meow")?;
+        fs::write(root.join("b").join("readme.md"), "nyaa")?;
+        fs::write(root.join("c").join("README.md"), "purr")?;
+        fs::write(root.join("c").join("notes.md"), "mrrp")?;
+        let names = |ignore_case: bool| -> io::Result<Vec<(OsString, usize)>> {
+            let options = ScanOptions {
+                recursive: true,
+                ignore_case,
+                ..ScanOptions::default()
+            };
+            let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut vec![])?;
+            let mut names: Vec<(OsString, usize)> = find_namewise_dups(&file_list, &options)
+                .into_iter()
+                .map(|(name, paths)| (name, paths.len()))
+                .collect();
+            names.sort();
+            Ok(names)
+        };
+        /* test */
+        assert_eq!(names(false)?, [(OsString::from("README.md"), 2)]);
+        // names which only differ in case are grouped under the first of them
+        let names = names(true)?;
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].1, 3);
+        assert!(names[0].0.eq_ignore_ascii_case("readme.md"));
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn ignore_case() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-ignore-case");
+        fs::create_dir(root)?;
+        fs::write(root.join("Photo.JPG"), "meow")?;
+        fs::write(root.join("photo.jpg"), "meow")?;
+        fs::write(root.join("copy.jpg"), "meow")?;
+        fs::hard_link(root.join("copy.jpg"), root.join("COPY.jpg"))?;
+        let ignore_case = ScanOptions {
+            ignore_case: true,
+            ..ScanOptions::default()
+        };
+        let list = |options: &ScanOptions| -> Vec<Vec<PathBuf>> {
+            let mut acc = indexset![];
+            let mut errors = vec![];
+            let paths = ["Photo.JPG", "photo.jpg", "copy.jpg", "COPY.jpg"];
+            let paths = paths.map(|name| root.join(name));
+            add_to_file_list(&mut acc, paths[..2].to_vec(), options, &mut errors);
+            add_to_file_list(&mut acc, paths[2..].to_vec(), options, &mut errors);
+            assert!(errors.is_empty());
+            acc.iter()
+                .map(|f| f.paths().into_iter().cloned().collect())
+                .collect()
+        };
+        /* test */
+        // distinct files, and a file reached through two casings
+        assert_eq!(
+            list(&ScanOptions::default()),
+            [
+                vec![root.join("Photo.JPG")],
+                vec![root.join("photo.jpg")],
+                vec![root.join("COPY.jpg"), root.join("copy.jpg")],
+            ]
+        );
+        assert_eq!(
+            list(&ignore_case),
+            [vec![root.join("Photo.JPG")], vec![root.join("copy.jpg")]]
+        );
+        // only the distinct files are warned about
+        assert!(is_same_file(&root.join("copy.jpg"), &root.join("COPY.jpg")));
+        assert!(!is_same_file(
+            &root.join("Photo.JPG"),
+            &root.join("photo.jpg")
+        ));
+        // the first of the casings to be found is the one which is kept
+        let dirs = [PathBuf::from(root)];
+        let (dups, _) = stream_sizewise_dups(&dirs, &ignore_case, &mut vec![])?;
+        assert_eq!(dups[&4].len(), 2);
+        let photos = dups[&4]
+            .iter()
+            .flat_map(|f| f.paths())
+            .filter(|p| p.to_string_lossy().to_lowercase().ends_with("photo.jpg"));
+        assert_eq!(photos.count(), 1);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn verify_links() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-verify-links");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        fs::write(root.join("c"), "nyaa!")?;
+        let options = ScanOptions::default();
+        let paths = ["a", "b", "c"].map(|name| root.join(name));
+        let mut file_list = indexset![];
+        collect_into_metafiles(&mut file_list, paths.clone(), false, &mut vec![]);
+        // as if all three files had been reported with the identifier of a
+        let id = file_list[0].id();
+        let collided = MetaFile::new(id, 4, paths.iter().cloned().collect(), indexset![]);
+        let group = |dups: &SizewiseDups, size| -> Vec<Vec<PathBuf>> {
+            let mut files: Vec<Vec<PathBuf>> = dups[&size]
+                .iter()
+                .map(|f| f.paths().into_iter().cloned().collect())
+                .collect();
+            files.sort();
+            files
+        };
+        /* test */
+        let mut grouper = SizeGrouper::new(&options);
+        grouper.add(collided.clone());
+        assert!(grouper.finish().0.is_empty());
+        let mut grouper = SizeGrouper::new(&options);
+        grouper.add(collided);
+        for f in file_list.iter().skip(1) {
+            grouper.add(MetaFile::new(f.id(), f.size(), indexset![], indexset![]));
+        }
+        assert_eq!(
+            grouper.verify_links(),
+            [
+                (paths[1].clone(), paths[0].clone()),
+                (paths[2].clone(), paths[0].clone())
+            ]
+        );
+        let (dups, _) = grouper.finish();
+        assert_eq!(dups.len(), 1);
+        assert_eq!(
+            group(&dups, 4),
+            [vec![paths[0].clone()], vec![paths[1].clone()]]
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn limit() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-limit");
+        fs::create_dir(root)?;
+        for (idx, contents) in ["meow", "nyaa!", "purrr!"].into_iter().enumerate() {
+            for copy in 0..=idx {
+                fs::write(root.join(format!("{contents}{copy}")), contents)?;
+            }
+            fs::write(root.join(format!("{contents}{}", idx + 1)), contents)?;
+        }
+        let dirs = [PathBuf::from(root)];
+        let limited = |limit| {
+            let options = ScanOptions {
+                limit,
+                ..ScanOptions::default()
+            };
+            find_duplicates(&dirs, &options)
+        };
+        /* test */
+        assert_eq!(limited(None)?.len(), 3);
+        assert_eq!(limited(Some(1))?.len(), 1);
+        assert_eq!(limited(Some(2))?.len(), 2);
+        assert_eq!(limited(Some(5))?.len(), 3);
+        // the groups which are found are complete
+        let group = &limited(Some(1))?[0];
+        assert_eq!(group.files().len() as u64, group.size() - 2);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn interrupt() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-interrupt");
+        fs::create_dir(root)?;
+        for contents in ["meow", "nyaa!", "purrr!"] {
+            fs::write(root.join(format!("{contents}0")), contents)?;
+            fs::write(root.join(format!("{contents}1")), contents)?;
+        }
+        let dirs = [PathBuf::from(root)];
+        let interrupted_after = |checks| ScanOptions {
+            interrupt: Interrupt::after(checks),
+            ..ScanOptions::default()
+        };
+        let mut errors = vec![];
+        let file_list = build_file_list(&dirs, &ScanOptions::default(), &mut errors)?;
+        let (sizewise_dups, _) = find_sizewise_dups(
+            file_list.clone(),
+            &ScanOptions::default(),
+            &ProgressBar::hidden(),
+        );
+        let dups_interrupted_after = |checks| {
+            let progress = ProgressBar::hidden();
+            let options = interrupted_after(checks);
+            let dups = find_dups(
+                sizewise_dups.clone(),
+                &options,
+                &mut vec![],
+                None,
+                &progress,
+            );
+            (dups, progress.position())
+        };
+        /* test */
+        // the walk is checked before each path
+        assert_eq!(
+            build_file_list(&dirs, &interrupted_after(2), &mut errors)?.len(),
+            2
+        );
+        let progress = ProgressBar::hidden();
+        find_sizewise_dups(file_list, &interrupted_after(5), &progress);
+        assert_eq!(progress.position(), 5);
+        // each size is checked once, and then each of its files
+        let (dups, read) = dups_interrupted_after(0);
+        assert!(dups.is_empty());
+        assert_eq!(read, 0);
+        let (dups, read) = dups_interrupted_after(1);
+        assert!(dups.is_empty());
+        assert_eq!(read, 0);
+        let (dups, read) = dups_interrupted_after(2);
+        assert!(dups.is_empty());
+        assert_eq!(read, 1);
+        let (dups, read) = dups_interrupted_after(3);
+        assert_eq!(dups.len(), 1);
+        assert_eq!(dups[0].2.len(), 2);
+        assert_eq!(read, 2);
+        let (dups, read) = dups_interrupted_after(6);
+        assert_eq!(dups.len(), 2);
+        assert_eq!(read, 4);
+        assert_eq!(dups_interrupted_after(usize::MAX).0.len(), 3);
+        // blocks are only compared until interrupted, when no files have been
+        // compared in full
+        let files: HashSet<MetaFile> = sizewise_dups.values().next().unwrap().clone();
+        let size = files.iter().next().unwrap().size();
+        let blockwise_after = |checks| {
+            find_blockwise_dups(
+                files.clone(),
+                size,
+                &ScanOptions {
+                    block_size: Some(2),
+                    interrupt: Interrupt::after(checks),
+                    ..ScanOptions::default()
+                },
+                &mut vec![],
+                &ProgressBar::hidden(),
+            )
+        };
+        assert!(blockwise_after(1).is_empty());
+        assert_eq!(blockwise_after(usize::MAX).len(), 1);
+        assert!(errors.is_empty());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_special_files() -> io::Result<()> {
+        use std::os::unix::net::UnixListener;
+        use std::process::Command;
+
+        /* setup */
+        let root = Path::new("test-tmp-special-files");
+        fs::create_dir(root)?;
+        fs::write(root.join("empty1"), "")?;
+        fs::write(root.join("empty2"), "")?;
+        let fifo = root.join("fifo");
+        let status = Command::new("mkfifo").arg(&fifo).status()?;
+        assert!(status.success());
+        let socket = root.join("socket");
+        let _listener = UnixListener::bind(&socket)?;
+        std::os::unix::fs::symlink("fifo", root.join("fifo-symlink"))?;
+        // the fifo and socket are empty too, so they'd be read along with
+        // the empty files, and reading the fifo would block forever
+        let options = ScanOptions {
+            include_empty: true,
+            symlinks_as_duplicates: true,
+            ..ScanOptions::default()
+        };
+        let dirs = [PathBuf::from(root)];
+        /* test */
+        let mut errors = vec![];
+        let file_list = build_file_list(&dirs, &options, &mut errors)?;
+        assert_eq!(file_list.len(), 2);
+        let mut skipped: Vec<&PathBuf> = errors.iter().map(|(p, _)| p).collect();
+        skipped.sort();
+        assert_eq!(skipped, [&fifo, &root.join("fifo-symlink"), &socket]);
+        assert!(errors
+            .iter()
+            .all(|(_, e)| e.kind() == io::ErrorKind::InvalidInput));
+        let groups = find_duplicates(&dirs, &options)?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files().len(), 2);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_unreadable_dirs() -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        /* setup */
+        let root = Path::new("test-tmp-unreadable-dirs");
+        let locked = root.join("locked");
+        fs::create_dir_all(&locked)?;
+        fs::create_dir_all(root.join("open"))?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(locked.join("b"), "meow")?;
+        fs::write(root.join("open").join("c"), "meow")?;
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))?;
+        let options = ScanOptions {
+            recursive: true,
+            ..ScanOptions::default()
+        };
+        let dirs = [PathBuf::from(root)];
+        /* test */
+        // permissions don't apply to root, in which case there's nothing to
+        // test
+        if fs::read_dir(&locked).is_err() {
+            let mut errors = vec![];
+            let file_list = build_file_list(&dirs, &options, &mut errors)?;
+            // the walk carries on past the locked directory
+            assert_eq!(file_list.len(), 2);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].0, locked);
+            assert_eq!(errors[0].1.kind(), io::ErrorKind::PermissionDenied);
+            let mut errors = vec![];
+            let (sizewise_dups, _) = stream_sizewise_dups(&dirs, &options, &mut errors)?;
+            assert_eq!(sizewise_dups[&4].len(), 2);
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].0, locked);
+        }
+        /* cleanup */
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))?;
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn cross_set() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-cross-set");
+        let (originals, backup) = (root.join("originals"), root.join("backup"));
+        fs::create_dir_all(&originals)?;
+        fs::create_dir_all(&backup)?;
+        for (dir, name, contents) in [
+            // backed up
+            (&originals, "a", "meow"),
+            (&backup, "a", "meow"),
+            (&backup, "a-again", "meow"),
+            // only duplicated within one side
+            (&originals, "b1", "nyaa!"),
+            (&originals, "b2", "nyaa!"),
+            (&backup, "c1", "purr~~"),
+            (&backup, "c2", "purr~~"),
+            // not backed up
+            (&originals, "d", "mrrp~~~"),
+        ] {
+            fs::write(dir.join(name), contents)?;
+        }
+        fs::hard_link(originals.join("d"), backup.join("d-hardlink"))?;
+        let options = ScanOptions::default();
+        let groups = find_duplicates(&[originals.clone(), backup.clone()], &options)?;
+        /* test */
+        assert_eq!(groups.len(), 3);
+        let (set_a, set_b) = ([originals.clone()], [backup.clone()]);
+        let cross = cross_set_groups(groups.clone(), &set_a, &set_b);
+        assert_eq!(cross.len(), 1);
+        let mut sides: Vec<Option<Side>> = cross[0]
+            .files()
+            .iter()
+            .map(|f| side_of(f, &set_a, &set_b))
+            .collect();
+        sides.sort_by_key(|s| *s == Some(Side::A));
+        assert_eq!(sides, [Some(Side::B), Some(Side::B), Some(Side::A)]);
+        // a file linked into both sets isn't a duplicate of itself
+        let mut hardlinked = MetaFile::from_id_and_path(0, 7, originals.join("d"));
+        hardlinked.add_file(backup.join("d-hardlink"));
+        assert_eq!(side_of(&hardlinked, &set_a, &set_b), Some(Side::Both));
+        assert_eq!(
+            side_of(&hardlinked, &[root.join("elsewhere")], &set_b),
+            Some(Side::B)
+        );
+        assert!(cross_set_groups(groups, &[originals.join("b1")], &set_b).is_empty());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn skips_empty_files() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-empty");
+        fs::create_dir(root)?;
+        for name in ["a", "b", "c"] {
+            fs::write(root.join(name), "")?;
+        }
+        fs::write(root.join("d"), "meow")?;
+        fs::write(root.join("e"), "meow")?;
+        let scan = |include_empty| -> io::Result<(Vec<u64>, usize)> {
+            let options = ScanOptions {
+                include_empty,
+                ..ScanOptions::default()
+            };
+            let mut errors = vec![];
+            let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+            let progress = ProgressBar::hidden();
+            let (sizewise_dups, empty_count) = find_sizewise_dups(file_list, &options, &progress);
+            let mut sizes: Vec<u64> = sizewise_dups.keys().copied().collect();
+            sizes.sort();
+            Ok((sizes, empty_count))
+        };
+        /* test */
+        assert_eq!(scan(false)?, (vec![4], 3));
+        assert_eq!(scan(true)?, (vec![0, 4], 0));
+        let groups = find_duplicates(&[PathBuf::from(root)], &ScanOptions::default())?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size(), 4);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn extension_filters() -> io::Result<()> {
+        assert_eq!(
+            parse_extensions("jpg, .PNG,,gif"),
+            ["jpg", "png", "gif"].map(String::from).into()
+        );
+        let only_images = ScanOptions {
+            extensions: Some(parse_extensions("jpg,png")),
+            ..ScanOptions::default()
+        };
+        assert!(has_allowed_extension(Path::new("a.jpg"), &only_images));
+        assert!(has_allowed_extension(Path::new("b/a.JPG"), &only_images));
+        assert!(!has_allowed_extension(Path::new("a.gif"), &only_images));
+        assert!(!has_allowed_extension(Path::new("jpg"), &only_images));
+        assert!(!has_allowed_extension(Path::new(".jpg"), &only_images));
+        assert!(has_allowed_extension(
+            Path::new(".hidden.png"),
+            &only_images
+        ));
+        let no_logs = ScanOptions {
+            excluded_extensions: parse_extensions("LOG"),
+            ..ScanOptions::default()
+        };
+        assert!(!has_allowed_extension(Path::new("a.log"), &no_logs));
+        assert!(!has_allowed_extension(Path::new("a.Log"), &no_logs));
+        assert!(has_allowed_extension(Path::new("a"), &no_logs));
+        assert!(has_allowed_extension(Path::new(".log"), &no_logs));
+        /* setup */
+        let root = Path::new("test-tmp-extensions");
+        fs::create_dir(root)?;
+        for name in ["a.JPG", "b.jpg", "c.txt", "d", ".e"] {
+            fs::write(root.join(name), "meow")?;
+        }
+        /* test */
+        let groups = find_duplicates(&[PathBuf::from(root)], &only_images)?;
+        assert_eq!(groups.len(), 1);
+        let paths: Vec<&PathBuf> = groups[0].files().iter().map(|f| f.paths()[0]).collect();
+        assert_eq!(paths, [&root.join("a.JPG"), &root.join("b.jpg")]);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn annotations() {
+        /*
+            using the directory structure from the c_command tests:
+            ```
+            /
+                animal/
+                    nya
+                    mew
+                    dog/
+                        awrf
+                meow
+            ```
+        */
+        let group = |paths: &[&str]| DuplicateGroup {
+            size: 4,
+            checksum: 0,
+            files: paths
+                .iter()
+                .enumerate()
+                .map(|(id, p)| {
+                    MetaFile::new(id as u64, 4, indexset![PathBuf::from(p)], indexset![])
+                })
+                .collect(),
+        };
+        let inside = Annotation {
+            dup_inside: true,
+            dup_outside: false,
+        };
+        let outside = Annotation {
+            dup_inside: false,
+            dup_outside: true,
+        };
+        let both = Annotation {
+            dup_inside: true,
+            dup_outside: true,
+        };
+        assert_eq!(
+            group(&["/animal/nya", "/animal/mew"]).annotations(),
+            [inside, inside]
+        );
+        assert_eq!(
+            group(&["/animal/dog/awrf", "/meow"]).annotations(),
+            [outside, inside]
+        );
+        assert_eq!(inside.to_string(), "dup inside, unique outside");
+        assert_eq!(outside.to_string(), "unique inside, dup outside");
+        // in larger groups, each file is marked by whether any of the others
+        // are inside and whether any are outside
+        assert_eq!(
+            group(&["/animal/dog/awrf", "/animal/mew", "/animal/nya"]).annotations(),
+            [outside, inside, inside]
+        );
+        assert_eq!(
+            group(&["/animal/dog/awrf", "/animal/nya", "/meow"]).annotations(),
+            [outside, both, inside]
+        );
+        assert_eq!(both.to_string(), "dup inside, dup outside");
+    }
+
+    #[test]
+    fn include_hidden() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-include-hidden");
+        fs::create_dir_all(root.join(".config").join("app"))?;
+        fs::write(root.join("file"), "meow")?;
+        fs::write(root.join(".file"), "meow")?;
+        fs::write(root.join(".config").join("file"), "meow")?;
+        fs::write(root.join(".config").join("app").join("file"), "meow")?;
+        let scan = |include_hidden| -> io::Result<Vec<DuplicateGroup>> {
+            let options = ScanOptions {
+                recursive: true,
+                include_hidden,
+                ..ScanOptions::default()
+            };
+            find_duplicates(&[PathBuf::from(root)], &options)
+        };
+        /* test */
+        // only one of the copies isn't hidden or inside a hidden directory
+        assert!(scan(false)?.is_empty());
+        let groups = scan(true)?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files().len(), 4);
+        // listed paths are only left out when their own name is hidden
+        let mut acc = indexset![];
+        let mut errors = vec![];
+        let paths = [
+            root.join("file"),
+            root.join(".file"),
+            root.join(".config").join("file"),
+        ];
+        add_to_file_list(
+            &mut acc,
+            paths.clone(),
+            &ScanOptions::default(),
+            &mut errors,
+        );
+        let listed: Vec<&PathBuf> = acc.iter().flat_map(|f| f.paths()).collect();
+        assert_eq!(listed, [&paths[0], &paths[2]]);
+        assert!(errors.is_empty());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn respects_gitignore() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-gitignore");
+        fs::create_dir_all(root.join("src"))?;
+        fs::create_dir_all(root.join("target"))?;
+        fs::write(
+            root.join(".gitignore"),
+            "target/
+*.bak
+",
+        )?;
+        fs::write(root.join("src").join("main.rs"), "meow")?;
+        fs::write(root.join("src").join("main.rs.bak"), "meow")?;
+        fs::write(root.join("target").join("main"), "meow")?;
+        fs::write(root.join("copy.rs"), "meow")?;
+        let scan = |respect_gitignore| -> io::Result<Vec<PathBuf>> {
+            let options = ScanOptions {
+                recursive: true,
+                respect_gitignore,
+                ..ScanOptions::default()
+            };
+            let groups = find_duplicates(&[PathBuf::from(root)], &options)?;
+            assert_eq!(groups.len(), 1);
+            Ok(groups[0]
+                .files()
+                .iter()
+                .map(|f| f.paths()[0].clone())
+                .collect())
+        };
+        /* test */
+        assert_eq!(scan(false)?.len(), 4);
+        assert_eq!(
+            scan(true)?,
+            [root.join("copy.rs"), root.join("src").join("main.rs")]
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn checksum_cache() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-checksum-cache");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meow")?;
+        fs::write(root.join("b"), "meow")?;
+        let options = ScanOptions::default();
+        let scan = |cache: &mut ChecksumCache| -> io::Result<usize> {
+            let mut errors = vec![];
+            let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+            let progress = ProgressBar::hidden();
+            let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+            Ok(find_dups(sizewise_dups, &options, &mut errors, Some(cache), &progress).len())
+        };
+        /* test */
+        let mut cache = ChecksumCache::default();
+        assert_eq!(scan(&mut cache)?, 1);
+        assert_eq!(cache.len(), 2);
+        // cached checksums are used in place of reading the file, so
+        // changing the contents without changing the size or mtime of `a`
+        // goes unnoticed
+        let a = fs::File::options().write(true).open(root.join("a"))?;
+        let mtime = a.metadata()?.modified()?;
+        fs::write(root.join("a"), "nyaa")?;
+        a.set_modified(mtime)?;
+        assert_eq!(scan(&mut cache)?, 1);
+        // until the mtime changes
+        a.set_modified(mtime + std::time::Duration::from_secs(1))?;
+        assert_eq!(scan(&mut cache)?, 0);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn checksum_cache_blockwise() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-checksum-cache-blockwise");
+        fs::create_dir(root)?;
+        let mut contents = vec![0; BLOCKWISE_MIN_SIZE as usize];
+        fs::write(root.join("a"), &contents)?;
+        *contents.last_mut().unwrap() = 1;
+        fs::write(root.join("b"), &contents)?;
+        let options = ScanOptions::default();
+        let mut errors = vec![];
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let progress = ProgressBar::hidden();
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        /* test */
+        // both files are cached with the same checksum, but big files are
+        // still compared byte by byte
+        let mut cache = ChecksumCache::default();
+        for f in &sizewise_dups[&BLOCKWISE_MIN_SIZE] {
+            cache.insert(f.primary(), Fingerprint::from_stat(f.stat()).unwrap(), 1);
+        }
+        let dups = find_dups(
+            sizewise_dups.clone(),
+            &options,
+            &mut errors,
+            Some(&mut cache),
+            &progress,
+        );
+        assert!(dups.is_empty());
+        // once b has changed, it's no longer cached, so it's compared with a
+        let b = fs::File::options().write(true).open(root.join("b"))?;
+        let mtime = b.metadata()?.modified()?;
+        fs::write(root.join("b"), vec![0; BLOCKWISE_MIN_SIZE as usize])?;
+        b.set_modified(mtime + std::time::Duration::from_secs(1))?;
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        let dups = find_dups(
+            sizewise_dups,
+            &options,
+            &mut errors,
+            Some(&mut cache),
+            &progress,
+        );
+        assert_eq!(dups.len(), 1);
+        assert!(errors.is_empty());
+        // the checksums of the files which were compared in full are cached
+        assert_eq!(cache.len(), 2);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn block_size() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-block-size");
+        fs::create_dir(root)?;
+        let contents: Vec<u8> = (0..300_000u32).map(|n| (n * 7 % 251) as u8).collect();
+        let (a, b) = (root.join("a"), root.join("b"));
+        fs::write(&a, &contents)?;
+        fs::write(&b, &contents)?;
+        let size = contents.len() as u64;
+        let checksum = adler32(&contents[..])?;
+        /* test */
+        // including sizes which don't divide the file, or are bigger than it
+        for block_size in [1, 4096, 5000, 64 * 1024, 1 << 20] {
+            assert_eq!(
+                calc_streamed_file_checksum(&a, size, size, block_size)?,
+                checksum
+            );
+            assert_eq!(
+                calc_streamed_file_checksum(&a, size, 100_000, block_size)?,
+                adler32(&contents[..100_000])?
+            );
+            let files: HashSet<MetaFile> = [&a, &b]
+                .into_iter()
+                .enumerate()
+                .map(|(idx, p)| MetaFile::from_id_and_path(idx as u64, size, p.clone()))
+                .collect();
+            let groups = find_blockwise_dups(
+                files,
+                size,
+                &ScanOptions {
+                    block_size: Some(block_size),
+                    ..ScanOptions::default()
+                },
+                &mut vec![],
+                &ProgressBar::hidden(),
+            );
+            assert_eq!(groups.len(), 1);
+            assert_eq!(groups[0].0, checksum);
+        }
+        for block_size in [None, Some(MIN_BLOCK_SIZE), Some(MAX_BLOCK_SIZE)] {
+            let options = ScanOptions {
+                block_size,
+                ..ScanOptions::default()
+            };
+            let groups = find_duplicates(&[root.to_path_buf()], &options)?;
+            assert_eq!(groups[0].checksum(), checksum);
+        }
+        // files which change size are still caught
+        assert!(calc_streamed_file_checksum(&a, size + 1, size + 1, 4096).is_err());
+        assert!(calc_streamed_file_checksum(&a, size - 1, size - 1, 4096).is_err());
+        assert_eq!(clamp_block_size(1), MIN_BLOCK_SIZE);
+        assert_eq!(clamp_block_size(1 << 16), 1 << 16);
+        assert_eq!(clamp_block_size(u64::MAX), MAX_BLOCK_SIZE);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn hardlinks() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-hardlinks");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meowmeow")?;
+        fs::hard_link(root.join("a"), root.join("a-link1"))?;
+        fs::hard_link(root.join("a"), root.join("a-link2"))?;
+        fs::write(root.join("b"), "meowmeow")?;
+        /* test */
+        let groups = find_duplicates(&[root.to_path_buf()], &ScanOptions::default())?;
+        assert_eq!(groups.len(), 1);
+        let group = &groups[0];
+        // the links are paths of one file, and only the copy takes up space
+        assert_eq!(group.files().len(), 2);
+        assert_eq!(group.hardlink_count(), 2);
+        assert_eq!(group.reclaimable_space(), 8);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn no_size_filter() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-no-size-filter");
+        fs::create_dir_all(root.join("nested"))?;
+        for (name, contents) in [
+            ("a1", &b"meow"[..]),
+            ("nested/a2", b"meow"),
+            ("b1", b"nyaa!"),
+            ("b2", b"nyaa!"),
+            ("b3", b"nyaa!"),
+            ("c", b"purr"),
+            ("d", &[1; 2000]),
+            ("e1", &[2; 3000]),
+            ("nested/e2", &[2; 3000]),
+            ("empty", b""),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        // each group as its size and sorted paths, in order
+        let groups = |no_size_filter: bool| -> io::Result<Vec<(u64, Vec<PathBuf>)>> {
+            let options = ScanOptions {
+                recursive: true,
+                no_size_filter,
+                ..ScanOptions::default()
+            };
+            let mut groups: Vec<(u64, Vec<PathBuf>)> =
+                find_duplicates(&[root.to_path_buf()], &options)?
+                    .iter()
+                    .map(|g| {
+                        let mut paths: Vec<PathBuf> =
+                            g.files().iter().map(|f| f.primary().clone()).collect();
+                        paths.sort();
+                        (g.size(), paths)
+                    })
+                    .collect();
+            groups.sort();
+            Ok(groups)
+        };
+        /* test */
+        let filtered = groups(false)?;
+        assert_eq!(filtered.len(), 3);
+        assert_eq!(groups(true)?, filtered);
+        // every file with a size is read, including the ones on their own
+        let options = ScanOptions {
+            no_size_filter: true,
+            ..ScanOptions::default()
+        };
+        let mut errors = vec![];
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let (sizewise_dups, empty_count) =
+            find_sizewise_dups(file_list, &options, &ProgressBar::hidden());
+        assert_eq!(empty_count, 1);
+        assert_eq!(sizewise_dups.values().map(HashSet::len).sum::<usize>(), 7);
+        let progress = ProgressBar::hidden();
+        find_dups(sizewise_dups, &options, &mut errors, None, &progress);
+        assert!(errors.is_empty());
+        assert_eq!(progress.position(), 7);
+        // a file whose size changes after the files were grouped by size is
+        // only matched up with its copy when files of every size are
+        // compared with each other
+        fs::write(root.join("f"), "meowmeow")?;
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        fs::write(root.join("f"), "purr")?;
+        let has_f = |files: &HashSet<MetaFile>| files.iter().any(|f| f.primary().ends_with("f"));
+        let sized = ScanOptions::default();
+        let dups = find_dups(sizewise_dups.clone(), &sized, &mut errors, None, &progress);
+        assert!(!dups.iter().any(|(_, _, files)| has_f(files)));
+        let dups = find_dups(sizewise_dups, &options, &mut errors, None, &progress);
+        let (size, _, files) = dups.iter().find(|(_, _, files)| has_f(files)).unwrap();
+        assert_eq!((*size, files.len()), (4, 2));
+        assert!(errors.is_empty());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn max_open_files() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-max-open-files");
+        fs::create_dir(root)?;
+        // 20 pairs of files, all of the same size
+        for n in 0..20 {
+            for copy in ["a", "b"] {
+                fs::write(root.join(format!("{n}{copy}")), format!("meow{n:02}"))?;
+            }
+        }
+        /* test */
+        for threads in [1, 4] {
+            let options = ScanOptions {
+                open_files: OpenFileLimit::new(2),
+                threads,
+                ..ScanOptions::default()
+            };
+            let groups = find_duplicates(&[root.to_path_buf()], &options)?;
+            assert_eq!(groups.len(), 20);
+            assert!(groups.iter().all(|g| g.files().len() == 2));
+        }
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn digest() {
+        assert_eq!(Digest(0).to_string(), "adler32:00000000");
+        assert_eq!(Digest(0x1a2b).to_string(), "adler32:00001a2b");
+        assert_eq!(Digest(u32::MAX).to_string(), "adler32:ffffffff");
+        let checksum = adler32(&b"meow"[..]).unwrap();
+        assert_eq!(
+            Digest(checksum).to_string(),
+            format!("adler32:{checksum:08x}")
+        );
+    }
+
+    #[test]
+    fn analyze() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-analyze");
+        fs::create_dir(root)?;
+        for name in ["a1", "a2"] {
+            fs::write(root.join(name), "meow")?;
+        }
+        for name in ["b1", "b2", "b3"] {
+            fs::write(root.join(name), vec![0u8; 2000])?;
+        }
+        fs::write(root.join("c"), "purr!")?;
+        let options = ScanOptions::default();
+        let mut errors = vec![];
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &ProgressBar::hidden());
+        /* test */
+        let analysis = analyze_sizes(&sizewise_dups, 1);
+        assert_eq!(analysis.group_count, 2);
+        assert_eq!(analysis.candidate_count, 5);
+        assert_eq!(analysis.candidate_bytes, 2 * 4 + 3 * 2000);
+        assert_eq!(
+            analysis.histogram,
+            [
+                SizeBucket {
+                    min_size: 0,
+                    file_count: 2,
+                    bytes: 8
+                },
+                SizeBucket {
+                    min_size: 1024,
+                    file_count: 3,
+                    bytes: 6000
+                },
+            ]
+        );
+        assert_eq!(analysis.largest, [(2000, 3)]);
+        assert_eq!(analyze_sizes(&SizewiseDups::new(), 10).candidate_bytes, 0);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn hash_limit() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-hash-limit");
+        fs::create_dir(root)?;
+        fs::write(root.join("a"), "meowmeow")?;
+        fs::write(root.join("b"), "meownyaa")?;
+        let scan = |options: &ScanOptions| -> io::Result<usize> {
+            let mut errors = vec![];
+            let file_list = build_file_list(&[PathBuf::from(root)], options, &mut errors)?;
+            let progress = ProgressBar::hidden();
+            let (sizewise_dups, _) = find_sizewise_dups(file_list, options, &progress);
+            let dups = find_dups(sizewise_dups, options, &mut errors, None, &progress);
+            assert!(errors.is_empty());
+            Ok(dups.len())
+        };
+        /* test */
+        assert_eq!(scan(&ScanOptions::default())?, 0);
+        // files which match up to the limit are duplicates, however they differ
+        // after it
+        for mmap in [false, true] {
+            let options = ScanOptions {
+                hash_limit: Some(4),
+                mmap,
+                ..Default::default()
+            };
+            assert_eq!(scan(&options)?, 1);
+        }
+        // and a limit past the end of the files compares them in full
+        let options = ScanOptions {
+            hash_limit: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(scan(&options)?, 0);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn mmap_matches_read() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-mmap");
+        fs::create_dir(root)?;
+        for idx in 0..8 {
+            fs::write(root.join(format!("file{idx}")), format!("meow{}", idx % 3))?;
+        }
+        fs::write(root.join("empty1"), "")?;
+        fs::write(root.join("empty2"), "")?;
+        /* test */
+        let scan = |mmap| -> io::Result<Vec<(u32, Vec<PathBuf>)>> {
+            let options = ScanOptions {
+                mmap,
+                include_empty: true,
+                ..ScanOptions::default()
+            };
+            let groups = find_duplicates(&[PathBuf::from(root)], &options)?;
+            Ok(groups
+                .iter()
+                .map(|g| {
+                    let paths = g.files().iter().map(|f| f.paths()[0].clone()).collect();
+                    (g.checksum(), paths)
+                })
+                .collect())
+        };
+        let read = scan(false)?;
+        assert_eq!(read.len(), 4);
+        assert_eq!(scan(true)?, read);
+        // files which no longer have the size they were grouped by are skipped
+        let file0 = root.join("file0");
+        assert_eq!(
+            calc_mapped_file_checksum(&file0, 5, 5)?,
+            Some(adler32(&b"meow0"[..])?)
+        );
+        assert!(calc_mapped_file_checksum(&file0, 6, 6).is_err());
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_as_duplicates() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-symlinks-as-duplicates");
+        let scan = root.join("scan");
+        fs::create_dir_all(&scan)?;
+        fs::write(scan.join("a"), "meow")?;
+        fs::write(scan.join("b"), "meow")?;
+        fs::write(scan.join("c"), "nyaa")?;
+        fs::write(root.join("outside"), "nyaa")?;
+        std::os::unix::fs::symlink("a", scan.join("a-link"))?;
+        std::os::unix::fs::symlink("../outside", scan.join("outside-link"))?;
+        /* test */
+        let scan_with = |symlinks_as_duplicates| -> io::Result<Vec<String>> {
+            let options = ScanOptions {
+                symlinks_as_duplicates,
+                ..ScanOptions::default()
+            };
+            let groups = find_duplicates(std::slice::from_ref(&scan), &options)?;
+            Ok(groups
+                .iter()
+                .flat_map(|g| g.files())
+                .map(|f| f.to_string())
+                .collect())
+        };
+        let a = format!("{:?}", scan.join("a").to_string_lossy());
+        let a_link = format!("{:?}", scan.join("a-link").to_string_lossy());
+        let b = format!("{:?}", scan.join("b").to_string_lossy());
+        // the link to a file outside of the scan isn't read, so `c` has no
+        // duplicates either way
+        assert_eq!(scan_with(false)?, [a.clone(), b.clone()]);
+        assert_eq!(scan_with(true)?, [format!("{a} (aka symlink {a_link})"), b]);
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn sampled_comparison() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-sampled");
+        fs::create_dir(root)?;
+        // with 4 byte blocks, the sampled blocks are bytes 0..4, 6..10 and
+        // 12..16
+        for (name, contents) in [
+            ("same1", "meow--meow--meow"),
+            ("same2", "meow--meow--meow"),
+            ("unsampled", "meowXXmeowXXmeow"),
+            ("middle", "meow--nyaa--meow"),
+            ("small1", "meow"),
+            ("small2", "meow"),
+            ("small-unique", "nyaa"),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        let options = ScanOptions::default();
+        let mut errors = vec![];
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let progress = ProgressBar::hidden();
+        let (sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        let read_stats = ReadStats::new();
+        /* test */
+        let groups = find_probable_dups(
+            sizewise_dups,
+            4,
+            &Interrupt::new(),
+            &read_stats,
+            &mut errors,
+            &progress,
+        );
+        assert!(errors.is_empty());
+        // three blocks of each of the bigger files, and all of each of the
+        // smaller ones
+        let sampled = read_stats.sampled();
+        assert_eq!((sampled.files(), sampled.bytes()), (7, 4 * 12 + 3 * 4));
+        let paths: Vec<Vec<PathBuf>> = groups
+            .iter()
+            .map(|g| g.files().iter().map(|f| f.paths()[0].clone()).collect())
+            .collect();
+        // files which only differ outside of the sampled blocks are
+        // probable duplicates, while small files are read in full
+        assert_eq!(
+            paths,
+            [
+                vec![
+                    root.join("same1"),
+                    root.join("same2"),
+                    root.join("unsampled")
+                ],
+                vec![root.join("small1"), root.join("small2")],
+            ]
+        );
+        assert_eq!(groups[1].signature(), adler32(&b"meow"[..])?);
+        // only groups of big files are sampled
+        let mut sizewise_dups = HashMap::from([
+            (SAMPLE_MIN_SIZE - 1, HashSet::new()),
+            (SAMPLE_MIN_SIZE, HashSet::new()),
+        ]);
+        let sampled = split_off_sampled(&mut sizewise_dups);
+        assert_eq!(sampled.keys().collect::<Vec<_>>(), [&SAMPLE_MIN_SIZE]);
+        assert_eq!(
+            sizewise_dups.keys().collect::<Vec<_>>(),
+            [&(SAMPLE_MIN_SIZE - 1)]
+        );
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+
+    #[test]
+    fn blockwise_comparison() -> io::Result<()> {
+        /* setup */
+        let root = Path::new("test-tmp-blockwise");
+        fs::create_dir(root)?;
+        for (name, contents) in [
+            ("same1", "meowmeowmeow"),
+            ("same2", "meowmeowmeow"),
+            ("same3", "meowmeowmeow"),
+            ("first1", "nyaameowmeow"),
+            ("first2", "nyaameowmeow"),
+            ("first-unique", "mrrpmeowmeow"),
+            ("last-unique", "meowmeowmrrp"),
+        ] {
+            fs::write(root.join(name), contents)?;
+        }
+        let options = ScanOptions::default();
+        let mut errors = vec![];
+        let file_list = build_file_list(&[PathBuf::from(root)], &options, &mut errors)?;
+        let progress = ProgressBar::hidden();
+        let (mut sizewise_dups, _) = find_sizewise_dups(file_list, &options, &progress);
+        let files = sizewise_dups.remove(&12).unwrap();
+        let meta_files = |paths: &[PathBuf]| -> HashSet<MetaFile> {
+            paths
+                .iter()
+                .enumerate()
+                .map(|(idx, p)| MetaFile::from_id_and_path(idx as u64, 12, p.clone()))
+                .collect()
+        };
+        let blockwise = ScanOptions {
+            block_size: Some(4),
+            ..ScanOptions::default()
+        };
+        /* test */
+        let mut groups: Vec<(u32, Vec<PathBuf>)> =
+            find_blockwise_dups(files, 12, &blockwise, &mut errors, &progress)
+                .into_iter()
+                .map(|(checksum, files)| {
+                    let mut paths: Vec<PathBuf> =
+                        files.iter().map(|f| f.paths()[0].clone()).collect();
+                    paths.sort();
+                    (checksum, paths)
+                })
+                .collect();
+        groups.sort_by(|(_, a), (_, b)| a.cmp(b));
+        assert!(errors.is_empty());
+        // files which differ in the first or the last block are only grouped
+        // with the files they match in full
+        assert_eq!(
+            groups,
+            [
+                (
+                    adler32(&b"nyaameowmeow"[..])?,
+                    vec![root.join("first1"), root.join("first2")]
+                ),
+                (
+                    adler32(&b"meowmeowmeow"[..])?,
+                    vec![root.join("same1"), root.join("same2"), root.join("same3")]
+                ),
+            ]
+        );
+        // files are only read up to the block they're found to differ in
+        let compared = blockwise.read_stats.compared();
+        assert_eq!(
+            (compared.files(), compared.bytes()),
+            (7, 7 * 4 + 6 * 4 + 6 * 4)
+        );
+        // files are compared the same way when none of them can be kept open
+        let files = meta_files(&[root.join("same1"), root.join("same2"), root.join("first1")]);
+        let groups = find_blockwise_dups(
+            files,
+            12,
+            &ScanOptions {
+                block_size: Some(4),
+                open_files: OpenFileLimit::new(1),
+                ..ScanOptions::default()
+            },
+            &mut errors,
+            &progress,
+        );
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1.len(), 2);
+        // files which only differ past the limit are grouped together
+        let files = meta_files(&[root.join("same1"), root.join("last-unique")]);
+        let groups = find_blockwise_dups(
+            files,
+            12,
+            &ScanOptions {
+                hash_limit: Some(8),
+                block_size: Some(4),
+                ..ScanOptions::default()
+            },
+            &mut errors,
+            &progress,
+        );
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1.len(), 2);
+        // and a file which changes size is left out
+        fs::write(root.join("same3"), "meowmeowmeowmeow")?;
+        let files = meta_files(&[root.join("same1"), root.join("same2"), root.join("same3")]);
+        let groups = find_blockwise_dups(
+            files,
+            12,
+            &ScanOptions {
+                block_size: Some(5),
+                ..ScanOptions::default()
+            },
+            &mut errors,
+            &progress,
+        );
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].1.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, root.join("same3"));
+        /* cleanup */
+        fs::remove_dir_all(root)
+    }
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn run(args: &[&str]) -> io::Result<Option<i32>> {
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    Ok(output.status.code())
+}
+
+#[test]
+fn confirmation() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-confirmation");
+    fs::create_dir(root)?;
+    fs::write(root.join("a"), "meow")?;
+    fs::write(root.join("b"), "meow")?;
+    let root_str = root.to_str().unwrap();
+    /* test */
+    // stdin isn't a terminal, so the changes can't be confirmed
+    for action in ["--delete", "--hardlink", "--reflink"] {
+        assert_eq!(run(&["-q", action, root_str])?, Some(2));
+        assert_eq!(fs::read_dir(root)?.count(), 2);
+        assert_eq!(fs::read(root.join("b"))?, b"meow");
+    }
+    assert_eq!(run(&["-q", "--delete", "--dry-run", root_str])?, Some(1));
+    assert!(root.join("b").exists());
+    assert_eq!(run(&["-q", "--yes", root_str])?, Some(2));
+    assert_eq!(run(&["-q", "--delete", "--yes", root_str])?, Some(1));
+    assert!(root.join("a").exists());
+    assert!(!root.join("b").exists());
+    // and with no duplicates left, there's nothing to confirm
+    assert_eq!(run(&["-q", "--delete", root_str])?, Some(0));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+fn run_in(dir: &Path, args: &[&str]) -> io::Result<Option<i32>> {
+    let status = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .current_dir(dir)
+        .output()?
+        .status;
+    Ok(status.code())
+}
+
+#[test]
+fn args() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-args");
+    fs::create_dir_all(root.join("dups").join("nested"))?;
+    fs::write(root.join("dups").join("a"), "meow")?;
+    fs::write(root.join("dups").join("nested").join("b"), "meow")?;
+    // a directory which looks like a flag
+    fs::create_dir(root.join("-rq"))?;
+    let run = |args: &[&str]| run_in(root, args);
+    /* test */
+    assert_eq!(run(&["-q", "dups"])?, Some(0));
+    // combined short flags
+    assert_eq!(run(&["-rq", "dups"])?, Some(1));
+    assert_eq!(run(&["-qr0", "dups"])?, Some(1));
+    assert_eq!(run(&["-rqx", "dups"])?, Some(2));
+    // --flag=value, and the same flag with a separate value
+    assert_eq!(run(&["-rq", "--min-size=5", "dups"])?, Some(0));
+    assert_eq!(run(&["-rq", "--min-size", "5", "dups"])?, Some(0));
+    assert_eq!(
+        run(&["-rq", "--sort=path", "--keep=last", "dups"])?,
+        Some(1)
+    );
+    assert_eq!(run(&["-rq", "--sort=nonsense", "dups"])?, Some(2));
+    assert_eq!(run(&["-rq", "--recursive=yes", "dups"])?, Some(2));
+    // the value of a flag isn't split, even if it looks like flags
+    assert_eq!(run(&["-rq", "--exclude", "-rq", "dups"])?, Some(1));
+    // everything after -- is a directory
+    assert_eq!(run(&["-q", "--", "-rq", "dups"])?, Some(0));
+    assert_eq!(run(&["-q", "--", "-r", "dups"])?, Some(2));
+    assert_eq!(run(&["-q", "--"])?, Some(2));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
+
+#[test]
+fn end_of_flags() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-end-of-flags");
+    let dir = root.join("-r");
+    fs::create_dir_all(dir.join("nested"))?;
+    fs::write(dir.join("a"), "meow")?;
+    fs::write(dir.join("b"), "meow")?;
+    fs::write(dir.join("nested").join("c"), "purr")?;
+    fs::write(dir.join("nested").join("d"), "purr")?;
+    /* test */
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(["--csv", "--", "-r"])
+        .current_dir(root)
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    // -r is scanned, but not recursively
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-r/a"), "{stdout}");
+    assert!(!stdout.contains("nested"), "{stdout}");
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_input() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-symlinked-input");
+    let dir = root.join("data");
+    fs::create_dir_all(dir.join("nested"))?;
+    fs::write(dir.join("a"), "meow")?;
+    fs::write(dir.join("nested").join("b"), "meow")?;
+    std::os::unix::fs::symlink("data", root.join("link"))?;
+    let canonical = dir.canonicalize()?;
+    /* test */
+    // the link is followed without --follow-symlinks, even with a trailing
+    // slash or --one-file-system, and its files are listed under the
+    // directory it points to
+    for link in ["link", "link/"] {
+        for args in [&["-r"][..], &["-r", "--one-file-system"]] {
+            let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+                .args(args)
+                .args(["--csv", link])
+                .current_dir(root)
+                .output()?;
+            assert_eq!(output.status.code(), Some(1));
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let listed = |p: &Path| stdout.contains(p.to_str().unwrap());
+            assert!(listed(&canonical.join("a")), "{stdout}");
+            assert!(listed(&canonical.join("nested").join("b")), "{stdout}");
+        }
+    }
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+// the key=value pairs of each phase printed by --bench
+fn phases(args: &[&str]) -> io::Result<Vec<Vec<(String, String)>>> {
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| line.strip_prefix("bench: "))
+        .map(|line| {
+            line.split(' ')
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap();
+                    (key.to_string(), value.to_string())
+                })
+                .collect()
+        })
+        .collect())
+}
+
+#[test]
+fn bench() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-bench");
+    fs::create_dir(root)?;
+    fs::write(root.join("a"), "meow")?;
+    fs::write(root.join("b"), "meow")?;
+    fs::write(root.join("c"), "nyaa!")?;
+    let root_str = root.to_str().unwrap();
+    /* test */
+    assert!(phases(&["-q", root_str])?.is_empty());
+    let expected = [
+        ("walk", "3", "13"),
+        ("size-group", "2", "8"),
+        ("compare", "0", "0"),
+        ("hash", "2", "8"),
+        ("sample", "0", "0"),
+    ];
+    for args in [
+        &["-q", "--bench", root_str][..],
+        &["--bench", "-u", root_str],
+    ] {
+        let phases = phases(args)?;
+        assert_eq!(phases.len(), expected.len());
+        for (phase, (name, files, bytes)) in phases.iter().zip(expected) {
+            let keys: Vec<&str> = phase.iter().map(|(key, _)| key.as_str()).collect();
+            assert_eq!(keys, ["phase", "files", "bytes", "micros"]);
+            assert_eq!(phase[0].1, name);
+            assert_eq!(phase[1].1, files);
+            assert_eq!(phase[2].1, bytes);
+            assert!(phase[3].1.parse::<u128>().is_ok());
+        }
+    }
+    // files which are compared block by block are only read up to the block
+    // they differ in, rather than counted in full
+    let size = 16 << 20;
+    let mut contents = vec![0; size];
+    fs::write(root.join("big1"), &contents)?;
+    contents[0] = 1;
+    fs::write(root.join("big2"), &contents)?;
+    let phases = phases(&["-q", "--bench", "--block-size", "4K", root_str])?;
+    let compare = phases.iter().find(|phase| phase[0].1 == "compare").unwrap();
+    assert_eq!(
+        (compare[1].1.as_str(), compare[2].1.as_str()),
+        ("2", "8192")
+    );
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
@@ -0,0 +1,34 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+fn stdout(args: &[&str]) -> io::Result<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn color() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-color");
+    fs::create_dir(root)?;
+    fs::write(root.join("a"), "meow")?;
+    fs::write(root.join("b"), "meow")?;
+    let root_str = root.to_str().unwrap();
+    /* test */
+    // output isn't a terminal here, so `auto` doesn't color it
+    let plain = stdout(&["-q", root_str])?;
+    assert!(!plain.contains('\x1b'));
+    assert_eq!(stdout(&["-q", "--color", "never", root_str])?, plain);
+    assert_eq!(stdout(&["-q", "--color", "auto", root_str])?, plain);
+    let colored = stdout(&["-q", "--color", "always", root_str])?;
+    let path = |name| format!("{:?}", root.join(name).to_string_lossy());
+    // the file which would be kept is green, and its copies yellow
+    assert!(colored.contains(&format!("\x1b[32m{}\x1b[0m", path("a"))));
+    assert!(colored.contains(&format!("\x1b[33m{}\x1b[0m", path("b"))));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
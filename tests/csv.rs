@@ -0,0 +1,60 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use adler32::adler32;
+
+#[test]
+fn csv() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-csv");
+    fs::create_dir(root)?;
+    let tricky = root.join("a, \"quoted\" name");
+    fs::write(&tricky, "meow")?;
+    fs::write(root.join("b"), "meow")?;
+    fs::write(root.join("c"), "nyaa!")?;
+    fs::write(root.join("d"), "nyaa!")?;
+    /* test */
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(["--csv", root.to_str().unwrap()])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    let mut reader = csv::Reader::from_reader(&output.stdout[..]);
+    assert_eq!(
+        reader.headers()?.iter().collect::<Vec<_>>(),
+        [
+            "group",
+            "checksum",
+            "path",
+            "size",
+            "is_primary",
+            "is_symlink"
+        ]
+    );
+    let rows: Vec<Vec<String>> = reader
+        .records()
+        .map(|row| Ok(row?.iter().map(String::from).collect()))
+        .collect::<io::Result<_>>()?;
+    let meow = adler32("meow".as_bytes())?.to_string();
+    let nyaa = adler32("nyaa!".as_bytes())?.to_string();
+    let row = |group: &str, checksum: &str, path: &Path, size: &str, is_primary: &str| {
+        let path = path.to_str().unwrap();
+        [group, checksum, path, size, is_primary, "false"]
+            .map(String::from)
+            .to_vec()
+    };
+    // the larger group is listed first, and the path with a comma and
+    // quotes in it comes back out as it went in
+    assert_eq!(
+        rows,
+        [
+            row("1", &nyaa, &root.join("c"), "5", "true"),
+            row("1", &nyaa, &root.join("d"), "5", "false"),
+            row("2", &meow, &tricky, "4", "true"),
+            row("2", &meow, &root.join("b"), "4", "false"),
+        ]
+    );
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+fn run(args: &[&str]) -> io::Result<Option<i32>> {
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()?;
+    Ok(output.status.code())
+}
+
+#[test]
+fn exit_codes() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-exit-codes");
+    fs::create_dir_all(root.join("dups"))?;
+    fs::create_dir_all(root.join("uniques"))?;
+    fs::write(root.join("dups").join("a"), "meow")?;
+    fs::write(root.join("dups").join("b"), "meow")?;
+    fs::write(root.join("uniques").join("a"), "meow")?;
+    fs::write(root.join("uniques").join("b"), "nyaa")?;
+    fs::write(root.join("uniques").join(".a"), "meow")?;
+    let dups = root.join("dups");
+    let uniques = root.join("uniques");
+    let (dups, uniques) = (dups.to_str().unwrap(), uniques.to_str().unwrap());
+    /* test */
+    assert_eq!(run(&["-q", uniques])?, Some(0));
+    assert_eq!(run(&["-q", dups])?, Some(1));
+    // --all is still accepted, though every group is listed without it
+    assert_eq!(run(&["-q", "--all", dups])?, Some(1));
+    assert_eq!(run(&["-q", "--include-hidden", uniques])?, Some(1));
+    assert_eq!(run(&["-q", "-r", root.to_str().unwrap()])?, Some(1));
+    let excludes = root.join("excludes");
+    fs::write(&excludes, "# leaves a on its own\n\nb\n")?;
+    let excludes = excludes.to_str().unwrap();
+    assert_eq!(run(&["-q", "--exclude-from", excludes, dups])?, Some(0));
+    assert_eq!(
+        run(&["-q", "--exclude-from", "no-such-file", dups])?,
+        Some(2)
+    );
+    // dups and uniques share a, but uniques has no duplicates of its own
+    assert_eq!(run(&["-q", "--set-a", uniques, "--set-b", dups])?, Some(1));
+    assert_eq!(
+        run(&["-q", "--set-a", uniques, "--set-b", uniques])?,
+        Some(2)
+    );
+    assert_eq!(
+        run(&["-q", "--set-a", root.to_str().unwrap(), "--set-b", dups])?,
+        Some(2)
+    );
+    assert_eq!(run(&["-q", "--set-a", dups])?, Some(2));
+    assert_eq!(run(&["-q", "--max-open-files", "1", dups])?, Some(1));
+    assert_eq!(run(&["-q", "--max-open-files=0", dups])?, Some(2));
+    assert_eq!(run(&["-q", "--checkpoint"])?, Some(2));
+    assert_eq!(
+        run(&["-q", "--checkpoint", "cp", "--no-size-filter", dups])?,
+        Some(2)
+    );
+    assert_eq!(
+        run(&["-q", "--checkpoint", "cp", "--limit", "1", dups])?,
+        Some(2)
+    );
+    // --analyze stops before finding any duplicates
+    assert_eq!(run(&["-q", "--analyze", dups])?, Some(0));
+    assert_eq!(run(&["-q", "--analyze", "--json", dups])?, Some(0));
+    assert_eq!(run(&["-q", "--json", dups])?, Some(2));
+    assert_eq!(run(&["-q", "--analyze", "--delete", dups])?, Some(2));
+    assert_eq!(run(&["-q", "--interactive", dups])?, Some(2));
+    assert_eq!(run(&["-q", "--save-decisions", "d", dups])?, Some(2));
+    let decisions = root.join("decisions");
+    let decisions = decisions.to_str().unwrap();
+    assert_eq!(run(&["-q", "--apply-decisions", decisions, dups])?, Some(2));
+    assert_eq!(run(&["-q", "--apply-decisions", decisions])?, Some(2));
+    let saved = [
+        "-q",
+        "--delete",
+        "--dry-run",
+        "--save-decisions",
+        decisions,
+        dups,
+    ];
+    assert_eq!(run(&saved)?, Some(1));
+    assert_eq!(run(&["-q", "-y", "--apply-decisions", decisions])?, Some(0));
+    assert!(!root.join("dups").join("b").exists());
+    // b is gone, so its group no longer matches the decisions
+    assert_eq!(run(&["-q", "-y", "--apply-decisions", decisions])?, Some(1));
+    assert!(root.join("dups").join("a").exists());
+    fs::write(root.join("dups").join("b"), "meow")?;
+    assert_eq!(run(&["-q", "--no-such-flag", dups])?, Some(2));
+    assert_eq!(run(&["-q"])?, Some(2));
+    assert_eq!(run(&["-h"])?, Some(0));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
+
+// paths which aren't valid UTF-8 can't be written to csv or json, so
+// they're an error rather than being written lossily
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths() -> io::Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    /* setup */
+    let root = Path::new("test-tmp-non-utf8-paths");
+    fs::create_dir(root)?;
+    fs::write(root.join("a"), "meow")?;
+    fs::write(root.join(OsStr::from_bytes(b"\xff")), "meow")?;
+    let decisions = root.join("decisions");
+    let (root, decisions) = (root.to_str().unwrap(), decisions.to_str().unwrap());
+    /* test */
+    assert_eq!(run(&["-q", root])?, Some(1));
+    assert_eq!(run(&["--csv", root])?, Some(2));
+    assert_eq!(run(&["--json-stream", root])?, Some(2));
+    let saved = [
+        "-q",
+        "--delete",
+        "--dry-run",
+        "--save-decisions",
+        decisions,
+        root,
+    ];
+    assert_eq!(run(&saved)?, Some(2));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+// a group of duplicates as its checksum and size, and the paths in it.
+type Group = (u32, u64, BTreeSet<String>);
+
+fn run(args: &[&str]) -> io::Result<(Option<i32>, String)> {
+    let output = Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()?;
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 output");
+    Ok((output.status.code(), stdout))
+}
+
+// the value of `"key":` in `line`, up to the next `,` or `}`.
+fn number_field(line: &str, key: &str) -> u64 {
+    let start = line.find(&format!("\"{key}\":")).expect(key) + key.len() + 3;
+    let len = line[start..].find([',', '}']).unwrap();
+    line[start..start + len].parse().unwrap()
+}
+
+// every `"path":"..."` in `line`, unescaped.
+fn paths(line: &str) -> BTreeSet<String> {
+    let mut paths = BTreeSet::new();
+    for (start, _) in line.match_indices("\"path\":\"") {
+        let mut path = String::new();
+        let mut chars = line[start + 8..].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => path.push(chars.next().unwrap()),
+                c => path.push(c),
+            }
+        }
+        paths.insert(path);
+    }
+    paths
+}
+
+#[test]
+fn json_stream() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-json-stream");
+    fs::create_dir(root)?;
+    fs::write(root.join("a \"quoted\" \\ name"), "meow")?;
+    fs::write(root.join("b"), "meow")?;
+    fs::write(root.join("c"), "nyaa!")?;
+    fs::write(root.join("d"), "nyaa!")?;
+    fs::write(root.join("e"), "nyaa!")?;
+    fs::write(root.join("f"), "purr")?;
+    let dir = root.to_str().unwrap();
+    /* test */
+    let (code, stream) = run(&["--json-stream", dir])?;
+    assert_eq!(code, Some(1));
+    let lines: Vec<&str> = stream.lines().collect();
+    assert_eq!(lines.len(), 2);
+    let mut streamed: Vec<Group> = vec![];
+    for (idx, line) in lines.iter().enumerate() {
+        assert!(line.starts_with('{') && line.ends_with('}'), "{line}");
+        assert_eq!(number_field(line, "group"), idx as u64 + 1);
+        let checksum = number_field(line, "checksum") as u32;
+        streamed.push((checksum, number_field(line, "size"), paths(line)));
+    }
+    // the same groups as the batch report, whatever order they were found in
+    let (_, batch) = run(&["--csv", dir])?;
+    let mut reader = csv::Reader::from_reader(batch.as_bytes());
+    let mut batch: BTreeMap<String, Group> = BTreeMap::new();
+    for row in reader.records() {
+        let row = row?;
+        let group = batch.entry(row[0].to_string()).or_insert_with(|| {
+            (
+                row[1].parse().unwrap(),
+                row[3].parse().unwrap(),
+                BTreeSet::new(),
+            )
+        });
+        group.2.insert(row[2].to_string());
+    }
+    let batch: BTreeSet<Group> = batch.into_values().collect();
+    assert_eq!(streamed.into_iter().collect::<BTreeSet<_>>(), batch);
+    let (code, _) = run(&["--json-stream", "--delete", dir])?;
+    assert_eq!(code, Some(2));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
@@ -0,0 +1,41 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> io::Result<Output> {
+    Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()
+}
+
+#[test]
+fn output_relative() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-output-relative");
+    let (photos, backup) = (root.join("photos"), root.join("backup"));
+    fs::create_dir_all(photos.join("2023"))?;
+    fs::create_dir_all(&backup)?;
+    fs::write(photos.join("2023").join("a.jpg"), "meow")?;
+    fs::write(backup.join("a.jpg"), "meow")?;
+    let (photos, backup) = (photos.to_str().unwrap(), backup.to_str().unwrap());
+    /* test */
+    let output = run(&["-r", "--output-relative", photos, backup])?;
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  \"2023/a.jpg\"\n"), "{stdout}");
+    assert!(stdout.contains("  \"a.jpg\"\n"), "{stdout}");
+    assert!(!stdout.contains(photos), "{stdout}");
+    // paths are printed as they were found by default
+    let output = run(&["-r", photos, backup])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!("  \"{photos}/2023/a.jpg\"\n")),
+        "{stdout}"
+    );
+    // the paths which are acted on aren't changed
+    let output = run(&["-r", "--output-relative", "--delete", photos, backup])?;
+    assert_eq!(output.status.code(), Some(2));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
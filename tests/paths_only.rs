@@ -0,0 +1,68 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> io::Result<Output> {
+    Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()
+}
+
+// the lines printed to stdout, sorted.
+fn sorted_lines(output: &Output) -> Vec<String> {
+    let mut lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn paths_only() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-paths-only");
+    fs::create_dir(root)?;
+    for (name, contents) in [
+        ("a1", "meow"),
+        ("a2", "meow"),
+        ("a3", "meow"),
+        ("b1", "nyaa!"),
+        ("b2", "nyaa!"),
+        ("c", "purr"),
+    ] {
+        fs::write(root.join(name), contents)?;
+    }
+    let dir = root.to_str().unwrap();
+    let path = |name: &str| root.join(name).to_string_lossy().into_owned();
+    /* test */
+    // the redundant copies by default, and nothing else
+    for args in [
+        &["--paths-only", dir][..],
+        &["--paths-only=remove", dir],
+        &["-q", "--paths-only", dir],
+    ] {
+        let output = run(args)?;
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(sorted_lines(&output), [path("a2"), path("a3"), path("b2")]);
+        assert!(output.stderr.is_empty());
+    }
+    // or one file per group, chosen by --keep
+    let output = run(&["--paths-only=keep", dir])?;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(sorted_lines(&output), [path("a1"), path("b1")]);
+    let output = run(&["--paths-only=keep", "--keep", "last", dir])?;
+    assert_eq!(sorted_lines(&output), [path("a3"), path("b2")]);
+    let output = run(&["--paths-only=remove", "--keep", "last", dir])?;
+    assert_eq!(sorted_lines(&output), [path("a1"), path("a2"), path("b1")]);
+    for args in [
+        &["--paths-only=all", dir][..],
+        &["--paths-only", "--csv", dir],
+        &["--paths-only", "--delete", dir],
+    ] {
+        assert_eq!(run(args)?.status.code(), Some(2));
+    }
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
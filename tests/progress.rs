@@ -0,0 +1,41 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> io::Result<Output> {
+    Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()
+}
+
+// the report and summary counts. progress and timings go to stderr
+fn summary(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn progress_interval() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-progress-interval");
+    fs::create_dir(root)?;
+    for idx in 0..200 {
+        fs::write(root.join(format!("{idx}")), format!("meow{}", idx % 20))?;
+    }
+    let root_str = root.to_str().unwrap();
+    /* test */
+    let default = run(&["-v", root_str])?;
+    assert_eq!(default.status.code(), Some(1));
+    assert!(summary(&default).contains("Found 20 duplicates."));
+    for interval in ["1", "1000", "60000"] {
+        let throttled = run(&["-v", "--progress-interval", interval, root_str])?;
+        assert_eq!(throttled.status.code(), Some(1));
+        assert_eq!(summary(&throttled), summary(&default));
+    }
+    for invalid in ["0", "-5", "soon"] {
+        let output = run(&["--progress-interval", invalid, root_str])?;
+        assert_eq!(output.status.code(), Some(2));
+    }
+    /* cleanup */
+    fs::remove_dir_all(root)
+}
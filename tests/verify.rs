@@ -0,0 +1,57 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> io::Result<Output> {
+    Command::new(env!("CARGO_BIN_EXE_find-duplicates"))
+        .args(args)
+        .output()
+}
+
+#[test]
+fn verify() -> io::Result<()> {
+    /* setup */
+    let root = Path::new("test-tmp-verify-report");
+    let dir = root.join("files");
+    fs::create_dir_all(&dir)?;
+    for (name, contents) in [("a", "meow"), ("b", "meow"), ("c", "nyaa!"), ("d", "nyaa!")] {
+        fs::write(dir.join(name), contents)?;
+    }
+    let report = root.join("report.csv");
+    let output = run(&["--csv", dir.to_str().unwrap()])?;
+    fs::write(&report, output.stdout)?;
+    let report = report.to_str().unwrap();
+    /* test */
+    let output = run(&["verify", report])?;
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "2 of 2 groups are still duplicates.\n");
+    // the files are edited after the report was written
+    fs::write(dir.join("b"), "mrrp")?;
+    fs::remove_file(dir.join("d"))?;
+    let output = run(&["verify", report])?;
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.starts_with("0 of 2 groups are still duplicates.\n"),
+        "{stdout}"
+    );
+    let path = |name| format!("{:?}", dir.join(name).to_str().unwrap());
+    assert!(
+        stdout.contains(&format!("  changed {}\n", path("b"))),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("  unchanged {}\n", path("a"))),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(&format!("  gone {}\n", path("d"))),
+        "{stdout}"
+    );
+    assert_eq!(run(&["verify"])?.status.code(), Some(2));
+    assert_eq!(run(&["verify", "no-such-report"])?.status.code(), Some(2));
+    /* cleanup */
+    fs::remove_dir_all(root)
+}